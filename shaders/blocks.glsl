@@ -0,0 +1,15 @@
+#pragma once
+
+// Per-frame data shared by every program, uploaded once per frame from main.rs
+layout (std140) uniform Camera
+{
+    mat4 view;
+    mat4 projection;
+    vec3 viewPos;
+};
+
+layout (std140) uniform Light
+{
+    vec3 lightPos;
+    vec3 lightColor;
+};
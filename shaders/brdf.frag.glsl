@@ -0,0 +1,58 @@
+#version 330 core
+in vec2 TexCoords;
+
+out vec4 FragColor;
+
+uniform int sampleCount;
+
+#include "ibl.glsl"
+
+float geometrySchlickGGX(float NdotV, float roughness)
+{
+    // k is remapped differently for image based lighting than for point lights
+    float k = roughness * roughness / 2.0;
+    return NdotV / (NdotV * (1.0 - k) + k);
+}
+
+float geometrySmith(float NdotV, float NdotL, float roughness)
+{
+    return geometrySchlickGGX(NdotV, roughness) * geometrySchlickGGX(NdotL, roughness);
+}
+
+// The scale and bias applied to F0 by the split sum approximation
+vec2 integrateBRDF(float NdotV, float roughness)
+{
+    vec3 view = vec3(sqrt(1.0 - NdotV * NdotV), 0.0, NdotV);
+    vec3 normal = vec3(0.0, 0.0, 1.0);
+
+    uint count = uint(sampleCount);
+    float scale = 0.0;
+    float bias = 0.0;
+
+    for (uint i = 0u; i < count; ++i)
+    {
+        vec3 halfway = importanceSampleGGX(hammersley(i, count), normal, roughness);
+        vec3 light = normalize(2.0 * dot(view, halfway) * halfway - view);
+
+        float NdotL = max(light.z, 0.0);
+        float NdotH = max(halfway.z, 0.0);
+        float VdotH = max(dot(view, halfway), 0.0);
+
+        if (NdotL > 0.0)
+        {
+            float G = geometrySmith(NdotV, NdotL, roughness);
+            float visibility = (G * VdotH) / (NdotH * NdotV);
+            float fresnel = pow(1.0 - VdotH, 5.0);
+
+            scale += (1.0 - fresnel) * visibility;
+            bias += fresnel * visibility;
+        }
+    }
+
+    return vec2(scale, bias) / float(count);
+}
+
+void main()
+{
+    FragColor = vec4(integrateBRDF(TexCoords.x, TexCoords.y), 0.0, 1.0);
+}
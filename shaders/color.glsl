@@ -0,0 +1,43 @@
+#pragma once
+
+// Lighting is done in linear space. Colors are authored in sRGB and converted with the exact
+// piecewise sRGB transfer function, the same one an sRGB framebuffer encodes with in hardware
+// (SRGB_FRAMEBUFFER). Defining GAMMA switches both directions to a plain power curve with that
+// exponent instead.
+#ifdef GAMMA
+vec3 toLinear(vec3 color)
+{
+    return pow(max(color, vec3(0.0)), vec3(GAMMA));
+}
+
+vec3 fromLinear(vec3 color)
+{
+    return pow(max(color, vec3(0.0)), vec3(1.0 / GAMMA));
+}
+#else
+vec3 toLinear(vec3 color)
+{
+    vec3 low = color / 12.92;
+    vec3 high = pow((max(color, vec3(0.0)) + 0.055) / 1.055, vec3(2.4));
+
+    return mix(low, high, step(vec3(0.04045), color));
+}
+
+vec3 fromLinear(vec3 color)
+{
+    vec3 low = color * 12.92;
+    vec3 high = 1.055 * pow(max(color, vec3(0.0)), vec3(1.0 / 2.4)) - 0.055;
+
+    return mix(low, high, step(vec3(0.0031308), color));
+}
+#endif
+
+// Encodes a linear color for the framebuffer, unless the framebuffer does it itself
+vec3 toOutput(vec3 color)
+{
+#ifdef SRGB_FRAMEBUFFER
+    return color;
+#else
+    return fromLinear(color);
+#endif
+}
@@ -6,8 +6,8 @@ out vec3 FragPos;
 out vec3 Normal;
 
 uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+
+#include "blocks.glsl"
 
 void main()
 {
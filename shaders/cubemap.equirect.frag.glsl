@@ -0,0 +1,21 @@
+#version 330 core
+in vec3 LocalPos;
+
+out vec4 FragColor;
+
+uniform sampler2D equirectangularMap;
+
+#include "ibl.glsl"
+
+// Longitude maps to x and latitude to y, with the top row of the image pointing up
+vec2 sampleSphericalMap(vec3 direction)
+{
+    vec2 uv = vec2(atan(direction.z, direction.x), -asin(direction.y));
+    return uv / vec2(2.0 * PI, PI) + 0.5;
+}
+
+void main()
+{
+    vec2 uv = sampleSphericalMap(normalize(LocalPos));
+    FragColor = vec4(texture(equirectangularMap, uv).rgb, 1.0);
+}
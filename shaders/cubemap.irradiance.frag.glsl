@@ -0,0 +1,35 @@
+#version 330 core
+in vec3 LocalPos;
+
+out vec4 FragColor;
+
+uniform samplerCube environmentMap;
+
+#include "ibl.glsl"
+
+void main()
+{
+    // The cosine weighted average of the hemisphere around the normal
+    vec3 normal = normalize(LocalPos);
+    vec3 up = abs(normal.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(0.0, 0.0, 1.0);
+    vec3 right = normalize(cross(up, normal));
+    up = cross(normal, right);
+
+    const float sampleDelta = 0.025;
+    vec3 irradiance = vec3(0.0);
+    float sampleCount = 0.0;
+
+    for (float phi = 0.0; phi < 2.0 * PI; phi += sampleDelta)
+    {
+        for (float theta = 0.0; theta < 0.5 * PI; theta += sampleDelta)
+        {
+            vec3 tangentSample = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            vec3 direction = mat3(right, up, normal) * tangentSample;
+
+            irradiance += texture(environmentMap, direction).rgb * cos(theta) * sin(theta);
+            sampleCount += 1.0;
+        }
+    }
+
+    FragColor = vec4(PI * irradiance / sampleCount, 1.0);
+}
@@ -0,0 +1,47 @@
+#version 330 core
+in vec3 LocalPos;
+
+out vec4 FragColor;
+
+uniform samplerCube environmentMap;
+uniform float roughness;
+// Face size of the environment map's first level
+uniform float resolution;
+uniform int sampleCount;
+
+#include "ibl.glsl"
+
+void main()
+{
+    // Assume the view and reflection directions both equal the normal
+    vec3 normal = normalize(LocalPos);
+    vec3 view = normal;
+
+    uint count = uint(sampleCount);
+    vec3 color = vec3(0.0);
+    float totalWeight = 0.0;
+
+    for (uint i = 0u; i < count; ++i)
+    {
+        vec3 halfway = importanceSampleGGX(hammersley(i, count), normal, roughness);
+        vec3 light = normalize(2.0 * dot(view, halfway) * halfway - view);
+
+        float NdotL = max(dot(normal, light), 0.0);
+        if (NdotL > 0.0)
+        {
+            // Read unlikely directions from a blurrier level so bright spots do not alias
+            float NdotH = max(dot(normal, halfway), 0.0);
+            float HdotV = max(dot(halfway, view), 0.0);
+            float pdf = distributionGGX(NdotH, roughness) * NdotH / (4.0 * HdotV) + 0.0001;
+
+            float texelSolidAngle = 4.0 * PI / (6.0 * resolution * resolution);
+            float sampleSolidAngle = 1.0 / (float(count) * pdf + 0.0001);
+            float level = roughness == 0.0 ? 0.0 : 0.5 * log2(sampleSolidAngle / texelSolidAngle);
+
+            color += textureLod(environmentMap, light, level).rgb * NdotL;
+            totalWeight += NdotL;
+        }
+    }
+
+    FragColor = vec4(color / totalWeight, 1.0);
+}
@@ -0,0 +1,48 @@
+#pragma once
+
+// Helpers for precomputing image based lighting
+
+const float PI = 3.14159265359;
+
+// Van der Corput radical inverse, mirrors the bits around the decimal point
+float radicalInverse(uint bits)
+{
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return float(bits) * 2.3283064365386963e-10;
+}
+
+// The i-th point of a low discrepancy sequence of `count` points in [0, 1)^2
+vec2 hammersley(uint i, uint count)
+{
+    return vec2(float(i) / float(count), radicalInverse(i));
+}
+
+// Maps a point of the unit square to a half vector around the normal, distributed by GGX
+vec3 importanceSampleGGX(vec2 point, vec3 normal, float roughness)
+{
+    float a = roughness * roughness;
+
+    float phi = 2.0 * PI * point.x;
+    float cosTheta = sqrt((1.0 - point.y) / (1.0 + (a * a - 1.0) * point.y));
+    float sinTheta = sqrt(1.0 - cosTheta * cosTheta);
+
+    vec3 up = abs(normal.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, normal));
+    vec3 bitangent = cross(normal, tangent);
+
+    vec3 halfway = vec3(cos(phi) * sinTheta, sin(phi) * sinTheta, cosTheta);
+    return normalize(mat3(tangent, bitangent, normal) * halfway);
+}
+
+float distributionGGX(float NdotH, float roughness)
+{
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float denominator = NdotH * NdotH * (a2 - 1.0) + 1.0;
+
+    return a2 / (PI * denominator * denominator);
+}
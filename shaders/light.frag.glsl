@@ -1,7 +1,9 @@
 #version 330 core
 out vec4 FragColor;
 
+#include "color.glsl"
+
 void main()
 {
-    FragColor = vec4(1.0);
+    FragColor = vec4(toOutput(vec3(1.0)), 1.0);
 }
\ No newline at end of file
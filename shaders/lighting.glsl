@@ -0,0 +1,33 @@
+#pragma once
+
+// Phong (or Blinn-Phong when BLINN is defined) lighting for a single point light
+vec3 calculatePhong(vec3 normal, vec3 fragPos, vec3 viewPos, vec3 lightPos, vec3 lightColor)
+{
+    // Calculate Ambient Lighting
+    float ambientStrength = 0.1;
+    vec3 ambient = ambientStrength * lightColor;
+
+    // Calculate Diffuse Lighting
+    vec3 norm = normalize(normal);
+    vec3 lightDirection = normalize(lightPos - fragPos);
+
+    float difference = max(dot(norm, lightDirection), 0.0);
+    vec3 diffuse = difference * lightColor;
+
+    // Calculate the Specular Refections
+    float specularStrength = 0.5;
+    float specularShininess = 32;
+
+    vec3 viewDirection = normalize(viewPos - fragPos);
+
+#ifdef BLINN
+    vec3 halfwayDirection = normalize(lightDirection + viewDirection);
+    float spec = pow(max(dot(norm, halfwayDirection), 0.0), specularShininess * 2.0);
+#else
+    vec3 reflectDirection = reflect(-lightDirection, norm);
+    float spec = pow(max(dot(viewDirection, reflectDirection), 0.0), specularShininess);
+#endif
+    vec3 specular = specularStrength * spec * lightColor;
+
+    return ambient + diffuse + specular;
+}
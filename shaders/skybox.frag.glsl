@@ -0,0 +1,24 @@
+#version 330 core
+in vec3 TexCoords;
+
+out vec4 FragColor;
+
+uniform samplerCube skybox;
+#ifdef HDR
+uniform float exposure;
+#endif
+
+#include "color.glsl"
+
+void main()
+{
+    vec3 color = texture(skybox, TexCoords).rgb;
+#ifdef HDR
+    // An HDR environment goes far above 1.0, scale it by the exposure and compress it with
+    // Reinhard instead of letting it clip
+    color *= exposure;
+    color = color / (color + vec3(1.0));
+#endif
+
+    FragColor = vec4(toOutput(color), 1.0);
+}
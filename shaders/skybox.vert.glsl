@@ -0,0 +1,16 @@
+#version 330 core
+layout (location = 0) in vec3 aPos;
+
+out vec3 TexCoords;
+
+#include "blocks.glsl"
+
+void main()
+{
+    TexCoords = aPos;
+
+    // Drop the camera translation so the sky stays infinitely far away, and use w as z so the
+    // depth always ends up as 1.0
+    vec4 position = projection * mat4(mat3(view)) * vec4(aPos, 1.0);
+    gl_Position = position.xyww;
+}
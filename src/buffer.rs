@@ -0,0 +1,290 @@
+use std::{
+    marker::PhantomData,
+    mem::{self, size_of_val},
+};
+
+use gl::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum BufferTarget {
+    Array = gl::ARRAY_BUFFER,
+    ElementArray = gl::ELEMENT_ARRAY_BUFFER,
+    Uniform = gl::UNIFORM_BUFFER,
+    ShaderStorage = gl::SHADER_STORAGE_BUFFER,
+}
+
+/// How often the contents are expected to change (all `*_DRAW` usages)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum BufferUsage {
+    Static = gl::STATIC_DRAW,
+    Dynamic = gl::DYNAMIC_DRAW,
+    Stream = gl::STREAM_DRAW,
+}
+
+/// An owned GL buffer object, deleted when dropped
+pub struct Buffer {
+    id: GLuint,
+    target: BufferTarget,
+    // GL objects belong to the context's thread, so keep wrappers !Send and !Sync
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Buffer {
+    pub fn new(target: BufferTarget) -> Self {
+        let mut id: u32 = 0;
+
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+        }
+
+        Self {
+            id,
+            target,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Creates a buffer and fills it with `data`
+    pub fn with_data<T: Copy>(target: BufferTarget, data: &[T], usage: BufferUsage) -> Self {
+        let buffer = Self::new(target);
+        buffer.set_data(data, usage);
+
+        buffer
+    }
+
+    pub fn target(&self) -> BufferTarget {
+        self.target
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindBuffer(self.target as u32, self.id);
+        }
+    }
+
+    /// Reallocates the buffer to hold exactly `data`
+    pub fn set_data<T: Copy>(&self, data: &[T], usage: BufferUsage) {
+        self.bind();
+
+        unsafe {
+            gl::BufferData(
+                self.target as u32,
+                size_of_val(data) as isize,
+                data.as_ptr().cast(),
+                usage as u32,
+            );
+        }
+    }
+
+    /// Allocates `size` bytes of uninitialized storage
+    pub fn allocate(&self, size: usize, usage: BufferUsage) {
+        self.bind();
+
+        unsafe {
+            gl::BufferData(
+                self.target as u32,
+                size as isize,
+                std::ptr::null(),
+                usage as u32,
+            );
+        }
+    }
+
+    /// Overwrites part of the buffer, starting `offset` bytes in
+    pub fn set_sub_data<T: Copy>(&self, offset: usize, data: &[T]) {
+        self.bind();
+
+        unsafe {
+            gl::BufferSubData(
+                self.target as u32,
+                offset as isize,
+                size_of_val(data) as isize,
+                data.as_ptr().cast(),
+            );
+        }
+    }
+
+    pub unsafe fn id(&self) -> GLuint {
+        self.id
+    }
+
+    /// Gives up ownership of the buffer without deleting it
+    pub fn leak(mut self) -> GLuint {
+        mem::take(&mut self.id)
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        if self.id != 0 {
+            unsafe { gl::DeleteBuffers(1, &self.id) };
+        }
+    }
+}
+
+/// The scalar type of a vertex attribute as stored in the buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum AttributeType {
+    Float = gl::FLOAT,
+    Int = gl::INT,
+    UnsignedInt = gl::UNSIGNED_INT,
+}
+
+impl AttributeType {
+    pub fn size(&self) -> usize {
+        4
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexAttribute {
+    pub location: GLuint,
+    pub type_: AttributeType,
+    pub components: GLint,
+    /// Offset from the start of a vertex in bytes
+    pub offset: usize,
+}
+
+/// Describes how interleaved vertices are laid out in a buffer
+///
+/// Attributes are packed in the order they are added.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VertexLayout {
+    attributes: Vec<VertexAttribute>,
+    stride: usize,
+}
+
+impl VertexLayout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn attribute(mut self, location: GLuint, type_: AttributeType, components: GLint) -> Self {
+        self.attributes.push(VertexAttribute {
+            location,
+            type_,
+            components,
+            offset: self.stride,
+        });
+        self.stride += type_.size() * components as usize;
+        self
+    }
+
+    /// Shorthand for a float attribute with `components` components
+    pub fn float(self, location: GLuint, components: GLint) -> Self {
+        self.attribute(location, AttributeType::Float, components)
+    }
+
+    /// Skips `bytes` bytes of vertex data that this layout does not use
+    pub fn padding(mut self, bytes: usize) -> Self {
+        self.stride += bytes;
+        self
+    }
+
+    pub fn attributes(&self) -> &[VertexAttribute] {
+        &self.attributes
+    }
+
+    /// Size of one vertex in bytes
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+}
+
+/// An owned vertex array object, deleted when dropped
+pub struct VertexArray {
+    id: GLuint,
+    // Every attribute enabled through `set_layout`, so it can be checked against shaders
+    attributes: Vec<VertexAttribute>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl VertexArray {
+    pub fn new() -> Self {
+        let mut id: u32 = 0;
+
+        unsafe {
+            gl::GenVertexArrays(1, &mut id);
+        }
+
+        Self {
+            id,
+            attributes: Vec::new(),
+            _not_send: PhantomData,
+        }
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindVertexArray(self.id);
+        }
+    }
+
+    /// Sources every attribute of `layout` from `buffer`
+    pub fn set_layout(&mut self, buffer: &Buffer, layout: &VertexLayout) {
+        self.bind();
+        buffer.bind();
+
+        for attribute in layout.attributes() {
+            let stride = layout.stride() as GLsizei;
+            let offset = attribute.offset as *const _;
+
+            unsafe {
+                // Integer attributes need the I variant or they are converted to floats
+                match attribute.type_ {
+                    AttributeType::Float => gl::VertexAttribPointer(
+                        attribute.location,
+                        attribute.components,
+                        attribute.type_ as u32,
+                        gl::FALSE,
+                        stride,
+                        offset,
+                    ),
+                    AttributeType::Int | AttributeType::UnsignedInt => gl::VertexAttribIPointer(
+                        attribute.location,
+                        attribute.components,
+                        attribute.type_ as u32,
+                        stride,
+                        offset,
+                    ),
+                }
+                gl::EnableVertexAttribArray(attribute.location);
+            }
+
+            self.attributes
+                .retain(|existing| existing.location != attribute.location);
+            self.attributes.push(*attribute);
+        }
+    }
+
+    /// The attributes enabled on this vertex array
+    pub fn attributes(&self) -> &[VertexAttribute] {
+        &self.attributes
+    }
+
+    pub unsafe fn id(&self) -> GLuint {
+        self.id
+    }
+
+    /// Gives up ownership of the vertex array without deleting it
+    pub fn leak(mut self) -> GLuint {
+        mem::take(&mut self.id)
+    }
+}
+
+impl Default for VertexArray {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for VertexArray {
+    fn drop(&mut self) {
+        if self.id != 0 {
+            unsafe { gl::DeleteVertexArrays(1, &self.id) };
+        }
+    }
+}
@@ -0,0 +1,491 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    fs, io,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
+
+use gl::types::*;
+
+#[derive(Debug, Clone)]
+pub struct CaptureSettings {
+    pub output_dir: PathBuf,
+    pub frame_rate: f32,
+    pub scale: f32,
+    pub frame_limit: Option<u32>,
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        Self {
+            output_dir: PathBuf::from("capture"),
+            frame_rate: 60.0,
+            scale: 1.0,
+            frame_limit: None,
+        }
+    }
+}
+
+impl CaptureSettings {
+    /// Parses `--capture <dir> [--capture-fps <n>] [--capture-scale <n>] [--capture-frames <n>]`
+    ///
+    /// Returns `None` if capturing was not requested
+    pub fn from_args<I: IntoIterator<Item = String>>(
+        args: I,
+    ) -> Result<Option<Self>, CaptureArgsError> {
+        let mut settings = Self::default();
+        let mut enabled = false;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next().ok_or_else(|| CaptureArgsError::MissingValue {
+                    argument: arg.clone(),
+                })
+            };
+
+            match arg.as_str() {
+                "--capture" => {
+                    enabled = true;
+                    settings.output_dir = PathBuf::from(value()?);
+                }
+                "--capture-fps" => settings.frame_rate = parse_positive(&arg, value()?)?,
+                "--capture-scale" => settings.scale = parse_positive(&arg, value()?)?,
+                "--capture-frames" => settings.frame_limit = Some(parse_count(&arg, value()?)?),
+                _ => {}
+            }
+        }
+
+        Ok(enabled.then_some(settings))
+    }
+}
+
+// Parses a frame rate or scale, which must be finite and greater than zero
+fn parse_positive(argument: &str, value: String) -> Result<f32, CaptureArgsError> {
+    match value.parse::<f32>() {
+        Ok(parsed) if parsed.is_finite() && parsed > 0.0 => Ok(parsed),
+        _ => Err(invalid_value(argument, value, "a positive number")),
+    }
+}
+
+fn parse_count(argument: &str, value: String) -> Result<u32, CaptureArgsError> {
+    value
+        .parse()
+        .map_err(|_| invalid_value(argument, value, "a whole number"))
+}
+
+fn invalid_value(argument: &str, value: String, expected: &'static str) -> CaptureArgsError {
+    CaptureArgsError::InvalidValue {
+        argument: argument.to_owned(),
+        value,
+        expected,
+    }
+}
+
+#[derive(Debug)]
+pub enum CaptureArgsError {
+    MissingValue {
+        argument: String,
+    },
+    InvalidValue {
+        argument: String,
+        value: String,
+        expected: &'static str,
+    },
+}
+
+impl Display for CaptureArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureArgsError::MissingValue { argument } => {
+                write!(f, "Missing value for argument: {}", argument)
+            }
+            CaptureArgsError::InvalidValue {
+                argument,
+                value,
+                expected,
+            } => write!(
+                f,
+                "Invalid value for argument {}: {} (expected {})",
+                argument, value, expected
+            ),
+        }
+    }
+}
+
+impl Error for CaptureArgsError {}
+
+#[derive(Debug)]
+pub enum CaptureError {
+    InvalidFrameRate {
+        frame_rate: f32,
+    },
+    InvalidScale {
+        scale: f32,
+    },
+    CreateDir {
+        path: PathBuf,
+        error: io::Error,
+    },
+    IncompleteFramebuffer {
+        width: u32,
+        height: u32,
+        status: GLenum,
+    },
+}
+
+impl Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureError::InvalidFrameRate { frame_rate } => {
+                write!(f, "Capture frame rate must be positive, got {}", frame_rate)
+            }
+            CaptureError::InvalidScale { scale } => {
+                write!(f, "Capture scale must be positive, got {}", scale)
+            }
+            CaptureError::CreateDir { path, error } => write!(
+                f,
+                "Could not create capture directory {}: {}",
+                path.display(),
+                error
+            ),
+            CaptureError::IncompleteFramebuffer {
+                width,
+                height,
+                status,
+            } => write!(
+                f,
+                "Capture framebuffer is incomplete ({}x{}, status 0x{:04X})",
+                width, height, status
+            ),
+        }
+    }
+}
+
+impl Error for CaptureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CaptureError::CreateDir { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Renders frames into an offscreen framebuffer at a fixed virtual frame rate and writes each one
+/// out as a numbered PNG (`frame_00000.png`, `frame_00001.png`, ...)
+pub struct FrameCapture {
+    output_dir: PathBuf,
+    frame_rate: f32,
+    frame_index: u32,
+    frame_limit: Option<u32>,
+
+    width: u32,
+    height: u32,
+
+    framebuffer: GLuint,
+    color_buffer: GLuint,
+    depth_buffer: GLuint,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl FrameCapture {
+    pub fn new(
+        settings: CaptureSettings,
+        window_width: u32,
+        window_height: u32,
+    ) -> Result<Self, CaptureError> {
+        if !(settings.frame_rate.is_finite() && settings.frame_rate > 0.0) {
+            return Err(CaptureError::InvalidFrameRate {
+                frame_rate: settings.frame_rate,
+            });
+        }
+        if !(settings.scale.is_finite() && settings.scale > 0.0) {
+            return Err(CaptureError::InvalidScale {
+                scale: settings.scale,
+            });
+        }
+
+        fs::create_dir_all(&settings.output_dir).map_err(|error| CaptureError::CreateDir {
+            path: settings.output_dir.clone(),
+            error,
+        })?;
+
+        let width = (window_width as f32 * settings.scale).round() as u32;
+        let height = (window_height as f32 * settings.scale).round() as u32;
+
+        let mut framebuffer: u32 = 0;
+        let mut color_buffer: u32 = 0;
+        let mut depth_buffer: u32 = 0;
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut framebuffer);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+
+            // Color attachment (sRGB encoded like the window when the pipeline outputs linear
+            // colors)
+            let color_format = match gl::IsEnabled(gl::FRAMEBUFFER_SRGB) {
+                gl::TRUE => gl::SRGB8_ALPHA8,
+                _ => gl::RGBA8,
+            };
+            gl::GenRenderbuffers(1, &mut color_buffer);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, color_buffer);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, color_format, width as i32, height as i32);
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::RENDERBUFFER,
+                color_buffer,
+            );
+
+            // Depth/Stencil attachment
+            gl::GenRenderbuffers(1, &mut depth_buffer);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, depth_buffer);
+            gl::RenderbufferStorage(
+                gl::RENDERBUFFER,
+                gl::DEPTH24_STENCIL8,
+                width as i32,
+                height as i32,
+            );
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_STENCIL_ATTACHMENT,
+                gl::RENDERBUFFER,
+                depth_buffer,
+            );
+
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+                gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+                gl::DeleteFramebuffers(1, &framebuffer);
+                gl::DeleteRenderbuffers(1, &color_buffer);
+                gl::DeleteRenderbuffers(1, &depth_buffer);
+
+                return Err(CaptureError::IncompleteFramebuffer {
+                    width,
+                    height,
+                    status,
+                });
+            }
+
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        Ok(Self {
+            output_dir: settings.output_dir,
+            frame_rate: settings.frame_rate,
+            frame_index: 0,
+            frame_limit: settings.frame_limit,
+            width,
+            height,
+            framebuffer,
+            color_buffer,
+            depth_buffer,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// The virtual time of the current frame in seconds
+    pub fn time(&self) -> f32 {
+        self.frame_index as f32 / self.frame_rate
+    }
+
+    pub fn frame_index(&self) -> u32 {
+        self.frame_index
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame_limit
+            .is_some_and(|frame_limit| self.frame_index >= frame_limit)
+    }
+
+    /// Redirects all rendering into the capture framebuffer
+    pub fn begin_frame(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            gl::Viewport(0, 0, self.width as i32, self.height as i32);
+        }
+    }
+
+    /// Writes the captured frame to disk and copies it to the window so it stays visible
+    pub fn end_frame(&mut self, window_width: i32, window_height: i32) {
+        let mut pixels = vec![0u8; (self.width * self.height * 4) as usize];
+
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.framebuffer);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                self.width as i32,
+                self.height as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr().cast(),
+            );
+
+            // Scale the frame down onto the default framebuffer
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+            gl::BlitFramebuffer(
+                0,
+                0,
+                self.width as i32,
+                self.height as i32,
+                0,
+                0,
+                window_width,
+                window_height,
+                gl::COLOR_BUFFER_BIT,
+                gl::LINEAR,
+            );
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::Viewport(0, 0, window_width, window_height);
+        }
+
+        let mut image = image::RgbaImage::from_raw(self.width, self.height, pixels)
+            .expect("Capture buffer does not match the frame size");
+
+        // GL reads rows bottom to top
+        image::imageops::flip_vertical_in_place(&mut image);
+
+        let path = self.frame_path(self.frame_index);
+        image
+            .save(&path)
+            .unwrap_or_else(|_| panic!("Could not write captured frame: {}", path.display()));
+
+        self.frame_index += 1;
+    }
+
+    pub fn output_dir(&self) -> &Path {
+        &self.output_dir
+    }
+
+    fn frame_path(&self, frame_index: u32) -> PathBuf {
+        self.output_dir
+            .join(format!("frame_{:05}.png", frame_index))
+    }
+}
+
+impl Drop for FrameCapture {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.framebuffer);
+            gl::DeleteRenderbuffers(1, &self.color_buffer);
+            gl::DeleteRenderbuffers(1, &self.depth_buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<CaptureSettings>, CaptureArgsError> {
+        CaptureSettings::from_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn capture_is_off_without_capture_argument() {
+        assert!(parse(&["--capture-fps", "30"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn parses_capture_settings() {
+        let settings = parse(&[
+            "--capture",
+            "out",
+            "--capture-fps",
+            "30",
+            "--capture-frames",
+            "0",
+        ])
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(settings.output_dir, PathBuf::from("out"));
+        assert_eq!(settings.frame_rate, 30.0);
+        assert_eq!(settings.scale, 1.0);
+        assert_eq!(settings.frame_limit, Some(0));
+    }
+
+    #[test]
+    fn reports_missing_values() {
+        let error = parse(&["--capture", "out", "--capture-scale"]).unwrap_err();
+
+        assert!(matches!(
+            error,
+            CaptureArgsError::MissingValue { argument } if argument == "--capture-scale"
+        ));
+    }
+
+    #[test]
+    fn reports_invalid_values() {
+        for args in [
+            ["--capture-fps", "fast"],
+            ["--capture-fps", "0"],
+            ["--capture-scale", "-1"],
+            ["--capture-frames", "1.5"],
+        ] {
+            let error = parse(&args).unwrap_err();
+
+            assert!(
+                matches!(error, CaptureArgsError::InvalidValue { .. }),
+                "{:?}",
+                args
+            );
+        }
+    }
+
+    // The settings checks and directory creation run before any GL call
+    #[test]
+    fn new_rejects_invalid_settings() {
+        let settings = CaptureSettings {
+            frame_rate: 0.0,
+            ..CaptureSettings::default()
+        };
+        assert!(matches!(
+            FrameCapture::new(settings, 800, 600),
+            Err(CaptureError::InvalidFrameRate { .. })
+        ));
+
+        let settings = CaptureSettings {
+            scale: f32::NAN,
+            ..CaptureSettings::default()
+        };
+        assert!(matches!(
+            FrameCapture::new(settings, 800, 600),
+            Err(CaptureError::InvalidScale { .. })
+        ));
+    }
+
+    #[test]
+    fn new_reports_unwritable_capture_directory() {
+        // A directory cannot be created below a file
+        let file = std::env::temp_dir().join(format!("capture-file-{}", std::process::id()));
+        fs::write(&file, b"").unwrap();
+
+        let settings = CaptureSettings {
+            output_dir: file.join("frames"),
+            ..CaptureSettings::default()
+        };
+        let result = FrameCapture::new(settings, 800, 600);
+        fs::remove_file(&file).unwrap();
+
+        match result {
+            Err(CaptureError::CreateDir { path, .. }) => assert_eq!(path, file.join("frames")),
+            Err(error) => panic!("expected a directory error, got {}", error),
+            Ok(_) => panic!("expected a directory error"),
+        }
+    }
+}
@@ -0,0 +1,411 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    path::Path,
+    rc::Rc,
+};
+
+use gl::types::*;
+use nalgebra_glm as glm;
+
+use crate::{
+    buffer::{Buffer, BufferTarget, BufferUsage, VertexArray, VertexLayout},
+    shader::{Shader, ShaderError, ShaderLibrary, ShaderVariant},
+    skybox::CUBE_VERTICES,
+    texture::{
+        CubeFace, SamplerDescriptor, Texture, Texture2d, TextureBuilder, TextureCube, TextureError,
+        TextureFilter, TextureFormat, TextureWrap,
+    },
+};
+
+pub use cache::EnvironmentCache;
+
+mod cache;
+
+const EQUIRECT_PROGRAM: &str = "cubemap.equirect";
+const IRRADIANCE_PROGRAM: &str = "cubemap.irradiance";
+const PREFILTER_PROGRAM: &str = "cubemap.prefilter";
+const BRDF_PROGRAM: &str = "brdf";
+
+/// Registers the programs used to precompute environments with a library rooted at the shader
+/// directory
+pub fn register_shaders(library: &mut ShaderLibrary) {
+    for program in [EQUIRECT_PROGRAM, IRRADIANCE_PROGRAM, PREFILTER_PROGRAM] {
+        library.register(
+            program,
+            "cubemap.vert.glsl".to_owned(),
+            format!("{}.frag.glsl", program),
+        );
+    }
+    library.register(BRDF_PROGRAM, "brdf.vert.glsl", "brdf.frag.glsl");
+}
+
+/// Face sizes and sample counts used when precomputing an [`Environment`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EnvironmentSettings {
+    /// Face size of the cube map the equirectangular image is converted to
+    pub environment_size: u32,
+    pub irradiance_size: u32,
+    /// Face size of the first prefiltered level (roughness 0)
+    pub prefiltered_size: u32,
+    /// Number of prefiltered levels, the last one is for roughness 1
+    pub prefiltered_levels: u32,
+    /// Number of GGX samples per texel of the prefiltered map and the BRDF lookup table
+    pub sample_count: u32,
+    pub brdf_lut_size: u32,
+}
+
+impl Default for EnvironmentSettings {
+    fn default() -> Self {
+        Self {
+            environment_size: 512,
+            irradiance_size: 32,
+            prefiltered_size: 128,
+            prefiltered_levels: 5,
+            sample_count: 1024,
+            brdf_lut_size: 512,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EnvironmentError {
+    Texture(TextureError),
+    Shader(ShaderError),
+}
+
+impl Display for EnvironmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvironmentError::Texture(error) => write!(f, "{}", error),
+            EnvironmentError::Shader(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for EnvironmentError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EnvironmentError::Texture(error) => Some(error),
+            EnvironmentError::Shader(error) => Some(error),
+        }
+    }
+}
+
+impl From<TextureError> for EnvironmentError {
+    fn from(error: TextureError) -> Self {
+        EnvironmentError::Texture(error)
+    }
+}
+
+impl From<ShaderError> for EnvironmentError {
+    fn from(error: ShaderError) -> Self {
+        EnvironmentError::Shader(error)
+    }
+}
+
+/// The lighting of an HDR environment, precomputed for image based lighting
+///
+/// - `environment` is the image itself as a mipmapped cube map, e.g. for a [`Skybox`]
+/// - `irradiance` is the diffuse lighting arriving at a surface, looked up with its normal
+/// - `prefiltered` is the specular lighting, looked up with the reflection vector in the level
+///   `roughness * (levels - 1)`
+/// - `brdf_lut` holds the scale (red) and bias (green) the split sum approximation applies to F0,
+///   indexed by `(dot(N, V), roughness)`
+///
+/// [`Skybox`]: crate::skybox::Skybox
+pub struct Environment {
+    environment: Rc<TextureCube>,
+    irradiance: Rc<TextureCube>,
+    prefiltered: Rc<TextureCube>,
+    brdf_lut: Rc<Texture2d>,
+}
+
+impl Environment {
+    /// Loads an equirectangular `.hdr` or `.exr` image and precomputes its lighting
+    ///
+    /// With a cache the results are reused as long as the image and settings do not change.
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        settings: &EnvironmentSettings,
+        library: &mut ShaderLibrary,
+        cache: Option<&EnvironmentCache>,
+    ) -> Result<Self, EnvironmentError> {
+        let path = path.as_ref();
+        let cache_key = cache.and_then(|cache| cache.key(path, settings).ok());
+
+        if let (Some(cache), Some(key)) = (cache, cache_key) {
+            if let Some(environment) = cache.load(key, settings) {
+                return Ok(environment);
+            }
+        }
+
+        let equirect = TextureBuilder::new()
+            .format(TextureFormat::RGB32F)
+            .wrap(TextureWrap::ClampToEdge)
+            .filters(TextureFilter::Linear, TextureFilter::Linear)
+            .mipmaps(false)
+            .load(path)?;
+        let environment = Self::from_equirect(&equirect, settings, library)?;
+
+        if let (Some(cache), Some(key)) = (cache, cache_key) {
+            if let Err(error) = cache.store(key, &environment) {
+                eprintln!(
+                    "Warning: could not cache environment {}: {}",
+                    path.display(),
+                    error
+                );
+            }
+        }
+
+        Ok(environment)
+    }
+
+    /// Precomputes the lighting of an equirectangular image that is already uploaded
+    pub fn from_equirect(
+        equirect: &Texture2d,
+        settings: &EnvironmentSettings,
+        library: &mut ShaderLibrary,
+    ) -> Result<Self, EnvironmentError> {
+        let _state = SavedRenderState::save();
+        let renderer = CubeRenderer::new();
+
+        // Convert the image to a cube map, with mipmaps for prefiltering to sample from
+        let environment = allocate_cube(settings.environment_size, u32::MAX);
+        renderer.render(library, EQUIRECT_PROGRAM, &environment, 0, |shader| {
+            shader.set_texture("equirectangularMap", equirect)
+        })?;
+        environment.generate_mipmaps();
+
+        let irradiance = allocate_cube(settings.irradiance_size, 1);
+        renderer.render(library, IRRADIANCE_PROGRAM, &irradiance, 0, |shader| {
+            shader.set_texture("environmentMap", &environment)
+        })?;
+
+        let prefiltered = allocate_cube(settings.prefiltered_size, settings.prefiltered_levels);
+        for level in 0..prefiltered.levels() {
+            let roughness = level as f32 / (prefiltered.levels() - 1).max(1) as f32;
+
+            renderer.render(library, PREFILTER_PROGRAM, &prefiltered, level, |shader| {
+                shader.set_texture("environmentMap", &environment)?;
+                shader.set_uniform("roughness", roughness)?;
+                shader.set_uniform("resolution", environment.size() as f32)?;
+                shader.set_uniform("sampleCount", settings.sample_count as i32)
+            })?;
+        }
+
+        let brdf_lut = render_brdf_lut(library, settings)?;
+
+        Ok(Self {
+            environment: Rc::new(environment),
+            irradiance: Rc::new(irradiance),
+            prefiltered: Rc::new(prefiltered),
+            brdf_lut: Rc::new(brdf_lut),
+        })
+    }
+
+    pub fn environment(&self) -> &Rc<TextureCube> {
+        &self.environment
+    }
+
+    pub fn irradiance(&self) -> &Rc<TextureCube> {
+        &self.irradiance
+    }
+
+    pub fn prefiltered(&self) -> &Rc<TextureCube> {
+        &self.prefiltered
+    }
+
+    pub fn brdf_lut(&self) -> &Rc<Texture2d> {
+        &self.brdf_lut
+    }
+
+    /// Binds the precomputed maps to the `irradianceMap`, `prefilterMap` and `brdfLUT` samplers
+    pub fn apply(&self, shader: &mut Shader) -> Result<(), ShaderError> {
+        shader.set_texture("irradianceMap", self.irradiance.as_ref())?;
+        shader.set_texture("prefilterMap", self.prefiltered.as_ref())?;
+        shader.set_texture("brdfLUT", self.brdf_lut.as_ref())
+    }
+}
+
+// A half float cube map with clamped, trilinearly filtered faces
+fn allocate_cube(size: u32, levels: u32) -> TextureCube {
+    let min_filter = if levels > 1 {
+        TextureFilter::LinearMipmapLinear
+    } else {
+        TextureFilter::Linear
+    };
+    let sampler = SamplerDescriptor::default()
+        .wrap(TextureWrap::ClampToEdge)
+        .filters(min_filter, TextureFilter::Linear);
+
+    TextureCube::allocate(size, TextureFormat::RGB16F, levels, sampler)
+}
+
+fn brdf_lut_sampler() -> SamplerDescriptor {
+    SamplerDescriptor::default()
+        .wrap(TextureWrap::ClampToEdge)
+        .filters(TextureFilter::Linear, TextureFilter::Linear)
+}
+
+fn render_brdf_lut(
+    library: &mut ShaderLibrary,
+    settings: &EnvironmentSettings,
+) -> Result<Texture2d, ShaderError> {
+    let size = settings.brdf_lut_size;
+    let brdf_lut = Texture2d::allocate(size, size, TextureFormat::RGB16F, brdf_lut_sampler());
+
+    let shader = library.variant(&ShaderVariant::new(BRDF_PROGRAM))?;
+    shader.use_program();
+    shader.set_uniform("sampleCount", settings.sample_count as i32)?;
+
+    // The vertex shader makes up its own vertices, but core profiles still need a VAO bound
+    let vao = VertexArray::new();
+    let framebuffer = Framebuffer::new();
+
+    vao.bind();
+    unsafe {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer.0);
+        gl::FramebufferTexture2D(
+            gl::FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT0,
+            gl::TEXTURE_2D,
+            brdf_lut.id(),
+            0,
+        );
+        gl::Viewport(0, 0, size as i32, size as i32);
+        gl::DrawArrays(gl::TRIANGLES, 0, 3);
+    }
+
+    Ok(brdf_lut)
+}
+
+// Renders a program over every face of one level of a cube map
+struct CubeRenderer {
+    framebuffer: Framebuffer,
+    vao: VertexArray,
+    _vbo: Buffer,
+}
+
+impl CubeRenderer {
+    fn new() -> Self {
+        let vbo = Buffer::with_data(BufferTarget::Array, &CUBE_VERTICES, BufferUsage::Static);
+        let mut vao = VertexArray::new();
+        vao.set_layout(&vbo, &VertexLayout::new().float(0, 3));
+
+        Self {
+            framebuffer: Framebuffer::new(),
+            vao,
+            _vbo: vbo,
+        }
+    }
+
+    fn render<F>(
+        &self,
+        library: &mut ShaderLibrary,
+        program: &str,
+        target: &TextureCube,
+        level: u32,
+        set_uniforms: F,
+    ) -> Result<(), ShaderError>
+    where
+        F: FnOnce(&mut Shader) -> Result<(), ShaderError>,
+    {
+        let shader = library.variant(&ShaderVariant::new(program))?;
+        shader.use_program();
+        shader.reset_texture_units();
+        set_uniforms(shader)?;
+
+        // A 90 degree frustum through each face, looking out from the center
+        let projection = glm::perspective(1.0, f32::to_radians(90.0), 0.1, 10.0);
+        shader.set_uniform("projection", projection)?;
+
+        let size = (target.size() >> level).max(1) as i32;
+        self.vao.bind();
+
+        for face in CubeFace::ALL {
+            shader.set_uniform("view", face_view(face))?;
+
+            unsafe {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer.0);
+                gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    face as GLenum,
+                    target.id(),
+                    level as GLint,
+                );
+                gl::Viewport(0, 0, size, size);
+                gl::DrawArrays(gl::TRIANGLES, 0, 36);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// The view matrix looking from the center through a cube map face, oriented the way GL expects
+// the face's texels
+fn face_view(face: CubeFace) -> glm::Mat4 {
+    let (direction, up) = match face {
+        CubeFace::PositiveX => (glm::vec3(1.0, 0.0, 0.0), glm::vec3(0.0, -1.0, 0.0)),
+        CubeFace::NegativeX => (glm::vec3(-1.0, 0.0, 0.0), glm::vec3(0.0, -1.0, 0.0)),
+        CubeFace::PositiveY => (glm::vec3(0.0, 1.0, 0.0), glm::vec3(0.0, 0.0, 1.0)),
+        CubeFace::NegativeY => (glm::vec3(0.0, -1.0, 0.0), glm::vec3(0.0, 0.0, -1.0)),
+        CubeFace::PositiveZ => (glm::vec3(0.0, 0.0, 1.0), glm::vec3(0.0, -1.0, 0.0)),
+        CubeFace::NegativeZ => (glm::vec3(0.0, 0.0, -1.0), glm::vec3(0.0, -1.0, 0.0)),
+    };
+
+    glm::look_at(&glm::vec3(0.0, 0.0, 0.0), &direction, &up)
+}
+
+// An owned framebuffer object without attachments of its own
+struct Framebuffer(GLuint);
+
+impl Framebuffer {
+    fn new() -> Self {
+        let mut framebuffer = 0;
+        unsafe { gl::GenFramebuffers(1, &mut framebuffer) };
+
+        Self(framebuffer)
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteFramebuffers(1, &self.0) };
+    }
+}
+
+// Restores the framebuffer and viewport when precomputing is done (or fails)
+struct SavedRenderState {
+    framebuffer: GLint,
+    viewport: [GLint; 4],
+}
+
+impl SavedRenderState {
+    fn save() -> Self {
+        let mut state = Self {
+            framebuffer: 0,
+            viewport: [0; 4],
+        };
+
+        unsafe {
+            gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut state.framebuffer);
+            gl::GetIntegerv(gl::VIEWPORT, state.viewport.as_mut_ptr());
+        }
+
+        state
+    }
+}
+
+impl Drop for SavedRenderState {
+    fn drop(&mut self) {
+        let [x, y, width, height] = self.viewport;
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer as GLuint);
+            gl::Viewport(x, y, width, height);
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use gl::types::*;
+
+use super::{allocate_cube, brdf_lut_sampler, Environment, EnvironmentSettings};
+use crate::{
+    shader::Fnv1a,
+    texture::{with_unpack_alignment, CubeFace, Texture, Texture2d, TextureCube, TextureFormat},
+};
+
+// Bump whenever the file layout or the precomputation changes to invalidate old entries
+const FORMAT_VERSION: u32 = 1;
+
+/// Stores precomputed environments on disk so later runs can skip the convolutions
+///
+/// Entries are keyed by a hash of the source image and the [`EnvironmentSettings`]. A file holds
+/// the texels of every stored face and level as little-endian RGB floats: the first level of the
+/// environment (the others are regenerated), the irradiance map, every prefiltered level and
+/// finally the BRDF lookup table.
+#[derive(Debug, Clone)]
+pub struct EnvironmentCache {
+    dir: PathBuf,
+}
+
+// One face or level of a texture stored in a cache file
+struct CachedImage {
+    target: GLenum,
+    texture: GLuint,
+    image_target: GLenum,
+    level: u32,
+    size: u32,
+}
+
+impl CachedImage {
+    fn texel_count(&self) -> usize {
+        self.size as usize * self.size as usize * 3
+    }
+}
+
+impl EnvironmentCache {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_owned(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Deletes every cached environment
+    pub fn clear(&self) -> io::Result<()> {
+        match fs::remove_dir_all(&self.dir) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+            _ => Ok(()),
+        }
+    }
+
+    pub(super) fn key(&self, path: &Path, settings: &EnvironmentSettings) -> io::Result<u64> {
+        let mut hasher = Fnv1a::new();
+        hasher.write(&FORMAT_VERSION.to_le_bytes());
+
+        for value in [
+            settings.environment_size,
+            settings.irradiance_size,
+            settings.prefiltered_size,
+            settings.prefiltered_levels,
+            settings.sample_count,
+            settings.brdf_lut_size,
+        ] {
+            hasher.write(&value.to_le_bytes());
+        }
+
+        hasher.write(&fs::read(path)?);
+
+        Ok(hasher.finish())
+    }
+
+    /// Uploads a cached environment, or returns `None` if there is no usable entry
+    pub(super) fn load(&self, key: u64, settings: &EnvironmentSettings) -> Option<Environment> {
+        let path = self.path(key);
+        let data = fs::read(&path).ok()?;
+
+        let environment = Environment {
+            environment: Rc::new(allocate_cube(settings.environment_size, u32::MAX)),
+            irradiance: Rc::new(allocate_cube(settings.irradiance_size, 1)),
+            prefiltered: Rc::new(allocate_cube(
+                settings.prefiltered_size,
+                settings.prefiltered_levels,
+            )),
+            brdf_lut: Rc::new(Texture2d::allocate(
+                settings.brdf_lut_size,
+                settings.brdf_lut_size,
+                TextureFormat::RGB16F,
+                brdf_lut_sampler(),
+            )),
+        };
+
+        let images = cached_images(&environment);
+        let texel_count: usize = images.iter().map(CachedImage::texel_count).sum();
+
+        if data.len() != texel_count * 4 {
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+
+        let texels: Vec<f32> = data
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+
+        let mut offset = 0;
+        for image in &images {
+            let image_texels = &texels[offset..offset + image.texel_count()];
+            offset += image.texel_count();
+
+            unsafe {
+                gl::BindTexture(image.target, image.texture);
+                with_unpack_alignment(TextureFormat::RGB32F, image.size, || {
+                    gl::TexSubImage2D(
+                        image.image_target,
+                        image.level as GLint,
+                        0,
+                        0,
+                        image.size as GLsizei,
+                        image.size as GLsizei,
+                        gl::RGB,
+                        gl::FLOAT,
+                        image_texels.as_ptr().cast(),
+                    )
+                });
+            }
+        }
+
+        environment.environment.generate_mipmaps();
+
+        Some(environment)
+    }
+
+    /// Reads back the precomputed textures and saves them
+    pub(super) fn store(&self, key: u64, environment: &Environment) -> io::Result<()> {
+        let images = cached_images(environment);
+        let texel_count: usize = images.iter().map(CachedImage::texel_count).sum();
+
+        let mut data = Vec::with_capacity(texel_count * 4);
+        for image in &images {
+            let mut texels = vec![0.0f32; image.texel_count()];
+
+            unsafe {
+                gl::BindTexture(image.target, image.texture);
+                gl::GetTexImage(
+                    image.image_target,
+                    image.level as GLint,
+                    gl::RGB,
+                    gl::FLOAT,
+                    texels.as_mut_ptr().cast(),
+                );
+            }
+
+            data.extend(texels.iter().flat_map(|texel| texel.to_le_bytes()));
+        }
+
+        // Write to a temporary file first so a crash never leaves a truncated entry behind
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(key);
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, data)?;
+        fs::rename(temp_path, path)
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.env", key))
+    }
+}
+
+// Every image stored in a cache file, in file order
+fn cached_images(environment: &Environment) -> Vec<CachedImage> {
+    let mut images = Vec::new();
+    let mut add_cube = |cube: &TextureCube, levels: u32| {
+        for level in 0..levels {
+            for face in CubeFace::ALL {
+                images.push(CachedImage {
+                    target: gl::TEXTURE_CUBE_MAP,
+                    texture: unsafe { cube.id() },
+                    image_target: face as GLenum,
+                    level,
+                    size: (cube.size() >> level).max(1),
+                });
+            }
+        }
+    };
+
+    add_cube(&environment.environment, 1);
+    add_cube(&environment.irradiance, 1);
+    add_cube(&environment.prefiltered, environment.prefiltered.levels());
+
+    images.push(CachedImage {
+        target: gl::TEXTURE_2D,
+        texture: unsafe { environment.brdf_lut.id() },
+        image_target: gl::TEXTURE_2D,
+        level: 0,
+        size: environment.brdf_lut.width(),
+    });
+
+    images
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes a temporary source image, removed when dropped
+    struct SourceFile(PathBuf);
+
+    impl SourceFile {
+        fn new(name: &str, bytes: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "environment-{}-{}.hdr",
+                name,
+                std::process::id()
+            ));
+            fs::write(&path, bytes).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for SourceFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn key_is_stable_for_identical_inputs() {
+        let cache = EnvironmentCache::new("unused");
+        let source = SourceFile::new("stable", b"#?RADIANCE");
+        let copy = SourceFile::new("stable-copy", b"#?RADIANCE");
+        let settings = EnvironmentSettings::default();
+
+        let key = cache.key(&source.0, &settings).unwrap();
+        assert_eq!(cache.key(&source.0, &settings).unwrap(), key);
+        // Only the contents count, not where the file is
+        assert_eq!(cache.key(&copy.0, &settings).unwrap(), key);
+    }
+
+    #[test]
+    fn key_changes_with_every_setting() {
+        let cache = EnvironmentCache::new("unused");
+        let source = SourceFile::new("settings", b"#?RADIANCE");
+        let changes: [fn(&mut EnvironmentSettings); 6] = [
+            |settings| settings.environment_size *= 2,
+            |settings| settings.irradiance_size *= 2,
+            |settings| settings.prefiltered_size *= 2,
+            |settings| settings.prefiltered_levels += 1,
+            |settings| settings.sample_count *= 2,
+            |settings| settings.brdf_lut_size *= 2,
+        ];
+
+        let mut keys = vec![cache
+            .key(&source.0, &EnvironmentSettings::default())
+            .unwrap()];
+        for change in changes {
+            let mut settings = EnvironmentSettings::default();
+            change(&mut settings);
+
+            let key = cache.key(&source.0, &settings).unwrap();
+            assert!(!keys.contains(&key), "{:?}", settings);
+            keys.push(key);
+        }
+    }
+
+    #[test]
+    fn key_changes_with_the_source_bytes() {
+        let cache = EnvironmentCache::new("unused");
+        let settings = EnvironmentSettings::default();
+
+        let first = SourceFile::new("bytes", b"#?RADIANCE 1");
+        let key = cache.key(&first.0, &settings).unwrap();
+        fs::write(&first.0, b"#?RADIANCE 2").unwrap();
+
+        assert_ne!(cache.key(&first.0, &settings).unwrap(), key);
+    }
+
+    #[test]
+    fn key_fails_for_missing_sources() {
+        let cache = EnvironmentCache::new("unused");
+        let path = std::env::temp_dir().join("environment-missing.hdr");
+
+        assert!(cache.key(&path, &EnvironmentSettings::default()).is_err());
+    }
+}
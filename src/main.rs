@@ -4,22 +4,44 @@
 extern crate gl;
 extern crate glfw;
 
-use std::ffi::c_void;
-use std::mem::{size_of, size_of_val};
+use std::error::Error;
+use std::mem::size_of;
+use std::rc::Rc;
 use std::sync::RwLock;
 
 use glfw::{fail_on_errors, Window};
 use glfw::{Action, Context, Key, OpenGlProfileHint, WindowHint};
 use nalgebra_glm as glm;
 
+use buffer::{Buffer, BufferTarget, BufferUsage, VertexArray, VertexLayout};
 use camera::CameraMovement;
-use shader::Shader;
+use capture::{CaptureSettings, FrameCapture};
+use environment::{Environment, EnvironmentCache, EnvironmentSettings};
+use material::Material;
+use shader::{ProgramBinaryCache, ShaderLibrary, ShaderVariant};
+use skybox::Skybox;
+use texture::{TextureBuilder, TextureWrap};
+use uniform_buffer::UniformBuffer;
 
 use crate::camera::Camera;
 
+mod buffer;
 mod camera;
+mod capture;
+mod environment;
+mod material;
 mod shader;
+mod skybox;
 mod texture;
+mod uniform_buffer;
+
+const SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/shaders");
+const MATERIAL_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/materials");
+const SKYBOX_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/skybox.png");
+const SKYBOX_EXPOSURE: f32 = 1.0;
+const SHADER_CACHE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/shader-cache");
+const ENVIRONMENT_CACHE_DIR: &str =
+    concat!(env!("CARGO_MANIFEST_DIR"), "/target/environment-cache");
 
 const SCREEN_WIDTH: u32 = 800;
 const SCREEN_HEIGHT: u32 = 600;
@@ -70,6 +92,25 @@ const VERTICES: [f32; 216] = [
    -0.5,  0.5, -0.5,  0.0,  1.0,  0.0
 ];
 
+std140_struct! {
+    struct CameraBlock {
+        view: glm::Mat4,
+        projection: glm::Mat4,
+        view_pos: glm::Vec3,
+    }
+}
+
+std140_struct! {
+    struct LightBlock {
+        position: glm::Vec3,
+        color: glm::Vec3,
+    }
+}
+
+// Display gamma for a plain power curve in place of the exact sRGB transfer function (`None`).
+// The sRGB framebuffer always encodes with the sRGB curve, so a custom gamma turns it off.
+const GAMMA: Option<f32> = None;
+
 const CUBE_POSITION: [f32; 3] = [0.0, 0.0, 0.0];
 const LIGHT_POSITION: [f32; 3] = [1.2, 1.0, 2.0];
 
@@ -85,7 +126,10 @@ lazy_static::lazy_static! {
 static mut DELTA_TIME: f32 = 0.0;
 static mut LAST_FRAME_TIME: f32 = 0.0;
 
-fn main() {
+fn main() -> Result<(), Box<dyn Error>> {
+    // Read the capture options first so typos are reported before a window opens
+    let capture_settings = CaptureSettings::from_args(std::env::args().skip(1))?;
+
     // Initialize GLFW
     let mut glfw = glfw::init(fail_on_errors!()).unwrap();
 
@@ -93,6 +137,10 @@ fn main() {
     glfw.window_hint(WindowHint::ContextVersion(3, 3));
     glfw.window_hint(WindowHint::OpenGlProfile(OpenGlProfileHint::Core));
 
+    // Ask for an sRGB capable framebuffer so the hardware can gamma encode our output
+    let srgb_requested = GAMMA.is_none() && !std::env::args().any(|arg| arg == "--no-srgb");
+    glfw.window_hint(WindowHint::SRgbCapable(srgb_requested));
+
     #[cfg(target_os = "macos")]
     glfw.window_hint(WindowHint::OpenGlForwardCompat(true));
 
@@ -128,87 +176,126 @@ fn main() {
     window.set_cursor_pos_callback(|x, y| mouse_callback(x as f32, y as f32));
     window.set_scroll_callback(|x, y| scroll_callback(x as f32, y as f32));
 
-    // Enable Depth Testing
+    // Enable Depth Testing, and filter across the edges of cube map faces
     unsafe {
         gl::Enable(gl::DEPTH_TEST);
+        gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
     }
 
-    // Initialize the shader programs
-    let mut cube_shader = Shader::new(
-        include_str!("../shaders/cube.vert.glsl"),
-        include_str!("../shaders/cube.frag.glsl"),
-    );
-    let mut light_shader = Shader::new(
-        include_str!("../shaders/light.vert.glsl"),
-        include_str!("../shaders/light.frag.glsl"),
-    );
-
-    // Initialize Cube VAO and VBO
-    let mut cube_vao: u32 = 0;
-    let mut vbo: u32 = 0;
-    unsafe {
-        gl::GenVertexArrays(1, &mut cube_vao);
-        gl::GenBuffers(1, &mut vbo);
-
-        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-        gl::BufferData(
-            gl::ARRAY_BUFFER,
-            size_of_val(&VERTICES) as isize,
-            VERTICES.as_ptr() as *const c_void,
-            gl::STATIC_DRAW,
-        );
+    // Let the framebuffer encode linear colors to sRGB when it can, otherwise the shaders do it
+    let srgb_framebuffer = srgb_requested && enable_srgb_framebuffer();
 
-        gl::BindVertexArray(cube_vao);
+    // Create the per-frame uniform buffers (before any programs are linked so their blocks get
+    // bound automatically)
+    let mut camera_buffer = UniformBuffer::<CameraBlock>::new("Camera", 0);
+    let mut light_buffer = UniformBuffer::<LightBlock>::new("Light", 1);
 
-        // Position Attribute
-        gl::VertexAttribPointer(
-            0,
-            3,
-            gl::FLOAT,
-            gl::FALSE,
-            6 * size_of::<f32>() as i32,
-            0 as *const _,
-        );
-        gl::EnableVertexAttribArray(0);
-
-        // Normal Attribute
-        gl::VertexAttribPointer(
-            1,
-            3,
-            gl::FLOAT,
-            gl::FALSE,
-            6 * size_of::<f32>() as i32,
-            (3 * size_of::<f32>()) as *const _,
-        );
-        gl::EnableVertexAttribArray(1);
+    // Register the shader programs (variants are compiled on first use and reloaded whenever
+    // their files change)
+    let mut shaders = ShaderLibrary::new(SHADER_DIR);
+    if !std::env::args().any(|arg| arg == "--no-shader-cache") {
+        shaders.set_binary_cache(Some(ProgramBinaryCache::new(SHADER_CACHE_DIR)));
     }
-
-    // Initialize Light VAO
-    let mut light_vao: u32 = 0;
-    unsafe {
-        gl::GenVertexArrays(1, &mut light_vao);
-        gl::BindVertexArray(light_vao);
-
-        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-
-        gl::VertexAttribPointer(
-            0,
-            3,
-            gl::FLOAT,
-            gl::FALSE,
-            6 * size_of::<f32>() as i32,
-            0 as *const _,
-        );
-        gl::EnableVertexAttribArray(0);
+    if let Some(gamma) = GAMMA {
+        shaders.set_define("GAMMA", format!("{:?}", gamma));
     }
+    if srgb_framebuffer {
+        shaders.set_define("SRGB_FRAMEBUFFER", 1);
+    }
+    shaders.register("cube", "cube.vert.glsl", "cube.frag.glsl");
+    shaders.register("light", "light.vert.glsl", "light.frag.glsl");
+    shaders.register("skybox", "skybox.vert.glsl", "skybox.frag.glsl");
+    environment::register_shaders(&mut shaders);
+
+    // Load the materials the scene is drawn with (`--blinn` picks the Blinn-Phong variant of the
+    // cube's shader)
+    let cube_material_name = if std::env::args().any(|arg| arg == "--blinn") {
+        "cube-blinn"
+    } else {
+        "cube"
+    };
+    let cube_material =
+        Material::from_file(format!("{}/{}.toml", MATERIAL_DIR, cube_material_name))?;
+    let light_material = Material::from_file(format!("{}/light.toml", MATERIAL_DIR))?;
+
+    // Initialize Cube VAO and VBO (interleaved positions and normals)
+    let vbo = Buffer::with_data(BufferTarget::Array, &VERTICES, BufferUsage::Static);
+    let cube_layout = VertexLayout::new().float(0, 3).float(1, 3);
+
+    let mut cube_vao = VertexArray::new();
+    cube_vao.set_layout(&vbo, &cube_layout);
+
+    // Initialize Light VAO (positions only)
+    let light_layout = VertexLayout::new()
+        .float(0, 3)
+        .padding(3 * size_of::<f32>());
+
+    let mut light_vao = VertexArray::new();
+    light_vao.set_layout(&vbo, &light_layout);
+
+    // Load the skybox drawn behind the scene
+    let skybox_texture = TextureBuilder::new()
+        .srgb(true)
+        .wrap(TextureWrap::ClampToEdge)
+        .load_cube_or_missing(SKYBOX_PATH);
+    let mut skybox = Skybox::new(Rc::new(skybox_texture), ShaderVariant::new("skybox"));
+
+    // Precompute image based lighting for an HDR environment given with `--environment <file>`,
+    // which also replaces the skybox
+    let environment_path = std::env::args()
+        .skip_while(|arg| arg != "--environment")
+        .nth(1);
+    // No material samples the irradiance, prefiltered and BRDF maps yet, the environment is bound
+    // only to keep them on the GPU for the whole run (the skybox holds its own cube map reference)
+    let _environment = match environment_path {
+        Some(path) => {
+            let cache = EnvironmentCache::new(ENVIRONMENT_CACHE_DIR);
+            let settings = EnvironmentSettings::default();
+            let environment = Environment::load(path, &settings, &mut shaders, Some(&cache))?;
+
+            skybox.set_hdr_texture(environment.environment().clone(), SKYBOX_EXPOSURE);
+            Some(environment)
+        }
+        None => None,
+    };
+
+    // Make sure the vertex layouts match what the shaders expect
+    shaders
+        .variant(cube_material.variant())?
+        .check_vertex_array(&cube_vao)?;
+    shaders
+        .variant(light_material.variant())?
+        .check_vertex_array(&light_vao)?;
+    shaders
+        .variant(skybox.variant())?
+        .check_vertex_array(skybox.vertex_array())?;
+
+    // Set up frame capture if requested on the command line
+    let mut capture = capture_settings
+        .map(|settings| FrameCapture::new(settings, SCREEN_WIDTH, SCREEN_HEIGHT))
+        .transpose()?;
 
     // Main render loop
     while !window.should_close() {
+        // Stop once every requested frame is written (before rendering, so a limit of 0 writes
+        // nothing)
+        if let Some(capture) = capture.as_ref().filter(|capture| capture.is_finished()) {
+            println!(
+                "Captured {} frames to {}",
+                capture.frame_index(),
+                capture.output_dir().display()
+            );
+            break;
+        }
+
         // Poll for events
         glfw.poll_events();
 
-        // Calculate Frame Times
-        let current_time = glfw.get_time() as f32;
+        // Calculate Frame Times (captures advance at a fixed virtual frame rate)
+        let current_time = match &capture {
+            Some(capture) => capture.time(),
+            None => glfw.get_time() as f32,
+        };
         unsafe {
             DELTA_TIME = current_time - LAST_FRAME_TIME;
             LAST_FRAME_TIME = current_time;
@@ -217,10 +304,16 @@ fn main() {
         // Check window events
         process_input(&mut window);
 
-        // Draw the background
+        // Pick up any edited shaders
+        shaders.reload_if_changed();
+
+        if let Some(capture) = &capture {
+            capture.begin_frame();
+        }
+
+        // Clear the depth buffer (the skybox covers every pixel the scene leaves empty)
         unsafe {
-            gl::ClearColor(0.1, 0.1, 0.1, 1.0);
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            gl::Clear(gl::DEPTH_BUFFER_BIT);
         }
 
         // Create our view matrix
@@ -235,6 +328,17 @@ fn main() {
             100.0,
         );
 
+        // Upload the per-frame data shared by every program
+        camera_buffer.upload(&CameraBlock {
+            view,
+            projection,
+            view_pos: camera.position,
+        });
+        light_buffer.upload(&LightBlock {
+            position: glm::make_vec3(&LIGHT_POSITION),
+            color: glm::vec3(1.0, 1.0, 1.0),
+        });
+
         // Render the cube
         {
             // Create our model matrix
@@ -242,20 +346,12 @@ fn main() {
             model = glm::translate(&model, &glm::make_vec3(&CUBE_POSITION));
 
             // Set Shader Uniforms
-            cube_shader.use_program();
-
-            cube_shader.set_mat4("model", model);
-            cube_shader.set_mat4("view", view);
-            cube_shader.set_mat4("projection", projection);
-
-            cube_shader.set_vec3("lightPos", glm::make_vec3(&LIGHT_POSITION));
-            cube_shader.set_vec3("viewPos", camera.position);
-            cube_shader.set_vec3("objectColor", glm::vec3(1.0, 0.5, 0.31));
-            cube_shader.set_vec3("lightColor", glm::vec3(1.0, 1.0, 1.0));
+            let cube_shader = cube_material.apply(&mut shaders)?;
+            cube_shader.set_uniform("model", model)?;
 
             // Draw the cube
+            cube_vao.bind();
             unsafe {
-                gl::BindVertexArray(cube_vao);
                 gl::DrawArrays(gl::TRIANGLES, 0, 36);
             }
         }
@@ -268,22 +364,51 @@ fn main() {
             model = glm::scale(&model, &glm::vec3(0.2, 0.2, 0.2));
 
             // Set Shader Uniforms
-            light_shader.use_program();
-
-            light_shader.set_mat4("model", model);
-            light_shader.set_mat4("view", view);
-            light_shader.set_mat4("projection", projection);
+            let light_shader = light_material.apply(&mut shaders)?;
+            light_shader.set_uniform("model", model)?;
 
             // Draw the cube
+            light_vao.bind();
             unsafe {
-                gl::BindVertexArray(light_vao);
                 gl::DrawArrays(gl::TRIANGLES, 0, 36);
             }
         }
 
+        // Render the background last so it is only shaded where nothing else was drawn
+        skybox.draw(&mut shaders)?;
+
+        // Write out the captured frame
+        if let Some(capture) = &mut capture {
+            let (width, height) = window.get_framebuffer_size();
+            capture.end_frame(width, height);
+        }
+
         // Swap the front and back buffers
         window.swap_buffers();
     }
+
+    Ok(())
+}
+
+// Enables `GL_FRAMEBUFFER_SRGB` if the default framebuffer actually stores sRGB colors
+fn enable_srgb_framebuffer() -> bool {
+    let mut encoding = 0;
+
+    unsafe {
+        gl::GetFramebufferAttachmentParameteriv(
+            gl::FRAMEBUFFER,
+            gl::BACK_LEFT,
+            gl::FRAMEBUFFER_ATTACHMENT_COLOR_ENCODING,
+            &mut encoding,
+        );
+
+        if encoding as u32 == gl::SRGB {
+            gl::Enable(gl::FRAMEBUFFER_SRGB);
+            return true;
+        }
+    }
+
+    false
 }
 
 fn process_input(window: &mut Window) {
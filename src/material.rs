@@ -0,0 +1,418 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use nalgebra_glm as glm;
+use serde::Deserialize;
+
+use crate::{
+    shader::{Shader, ShaderError, ShaderLibrary, ShaderVariant},
+    texture::{Texture2d, TextureBuilder},
+};
+
+/// A uniform value stored in a material
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaterialValue {
+    Float(f32),
+    Int(i32),
+    Bool(bool),
+    Vec2(glm::Vec2),
+    Vec3(glm::Vec3),
+    Vec4(glm::Vec4),
+}
+
+impl MaterialValue {
+    fn apply(&self, shader: &mut Shader, name: &str) -> Result<(), ShaderError> {
+        match *self {
+            // Data files cannot tell `1` from `1.0`, so follow the uniform's declared type
+            MaterialValue::Int(value)
+                if shader
+                    .uniform(name)
+                    .is_some_and(|info| info.type_ == gl::FLOAT) =>
+            {
+                shader.set_uniform(name, value as f32)
+            }
+            MaterialValue::Float(value) => shader.set_uniform(name, value),
+            MaterialValue::Int(value) => shader.set_uniform(name, value),
+            MaterialValue::Bool(value) => shader.set_uniform(name, value),
+            MaterialValue::Vec2(value) => shader.set_uniform(name, value),
+            MaterialValue::Vec3(value) => shader.set_uniform(name, value),
+            MaterialValue::Vec4(value) => shader.set_uniform(name, value),
+        }
+    }
+}
+
+macro_rules! impl_from_value {
+    ($type:ty, $variant:ident) => {
+        impl From<$type> for MaterialValue {
+            fn from(value: $type) -> Self {
+                MaterialValue::$variant(value)
+            }
+        }
+    };
+}
+
+impl_from_value!(f32, Float);
+impl_from_value!(i32, Int);
+impl_from_value!(bool, Bool);
+impl_from_value!(glm::Vec2, Vec2);
+impl_from_value!(glm::Vec3, Vec3);
+impl_from_value!(glm::Vec4, Vec4);
+
+#[derive(Debug)]
+pub enum MaterialError {
+    Io { path: PathBuf, error: io::Error },
+    Parse { path: PathBuf, message: String },
+}
+
+impl Display for MaterialError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaterialError::Io { path, error } => {
+                write!(
+                    f,
+                    "Could not read material file {}: {}",
+                    path.display(),
+                    error
+                )
+            }
+            MaterialError::Parse { path, message } => {
+                write!(f, "Invalid material file {}: {}", path.display(), message)
+            }
+        }
+    }
+}
+
+impl Error for MaterialError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MaterialError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// A shader variant together with the uniform values and textures to draw with
+///
+/// Materials can be loaded from TOML files:
+///
+/// ```toml
+/// shader = "cube"
+/// flags = ["BLINN"]
+///
+/// [parameters]
+/// objectColor = [1.0, 0.5, 0.31]
+///
+/// [textures]
+/// diffuse = "../container.jpg"  # relative to the material file
+/// normal = { path = "../container_normal.png", srgb = false }
+/// ```
+///
+/// Textures are treated as sRGB encoded color unless `srgb = false` is given.
+#[derive(Clone)]
+pub struct Material {
+    variant: ShaderVariant,
+    parameters: BTreeMap<String, MaterialValue>,
+    textures: BTreeMap<String, Rc<Texture2d>>,
+}
+
+// The on-disk layout of a material file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaterialFile {
+    shader: String,
+    #[serde(default)]
+    flags: Vec<String>,
+    #[serde(default)]
+    parameters: BTreeMap<String, MaterialFileValue>,
+    #[serde(default)]
+    textures: BTreeMap<String, MaterialFileTexture>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MaterialFileValue {
+    Bool(bool),
+    Int(i32),
+    Float(f32),
+    Vector(Vec<f32>),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+enum MaterialFileTexture {
+    Path(PathBuf),
+    Table {
+        path: PathBuf,
+        #[serde(default = "default_srgb")]
+        srgb: bool,
+    },
+}
+
+// A texture named in a material file: `(name, path relative to the file, srgb)`
+type TextureReference = (String, PathBuf, bool);
+
+fn default_srgb() -> bool {
+    true
+}
+
+impl Material {
+    pub fn new(variant: ShaderVariant) -> Self {
+        Self {
+            variant,
+            parameters: BTreeMap::new(),
+            textures: BTreeMap::new(),
+        }
+    }
+
+    /// Loads a material file, along with every texture it references
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, MaterialError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|error| MaterialError::Io {
+            path: path.to_owned(),
+            error,
+        })?;
+        let (mut material, textures) =
+            Self::parse(&text).map_err(|message| MaterialError::Parse {
+                path: path.to_owned(),
+                message,
+            })?;
+
+        let directory = path.parent().unwrap_or(Path::new(""));
+        for (name, texture_path, srgb) in textures {
+            // A broken texture should not stop the rest of the scene from loading
+            let texture = TextureBuilder::new()
+                .srgb(srgb)
+                .load_or_missing(directory.join(texture_path));
+            material.textures.insert(name, Rc::new(texture));
+        }
+
+        Ok(material)
+    }
+
+    // Parses a material file into the material without its textures, and the textures it
+    // references
+    fn parse(text: &str) -> Result<(Self, Vec<TextureReference>), String> {
+        let file: MaterialFile = toml::from_str(text).map_err(|error| error.to_string())?;
+        let mut material = Self::new(ShaderVariant::new(file.shader).with_flags(file.flags));
+
+        for (name, value) in file.parameters {
+            let value = match value {
+                MaterialFileValue::Bool(value) => MaterialValue::Bool(value),
+                MaterialFileValue::Int(value) => MaterialValue::Int(value),
+                MaterialFileValue::Float(value) => MaterialValue::Float(value),
+                MaterialFileValue::Vector(values) => match values[..] {
+                    [x, y] => MaterialValue::Vec2(glm::vec2(x, y)),
+                    [x, y, z] => MaterialValue::Vec3(glm::vec3(x, y, z)),
+                    [x, y, z, w] => MaterialValue::Vec4(glm::vec4(x, y, z, w)),
+                    _ => {
+                        return Err(format!(
+                            "parameter {} has {} components, expected 2 to 4",
+                            name,
+                            values.len()
+                        ))
+                    }
+                },
+            };
+
+            material.parameters.insert(name, value);
+        }
+
+        let textures = file
+            .textures
+            .into_iter()
+            .map(|(name, texture)| match texture {
+                MaterialFileTexture::Path(path) => (name, path, true),
+                MaterialFileTexture::Table { path, srgb } => (name, path, srgb),
+            })
+            .collect();
+
+        Ok((material, textures))
+    }
+
+    pub fn variant(&self) -> &ShaderVariant {
+        &self.variant
+    }
+
+    pub fn set_variant(&mut self, variant: ShaderVariant) {
+        self.variant = variant;
+    }
+
+    pub fn set_parameter<N: Into<String>, V: Into<MaterialValue>>(&mut self, name: N, value: V) {
+        self.parameters.insert(name.into(), value.into());
+    }
+
+    pub fn parameter(&self, name: &str) -> Option<MaterialValue> {
+        self.parameters.get(name).copied()
+    }
+
+    pub fn parameters(&self) -> impl Iterator<Item = (&str, &MaterialValue)> {
+        self.parameters
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    /// Sets the texture of a sampler, textures can be shared between materials
+    pub fn set_texture<N: Into<String>>(&mut self, name: N, texture: Rc<Texture2d>) {
+        self.textures.insert(name.into(), texture);
+    }
+
+    pub fn texture(&self, name: &str) -> Option<&Rc<Texture2d>> {
+        self.textures.get(name)
+    }
+
+    /// Makes the material's program current and uploads its parameters and textures
+    ///
+    /// Returns the program so per-object uniforms (like `model`) can be set before drawing.
+    pub fn apply<'a>(&self, library: &'a mut ShaderLibrary) -> Result<&'a mut Shader, ShaderError> {
+        let shader = library.variant(&self.variant)?;
+        shader.use_program();
+        shader.reset_texture_units();
+
+        for (name, value) in &self.parameters {
+            value.apply(shader, name)?;
+        }
+
+        for (name, texture) in &self.textures {
+            shader.set_texture(name, texture.as_ref())?;
+        }
+
+        Ok(shader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<(Material, Vec<TextureReference>), String> {
+        Material::parse(text)
+    }
+
+    #[test]
+    fn parses_vectors_by_arity() {
+        let (material, _) = parse(
+            r#"
+            shader = "cube"
+
+            [parameters]
+            offset = [1.0, 2.0]
+            color = [1.0, 0.5, 0.31]
+            tint = [0.1, 0.2, 0.3, 0.4]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            material.parameter("offset"),
+            Some(MaterialValue::Vec2(glm::vec2(1.0, 2.0)))
+        );
+        assert_eq!(
+            material.parameter("color"),
+            Some(MaterialValue::Vec3(glm::vec3(1.0, 0.5, 0.31)))
+        );
+        assert_eq!(
+            material.parameter("tint"),
+            Some(MaterialValue::Vec4(glm::vec4(0.1, 0.2, 0.3, 0.4)))
+        );
+    }
+
+    #[test]
+    fn rejects_vectors_of_other_sizes() {
+        for (vector, components) in [("[1.0]", 1), ("[1.0, 2.0, 3.0, 4.0, 5.0]", 5)] {
+            let text = format!("shader = \"cube\"\n[parameters]\nweights = {}", vector);
+
+            assert_eq!(
+                parse(&text).err().unwrap(),
+                format!(
+                    "parameter weights has {} components, expected 2 to 4",
+                    components
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_ints_next_to_floats() {
+        let (material, _) = parse(
+            r#"
+            shader = "cube"
+            flags = ["BLINN"]
+
+            [parameters]
+            shininess = 32
+            ambient = 0.1
+            lit = true
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(material.variant().flags().collect::<Vec<_>>(), ["BLINN"]);
+        assert_eq!(
+            material.parameter("shininess"),
+            Some(MaterialValue::Int(32))
+        );
+        assert_eq!(
+            material.parameter("ambient"),
+            Some(MaterialValue::Float(0.1))
+        );
+        assert_eq!(material.parameter("lit"), Some(MaterialValue::Bool(true)));
+    }
+
+    #[test]
+    fn parses_texture_paths_and_tables() {
+        let (_, textures) = parse(
+            r#"
+            shader = "cube"
+
+            [textures]
+            diffuse = "../container.jpg"
+            normal = { path = "../container_normal.png", srgb = false }
+            specular = { path = "specular.png" }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            textures,
+            [
+                (
+                    "diffuse".to_owned(),
+                    PathBuf::from("../container.jpg"),
+                    true
+                ),
+                (
+                    "normal".to_owned(),
+                    PathBuf::from("../container_normal.png"),
+                    false
+                ),
+                ("specular".to_owned(), PathBuf::from("specular.png"), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let error = parse("shader = \"cube\"\nshaders = \"light\"")
+            .err()
+            .unwrap();
+        assert!(error.contains("unknown field `shaders`"), "{}", error);
+
+        let texture = "shader = \"cube\"\n[textures]\nnormal = { path = \"n.png\", sRGB = false }";
+        assert!(parse(texture).is_err());
+    }
+
+    #[test]
+    fn parse_errors_include_the_location() {
+        let error = parse("shader = \"cube\"\n[parameters]\ncolor = [1.0,")
+            .err()
+            .unwrap();
+
+        assert!(error.contains("line 3"), "{}", error);
+    }
+}
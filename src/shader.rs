@@ -1,44 +1,413 @@
 use std::{
-    ffi::{CStr, CString},
-    path::Path,
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt::{self, Display},
+    fs, io,
+    marker::PhantomData,
+    mem,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
 };
 
 use gl::types::*;
-use nalgebra_glm as glm;
+
+use crate::{
+    buffer::{AttributeType, VertexArray},
+    texture::{Sampler, Texture, TextureUnit},
+    uniform_buffer,
+};
+
+pub(crate) use binary_cache::Fnv1a;
+pub use binary_cache::ProgramBinaryCache;
+pub use builder::{context_version, ShaderBuilder};
+pub use compute::{memory_barrier, ComputeShader, MemoryBarrier};
+pub use library::{ShaderLibrary, ShaderVariant};
+pub use preprocessor::{PreprocessedSource, ShaderPreprocessor, SourceFile};
+pub use reflection::{glsl_type_name, AttributeInfo, UniformBlockInfo, UniformInfo};
+pub use uniform::{Uniform, UniformElement};
+
+mod binary_cache;
+mod builder;
+mod compute;
+mod library;
+mod preprocessor;
+mod reflection;
+mod uniform;
+#[cfg(test)]
+mod validation;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum ShaderStage {
+    Vertex = gl::VERTEX_SHADER,
+    TessControl = gl::TESS_CONTROL_SHADER,
+    TessEvaluation = gl::TESS_EVALUATION_SHADER,
+    Geometry = gl::GEOMETRY_SHADER,
+    Fragment = gl::FRAGMENT_SHADER,
+    Compute = gl::COMPUTE_SHADER,
+}
+
+impl ShaderStage {
+    /// The minimum GL version with core support for the stage
+    pub fn required_version(&self) -> (u32, u32) {
+        match self {
+            ShaderStage::Vertex | ShaderStage::Fragment => (2, 0),
+            ShaderStage::Geometry => (3, 2),
+            ShaderStage::TessControl | ShaderStage::TessEvaluation => (4, 0),
+            ShaderStage::Compute => (4, 3),
+        }
+    }
+}
+
+impl Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderStage::Vertex => write!(f, "vertex"),
+            ShaderStage::TessControl => write!(f, "tessellation control"),
+            ShaderStage::TessEvaluation => write!(f, "tessellation evaluation"),
+            ShaderStage::Geometry => write!(f, "geometry"),
+            ShaderStage::Fragment => write!(f, "fragment"),
+            ShaderStage::Compute => write!(f, "compute"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Compile {
+        stage: ShaderStage,
+        log: String,
+    },
+    Link {
+        log: String,
+    },
+    MissingUniform {
+        name: String,
+    },
+    Preprocess {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    UnknownShader {
+        name: String,
+    },
+    UniformMismatch {
+        name: String,
+        glsl_type: String,
+        rust_type: String,
+    },
+    MissingUniformBlock {
+        name: String,
+    },
+    InvalidStages {
+        message: String,
+    },
+    Unsupported {
+        feature: String,
+        required: (u32, u32),
+        available: (u32, u32),
+    },
+    VertexLayoutMismatch {
+        mismatches: Vec<String>,
+    },
+    TooManyTextures {
+        name: String,
+        max: u32,
+    },
+}
+
+impl Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::Io { path, error } => {
+                write!(
+                    f,
+                    "Could not read shader file {}: {}",
+                    path.display(),
+                    error
+                )
+            }
+            ShaderError::Compile { stage, log } => {
+                write!(f, "Failed to compile {} shader:\n{}", stage, log)
+            }
+            ShaderError::Link { log } => write!(f, "Failed to link shader program:\n{}", log),
+            ShaderError::MissingUniform { name } => write!(f, "Could not find uniform: {}", name),
+            ShaderError::Preprocess {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            ShaderError::UnknownShader { name } => {
+                write!(f, "No shader registered with the name: {}", name)
+            }
+            ShaderError::UniformMismatch {
+                name,
+                glsl_type,
+                rust_type,
+            } => write!(
+                f,
+                "Uniform {} is declared as {} but was set with {}",
+                name, glsl_type, rust_type
+            ),
+            ShaderError::MissingUniformBlock { name } => {
+                write!(f, "Could not find uniform block: {}", name)
+            }
+            ShaderError::InvalidStages { message } => {
+                write!(f, "Invalid shader stages: {}", message)
+            }
+            ShaderError::Unsupported {
+                feature,
+                required: (required_major, required_minor),
+                available: (available_major, available_minor),
+            } => write!(
+                f,
+                "{} require OpenGL {}.{} but the context is {}.{}",
+                feature, required_major, required_minor, available_major, available_minor
+            ),
+            ShaderError::TooManyTextures { name, max } => write!(
+                f,
+                "Could not bind texture {}: all {} texture units are in use",
+                name, max
+            ),
+            ShaderError::VertexLayoutMismatch { mismatches } => {
+                write!(f, "Vertex layout does not match the shader's attributes:")?;
+                for mismatch in mismatches {
+                    write!(f, "\n    {}", mismatch)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for ShaderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ShaderError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// What to do when setting a uniform the program does not have
+///
+/// GLSL compilers strip uniforms that do not contribute to the output, so commenting out part of
+/// a shader can make uniforms the application still sets disappear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingUniformPolicy {
+    /// Silently skip the uniform
+    Ignore,
+    /// Print a warning the first time each missing uniform is set
+    #[default]
+    WarnOnce,
+    /// Return `ShaderError::MissingUniform`
+    Error,
+}
 
 pub struct Shader {
     id: GLuint,
+    uniforms: HashMap<String, UniformInfo>,
+    attributes: HashMap<String, AttributeInfo>,
+    uniform_blocks: HashMap<String, UniformBlockInfo>,
+    missing_uniform_policy: MissingUniformPolicy,
+    warned_uniforms: HashSet<String>,
+    // Sampler name -> texture unit, assigned in the order textures are first set
+    texture_units: HashMap<String, TextureUnit>,
+    watcher: Option<ShaderWatcher>,
+    // Programs belong to the context's thread
+    _not_send: PhantomData<*const ()>,
+}
+
+// Tracks the files a shader was built from so it can be recompiled when they change on disk
+struct ShaderWatcher {
+    builder: ShaderBuilder,
+    modified_times: Vec<(PathBuf, Option<SystemTime>)>,
+    last_poll: Instant,
+}
+
+impl ShaderWatcher {
+    const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+    fn new(builder: ShaderBuilder, sources: &[(ShaderStage, PreprocessedSource)]) -> Self {
+        let mut watcher = Self {
+            builder,
+            modified_times: Vec::new(),
+            last_poll: Instant::now(),
+        };
+        watcher.watch(sources);
+
+        watcher
+    }
+
+    // Replaces the watched files with every file (including includes) that went into the sources
+    fn watch(&mut self, sources: &[(ShaderStage, PreprocessedSource)]) {
+        self.modified_times.clear();
+
+        for (_, source) in sources {
+            for path in source.paths().filter(|path| path.is_file()) {
+                if !self
+                    .modified_times
+                    .iter()
+                    .any(|(watched, _)| watched == path)
+                {
+                    self.modified_times
+                        .push((path.to_owned(), modified_time(path)));
+                }
+            }
+        }
+    }
+
+    // Returns true if any watched file changed since the last poll
+    fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() < Self::POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+
+        let mut changed = false;
+        for (path, last_modified) in &mut self.modified_times {
+            let modified = modified_time(path);
+
+            if modified != *last_modified {
+                *last_modified = modified;
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    fn describe(&self) -> String {
+        self.builder
+            .files()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(" + ")
+    }
 }
 
 impl Shader {
-    pub fn new(vertex_source: &str, fragment_source: &str) -> Self {
-        // Compile Shaders
-        let vertex_shader = compile_shader(gl::VERTEX_SHADER, vertex_source);
-        let fragment_shader = compile_shader(gl::FRAGMENT_SHADER, fragment_source);
+    pub fn new(vertex_source: &str, fragment_source: &str) -> Result<Self, ShaderError> {
+        ShaderBuilder::new()
+            .vertex(vertex_source)
+            .fragment(fragment_source)
+            .build()
+    }
 
-        // Link Shaders
-        let shader_program = link_shaders(vertex_shader, fragment_shader);
+    // Wraps a linked program, reflecting its uniforms and binding its uniform blocks
+    fn from_program(shader_program: GLuint) -> Self {
+        // Look up every active uniform once instead of on every set
+        let uniforms = unsafe { reflection::reflect_uniforms(shader_program) };
+        let attributes = unsafe { reflection::reflect_attributes(shader_program) };
+        let uniform_blocks = unsafe { reflection::reflect_uniform_blocks(shader_program) };
+
+        // Attach uniform blocks to the buffers registered for them
+        for (name, block) in &uniform_blocks {
+            let Some(registered) = uniform_buffer::block_binding(name) else {
+                eprintln!(
+                    "Warning: uniform block {} has no uniform buffer yet, create it before linking \
+                     or bind the block manually",
+                    name
+                );
+                continue;
+            };
 
-        Self { id: shader_program }
+            if registered.size < block.size {
+                eprintln!(
+                    "Warning: uniform block {} needs {} bytes but its buffer only holds {}",
+                    name, block.size, registered.size
+                );
+            }
+
+            unsafe { gl::UniformBlockBinding(shader_program, block.index, registered.binding) };
+        }
+
+        Self {
+            id: shader_program,
+            uniforms,
+            attributes,
+            uniform_blocks,
+            missing_uniform_policy: MissingUniformPolicy::default(),
+            warned_uniforms: HashSet::new(),
+            texture_units: HashMap::new(),
+            watcher: None,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Builds a program from files, resolving includes relative to the vertex shader's directory
+    pub fn from_files<P: AsRef<Path>>(
+        vertex_path: P,
+        fragment_path: P,
+    ) -> Result<Self, ShaderError> {
+        let root = vertex_path
+            .as_ref()
+            .parent()
+            .map(Path::to_owned)
+            .unwrap_or_default();
+
+        Self::from_files_with(vertex_path, fragment_path, &ShaderPreprocessor::new(root))
     }
 
-    pub fn from_files<P: AsRef<Path>>(vertex_path: P, fragment_path: P) -> Self {
-        use std::fs;
+    pub fn from_files_with<P: AsRef<Path>>(
+        vertex_path: P,
+        fragment_path: P,
+        preprocessor: &ShaderPreprocessor,
+    ) -> Result<Self, ShaderError> {
+        ShaderBuilder::new()
+            .preprocessor(preprocessor)
+            .vertex_file(vertex_path)
+            .fragment_file(fragment_path)
+            .build()
+    }
 
-        let vertex_source = fs::read_to_string(&vertex_path).unwrap_or_else(|_| {
-            panic!(
-                "Could not read vertex shader file: {}",
-                vertex_path.as_ref().display()
-            )
-        });
-        let fragment_source = fs::read_to_string(fragment_path).unwrap_or_else(|_| {
-            panic!(
-                "Could not read fragment shader file: {}",
-                vertex_path.as_ref().display()
-            )
+    /// Recompiles the program if any of its source files changed on disk
+    ///
+    /// If the new sources fail to compile, the error is printed and the previous program is kept.
+    /// Returns true if the program was replaced.
+    pub fn reload_if_changed(&mut self) -> bool {
+        let Some(watcher) = &mut self.watcher else {
+            return false;
+        };
+
+        if !watcher.poll() {
+            return false;
+        }
+
+        let reloaded = watcher.builder.preprocess().and_then(|sources| {
+            let shader = watcher.builder.link(&sources)?;
+            watcher.watch(&sources);
+
+            Ok(shader)
         });
 
-        Self::new(&vertex_source, &fragment_source)
+        match reloaded {
+            Ok(mut shader) => {
+                // Swap the programs so the old one is deleted when `shader` drops
+                mem::swap(&mut self.id, &mut shader.id);
+                self.uniforms = mem::take(&mut shader.uniforms);
+                self.attributes = mem::take(&mut shader.attributes);
+                self.uniform_blocks = mem::take(&mut shader.uniform_blocks);
+                self.warned_uniforms.clear();
+                self.texture_units.clear();
+
+                println!("Reloaded shader: {}", watcher.describe());
+                true
+            }
+            Err(error) => {
+                eprintln!(
+                    "Failed to reload shader {} (keeping previous program)\n{}",
+                    watcher.describe(),
+                    error
+                );
+                false
+            }
+        }
     }
 
     pub fn use_program(&self) {
@@ -51,191 +420,495 @@ impl Shader {
         self.id
     }
 
-    fn get_uniform_location(&self, name: &str) -> i32 {
-        unsafe {
-            let name = CString::new(name).expect("Could not convert name to CString");
-            let uniform_location = gl::GetUniformLocation(self.id, name.as_ptr());
+    /// Gives up ownership of the program without deleting it
+    pub fn leak(mut self) -> GLuint {
+        mem::take(&mut self.id)
+    }
+
+    /// Looks up an active uniform by name
+    pub fn uniform(&self, name: &str) -> Option<&UniformInfo> {
+        self.uniforms.get(name)
+    }
+
+    /// Iterates over every active uniform of the program, sorted by location
+    pub fn uniforms(&self) -> impl Iterator<Item = (&str, &UniformInfo)> {
+        let mut uniforms: Vec<_> = self
+            .uniforms
+            .iter()
+            .map(|(name, info)| (name.as_str(), info))
+            .collect();
+        uniforms.sort_by_key(|(name, info)| (info.location, *name));
 
-            if uniform_location < 0 {
-                panic!("Could not set uniform: {}", name.to_str().unwrap());
+        uniforms.into_iter()
+    }
+
+    /// Looks up an active vertex attribute by name
+    pub fn attribute(&self, name: &str) -> Option<&AttributeInfo> {
+        self.attributes.get(name)
+    }
+
+    /// Iterates over every active vertex attribute, sorted by location
+    pub fn attributes(&self) -> impl Iterator<Item = (&str, &AttributeInfo)> {
+        let mut attributes: Vec<_> = self
+            .attributes
+            .iter()
+            .map(|(name, info)| (name.as_str(), info))
+            .collect();
+        attributes.sort_by_key(|(_, info)| info.location);
+
+        attributes.into_iter()
+    }
+
+    /// Checks that the vertex array feeds every active attribute with the same location,
+    /// component count and scalar type
+    ///
+    /// Attributes the vertex array provides but the shader does not use are fine.
+    pub fn check_vertex_array(&self, vertex_array: &VertexArray) -> Result<(), ShaderError> {
+        let mut mismatches = Vec::new();
+
+        for (name, info) in self.attributes() {
+            let (scalar, components, locations) = info.shape();
+
+            // Arrays and matrices span several consecutive locations
+            for location in info.location..info.location + locations * info.size {
+                let provided = vertex_array
+                    .attributes()
+                    .iter()
+                    .find(|attribute| attribute.location as GLint == location);
+
+                let Some(attribute) = provided else {
+                    mismatches.push(format!(
+                        "{} {} (location {}) is not provided by the vertex array",
+                        info.type_name(),
+                        name,
+                        location
+                    ));
+                    continue;
+                };
+
+                if attribute.components != components {
+                    mismatches.push(format!(
+                        "{} {} (location {}) needs {} components but the vertex array provides {}",
+                        info.type_name(),
+                        name,
+                        location,
+                        components,
+                        attribute.components
+                    ));
+                }
+
+                if attribute.type_ as GLenum != scalar {
+                    let provided_type = match attribute.type_ {
+                        AttributeType::Float => "float",
+                        AttributeType::Int => "int",
+                        AttributeType::UnsignedInt => "uint",
+                    };
+
+                    mismatches.push(format!(
+                        "{} {} (location {}) is read as {} data but the vertex array provides {}",
+                        info.type_name(),
+                        name,
+                        location,
+                        glsl_type_name(scalar),
+                        provided_type
+                    ));
+                }
             }
+        }
 
-            uniform_location
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(ShaderError::VertexLayoutMismatch { mismatches })
         }
     }
-}
 
-macro_rules! impl_set_uniform_1 {
-    ($type:ty, $func_name:ident, $gl_func:ident, $cast_type:ty) => {
-        pub fn $func_name(&mut self, name: &str, value: $type) {
-            unsafe {
-                gl::$gl_func(self.get_uniform_location(name), value as $cast_type);
-            }
+    pub fn uniform_block(&self, name: &str) -> Option<&UniformBlockInfo> {
+        self.uniform_blocks.get(name)
+    }
+
+    pub fn uniform_blocks(&self) -> impl Iterator<Item = (&str, &UniformBlockInfo)> {
+        self.uniform_blocks
+            .iter()
+            .map(|(name, block)| (name.as_str(), block))
+    }
+
+    /// Binds a uniform block to a binding point (see `UniformBuffer`)
+    pub fn bind_uniform_block(&mut self, name: &str, binding: GLuint) -> Result<(), ShaderError> {
+        let block =
+            self.uniform_blocks
+                .get(name)
+                .ok_or_else(|| ShaderError::MissingUniformBlock {
+                    name: name.to_owned(),
+                })?;
+
+        unsafe {
+            gl::UniformBlockBinding(self.id, block.index, binding);
         }
-    };
-}
 
-macro_rules! impl_set_uniform_2 {
-    ($type:ty, $func_name:ident, $gl_func:ident, $cast_type:ty) => {
-        pub fn $func_name(&mut self, name: &str, v0: $type, v1: $type) {
-            unsafe {
-                gl::$gl_func(
-                    self.get_uniform_location(name),
-                    v0 as $cast_type,
-                    v1 as $cast_type,
-                );
+        Ok(())
+    }
+
+    pub fn missing_uniform_policy(&self) -> MissingUniformPolicy {
+        self.missing_uniform_policy
+    }
+
+    pub fn set_missing_uniform_policy(&mut self, policy: MissingUniformPolicy) {
+        self.missing_uniform_policy = policy;
+    }
+
+    fn handle_missing_uniform(&mut self, name: &str) -> Result<(), ShaderError> {
+        match self.missing_uniform_policy {
+            MissingUniformPolicy::Ignore => Ok(()),
+            MissingUniformPolicy::WarnOnce => {
+                if self.warned_uniforms.insert(name.to_owned()) {
+                    eprintln!(
+                        "Warning: shader program {} has no active uniform named {} (it may have \
+                         been optimized out)",
+                        self.id, name
+                    );
+                }
+
+                Ok(())
             }
+            MissingUniformPolicy::Error => Err(ShaderError::MissingUniform {
+                name: name.to_owned(),
+            }),
         }
-    };
-}
+    }
 
-macro_rules! impl_set_uniform_3 {
-    ($type:ty, $func_name:ident, $gl_func:ident, $cast_type:ty) => {
-        pub fn $func_name(&mut self, name: &str, v0: $type, v1: $type, v2: $type) {
-            unsafe {
-                gl::$gl_func(
-                    self.get_uniform_location(name),
-                    v0 as $cast_type,
-                    v1 as $cast_type,
-                    v2 as $cast_type,
-                );
+    /// Sets a uniform of the program, which must currently be in use
+    ///
+    /// In debug builds the value is checked against the reflected GLSL type (and array size) and a
+    /// mismatch is reported as an error instead of being uploaded.
+    pub fn set_uniform<T: Uniform>(&mut self, name: &str, value: T) -> Result<(), ShaderError> {
+        let Some(info) = self.uniforms.get(name) else {
+            return self.handle_missing_uniform(name);
+        };
+
+        if cfg!(debug_assertions) {
+            let count = value.element_count();
+
+            if !T::GLSL_TYPES.contains(&info.type_) || count > info.size as usize {
+                let mut glsl_type = info.type_name().to_owned();
+                if info.size > 1 {
+                    glsl_type.push_str(&format!("[{}]", info.size));
+                }
+
+                let mut rust_type = std::any::type_name::<T>().to_owned();
+                if count > 1 {
+                    rust_type.push_str(&format!(" ({} elements)", count));
+                }
+
+                return Err(ShaderError::UniformMismatch {
+                    name: name.to_owned(),
+                    glsl_type,
+                    rust_type,
+                });
             }
         }
-    };
-}
 
-macro_rules! impl_set_uniform_4 {
-    ($type:ty, $func_name:ident, $gl_func:ident, $cast_type:ty) => {
-        pub fn $func_name(&mut self, name: &str, v0: $type, v1: $type, v2: $type, v3: $type) {
-            unsafe {
-                gl::$gl_func(
-                    self.get_uniform_location(name),
-                    v0 as $cast_type,
-                    v1 as $cast_type,
-                    v2 as $cast_type,
-                    v3 as $cast_type,
-                );
-            }
+        unsafe {
+            value.upload(info.location);
         }
-    };
-}
 
-macro_rules! impl_set_uniform_vector {
-    ($type:ty, $func_name:ident, $gl_func:ident) => {
-        pub fn $func_name(&mut self, name: &str, value: $type) {
-            unsafe {
-                gl::$gl_func(self.get_uniform_location(name), 1, value.as_ptr());
-            }
+        Ok(())
+    }
+
+    /// Binds a texture for the sampler uniform `name`, which must currently be in use
+    ///
+    /// Each sampler is given its own texture unit the first time it is set and keeps it until
+    /// `reset_texture_units` is called. The texture is sampled with its own sampling state.
+    pub fn set_texture<T: Texture + ?Sized>(
+        &mut self,
+        name: &str,
+        texture: &T,
+    ) -> Result<(), ShaderError> {
+        self.bind_texture(name, texture, None)
+    }
+
+    /// Like `set_texture`, but samples the texture with a separate sampler object
+    pub fn set_texture_with_sampler<T: Texture + ?Sized>(
+        &mut self,
+        name: &str,
+        texture: &T,
+        sampler: &Sampler,
+    ) -> Result<(), ShaderError> {
+        self.bind_texture(name, texture, Some(sampler))
+    }
+
+    fn bind_texture<T: Texture + ?Sized>(
+        &mut self,
+        name: &str,
+        texture: &T,
+        sampler: Option<&Sampler>,
+    ) -> Result<(), ShaderError> {
+        if !self.uniforms.contains_key(name) {
+            return self.handle_missing_uniform(name);
         }
-    };
-}
 
-macro_rules! impl_set_uniform_matrix {
-    ($type:ty, $func_name:ident, $gl_func:ident) => {
-        pub fn $func_name(&mut self, name: &str, value: $type) {
-            unsafe {
-                gl::$gl_func(
-                    self.get_uniform_location(name),
-                    1,
-                    gl::FALSE,
-                    value.as_ptr(),
-                );
+        let unit = match self.texture_units.get(name) {
+            Some(&unit) => unit,
+            None => {
+                let mut max_units: GLint = 0;
+                unsafe { gl::GetIntegerv(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS, &mut max_units) };
+
+                let unit = TextureUnit(self.texture_units.len() as u32);
+                if unit.0 >= max_units.max(0) as u32 {
+                    return Err(ShaderError::TooManyTextures {
+                        name: name.to_owned(),
+                        max: max_units.max(0) as u32,
+                    });
+                }
+
+                self.texture_units.insert(name.to_owned(), unit);
+                unit
             }
+        };
+
+        texture.bind_to_unit(unit);
+
+        // A sampler left on the unit by an earlier draw would override the texture's own state
+        match sampler {
+            Some(sampler) => sampler.bind(unit),
+            None => Sampler::unbind(unit),
         }
-    };
+
+        self.set_uniform(name, unit)
+    }
+
+    /// The texture unit assigned to a sampler by `set_texture`, if any
+    pub fn texture_unit(&self, name: &str) -> Option<TextureUnit> {
+        self.texture_units.get(name).copied()
+    }
+
+    /// Forgets every sampler's texture unit, so the next draw assigns them from unit 0 again
+    pub fn reset_texture_units(&mut self) {
+        self.texture_units.clear();
+    }
 }
 
-impl Shader {
-    impl_set_uniform_1!(f32, set_f32, Uniform1f, f32);
-    impl_set_uniform_2!(f32, set_f32_2, Uniform2f, f32);
-    impl_set_uniform_3!(f32, set_f32_3, Uniform3f, f32);
-    impl_set_uniform_4!(f32, set_f32_4, Uniform4f, f32);
-
-    impl_set_uniform_1!(i32, set_i32, Uniform1i, i32);
-    impl_set_uniform_2!(i32, set_i32_2, Uniform2i, i32);
-    impl_set_uniform_3!(i32, set_i32_3, Uniform3i, i32);
-    impl_set_uniform_4!(i32, set_i32_4, Uniform4i, i32);
-
-    impl_set_uniform_1!(u32, set_u32, Uniform1ui, u32);
-    impl_set_uniform_2!(u32, set_u32_2, Uniform2ui, u32);
-    impl_set_uniform_3!(u32, set_u32_3, Uniform3ui, u32);
-    impl_set_uniform_4!(u32, set_u32_4, Uniform4ui, u32);
-
-    impl_set_uniform_1!(bool, set_bool, Uniform1i, i32);
-    impl_set_uniform_2!(bool, set_bool_2, Uniform2i, i32);
-    impl_set_uniform_3!(bool, set_bool_3, Uniform3i, i32);
-    impl_set_uniform_4!(bool, set_bool_4, Uniform4i, i32);
-
-    impl_set_uniform_vector!(glm::Vec1, set_vec1, Uniform1fv);
-    impl_set_uniform_vector!(glm::Vec2, set_vec2, Uniform2fv);
-    impl_set_uniform_vector!(glm::Vec3, set_vec3, Uniform3fv);
-    impl_set_uniform_vector!(glm::Vec4, set_vec4, Uniform4fv);
-
-    impl_set_uniform_matrix!(glm::Mat2, set_mat2, UniformMatrix2fv);
-    impl_set_uniform_matrix!(glm::Mat3, set_mat3, UniformMatrix3fv);
-    impl_set_uniform_matrix!(glm::Mat4, set_mat4, UniformMatrix4fv);
-
-    impl_set_uniform_matrix!(glm::Mat2x3, set_mat2x3, UniformMatrix2x3fv);
-    impl_set_uniform_matrix!(glm::Mat3x2, set_mat3x2, UniformMatrix3x2fv);
-    impl_set_uniform_matrix!(glm::Mat2x4, set_mat2x4, UniformMatrix2x4fv);
-    impl_set_uniform_matrix!(glm::Mat4x2, set_mat4x2, UniformMatrix4x2fv);
-    impl_set_uniform_matrix!(glm::Mat3x4, set_mat3x4, UniformMatrix3x4fv);
-    impl_set_uniform_matrix!(glm::Mat4x3, set_mat4x3, UniformMatrix4x3fv);
-}
-
-fn compile_shader(type_: GLenum, source: &str) -> GLuint {
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+fn compile_shader(stage: ShaderStage, source: &PreprocessedSource) -> Result<GLuint, ShaderError> {
     unsafe {
-        let shader = gl::CreateShader(type_);
-        gl::ShaderSource(shader, 1, &(source.as_ptr().cast()), &(source.len() as i32));
+        let shader = gl::CreateShader(stage as GLenum);
+        let text = &source.source;
+        gl::ShaderSource(shader, 1, &(text.as_ptr().cast()), &(text.len() as i32));
         gl::CompileShader(shader);
 
         // Check for shader compile errors
         let mut success: i32 = 0;
-        let mut info_log: [i8; 1024] = [0; 1024];
         gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
 
-        // If compilation failed, print the error message
+        // If compilation failed, return the annotated error message
         if success == 0 {
-            gl::GetShaderInfoLog(shader, 1024, &mut 0, info_log.as_mut_ptr());
-            panic!(
-                "ERROR::SHADER::COMPILATION_FAILED\n{}",
-                CStr::from_ptr(info_log.as_ptr())
-                    .to_str()
-                    .expect("Could not convert GL compilation error to string")
-            );
+            let log = shader_info_log(shader);
+            gl::DeleteShader(shader);
+
+            return Err(ShaderError::Compile {
+                stage,
+                log: annotate_log(&log, source),
+            });
+        }
+
+        Ok(shader)
+    }
+}
+
+impl Drop for Shader {
+    fn drop(&mut self) {
+        if self.id != 0 {
+            unsafe { gl::DeleteProgram(self.id) };
         }
+    }
+}
 
-        shader
+// Compiles every stage and links them into a program
+//
+// `retrievable` asks the driver to keep the binary around for `glGetProgramBinary`.
+fn compile_program(
+    sources: &[(ShaderStage, PreprocessedSource)],
+    retrievable: bool,
+) -> Result<GLuint, ShaderError> {
+    // Compile Shaders
+    let mut shaders = Vec::with_capacity(sources.len());
+    for (stage, source) in sources {
+        match compile_shader(*stage, source) {
+            Ok(shader) => shaders.push(shader),
+            Err(error) => {
+                for shader in shaders {
+                    unsafe { gl::DeleteShader(shader) };
+                }
+                return Err(error);
+            }
+        }
     }
+
+    // Link Shaders
+    link_shaders(&shaders, retrievable)
 }
 
-fn link_shaders(vertex_shader: GLuint, fragment_shader: GLuint) -> GLuint {
+fn link_shaders(shaders: &[GLuint], retrievable: bool) -> Result<GLuint, ShaderError> {
     unsafe {
         let shader_program = gl::CreateProgram();
-        gl::AttachShader(shader_program, vertex_shader);
-        gl::AttachShader(shader_program, fragment_shader);
+        for &shader in shaders {
+            gl::AttachShader(shader_program, shader);
+        }
+        if retrievable {
+            gl::ProgramParameteri(
+                shader_program,
+                gl::PROGRAM_BINARY_RETRIEVABLE_HINT,
+                gl::TRUE as GLint,
+            );
+        }
         gl::LinkProgram(shader_program);
 
+        // Delete Now Unneeded Shader Objects
+        for &shader in shaders {
+            gl::DeleteShader(shader);
+        }
+
         // Check for shader linking errors
         let mut success: i32 = 0;
-        let mut info_log: [i8; 1024] = [0; 1024];
         gl::GetProgramiv(shader_program, gl::LINK_STATUS, &mut success);
 
-        // If linking failed, print the error message
+        // If linking failed, return the error message
         if success == 0 {
-            gl::GetProgramInfoLog(shader_program, 1024, &mut 0, info_log.as_mut_ptr());
-            panic!(
-                "ERROR::SHADER::PROGRAM::LINKING_FAILED\n{}",
-                CStr::from_ptr(info_log.as_ptr())
-                    .to_str()
-                    .expect("Could not convert GL compilation error to string")
-            );
+            let log = program_info_log(shader_program);
+            gl::DeleteProgram(shader_program);
+
+            return Err(ShaderError::Link { log });
         }
 
-        // Delete Now Unneeded Shader Objects
-        gl::DeleteShader(vertex_shader);
-        gl::DeleteShader(fragment_shader);
+        Ok(shader_program)
+    }
+}
+
+unsafe fn shader_info_log(shader: GLuint) -> String {
+    let mut length: i32 = 0;
+    gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut length);
+
+    let mut info_log = vec![0u8; length.max(1) as usize];
+    let mut written: i32 = 0;
+    gl::GetShaderInfoLog(shader, length, &mut written, info_log.as_mut_ptr().cast());
+    info_log.truncate(written.max(0) as usize);
+
+    String::from_utf8_lossy(&info_log).into_owned()
+}
+
+unsafe fn program_info_log(program: GLuint) -> String {
+    let mut length: i32 = 0;
+    gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut length);
+
+    let mut info_log = vec![0u8; length.max(1) as usize];
+    let mut written: i32 = 0;
+    gl::GetProgramInfoLog(program, length, &mut written, info_log.as_mut_ptr().cast());
+    info_log.truncate(written.max(0) as usize);
+
+    String::from_utf8_lossy(&info_log).into_owned()
+}
+
+/// Appends the offending source line below every log message that references one
+fn annotate_log(log: &str, source: &PreprocessedSource) -> String {
+    let mut annotated = String::with_capacity(log.len());
+
+    for message in log.lines() {
+        annotated.push_str(message);
+        annotated.push('\n');
+
+        let source_line = parse_log_location(message)
+            .and_then(|(source_string, line)| Some((line, source.line(source_string, line)?)));
+
+        if let Some((line, (path, text))) = source_line {
+            annotated.push_str(&format!(
+                "    {}:{} | {}\n",
+                path.display(),
+                line,
+                text.trim_end()
+            ));
+        }
+    }
+
+    annotated
+}
+
+/// Extracts the `(source string, line)` a driver log message refers to
+///
+/// Handles the common formats `0:12(5): error` (Mesa), `0(12) : error` (NVIDIA) and
+/// `ERROR: 0:12: ...` (AMD/Intel)
+fn parse_log_location(message: &str) -> Option<(usize, usize)> {
+    let bytes = message.as_bytes();
+    let digits_end = |start: usize| {
+        let mut end = start;
+        while end < bytes.len() && bytes[end].is_ascii_digit() {
+            end += 1;
+        }
+        end
+    };
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let starts_number =
+            bytes[i].is_ascii_digit() && (i == 0 || !bytes[i - 1].is_ascii_alphanumeric());
+
+        if !starts_number {
+            i += 1;
+            continue;
+        }
+
+        let source_end = digits_end(i);
+        let separator = bytes.get(source_end).copied();
+        let has_line = bytes
+            .get(source_end + 1)
+            .is_some_and(|byte| byte.is_ascii_digit());
+
+        if matches!(separator, Some(b':') | Some(b'(')) && has_line {
+            let line_end = digits_end(source_end + 1);
+
+            let source_string = message[i..source_end].parse().ok()?;
+            let line = message[source_end + 1..line_end].parse().ok()?;
+
+            return Some((source_string, line));
+        }
+
+        i = source_end;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_mesa_log_location() {
+        let message = "0:12(5): error: `color' undeclared";
+
+        assert_eq!(parse_log_location(message), Some((0, 12)));
+    }
+
+    #[test]
+    fn parses_nvidia_log_location() {
+        let message = "1(27) : error C1008: undefined variable \"color\"";
+
+        assert_eq!(parse_log_location(message), Some((1, 27)));
+    }
+
+    #[test]
+    fn parses_amd_log_location() {
+        let message = "ERROR: 2:8: 'color' : undeclared identifier";
+
+        assert_eq!(parse_log_location(message), Some((2, 8)));
+    }
 
-        shader_program
+    #[test]
+    fn ignores_messages_without_location() {
+        assert_eq!(parse_log_location("error: vec4 has no member x2"), None);
     }
 }
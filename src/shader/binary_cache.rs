@@ -0,0 +1,175 @@
+use std::{
+    ffi::CStr,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use gl::types::*;
+
+use super::{PreprocessedSource, ShaderStage};
+
+/// Stores linked program binaries on disk so later runs can skip compiling unchanged shaders
+///
+/// Binaries are keyed by a hash of the preprocessed sources and the driver's vendor, renderer and
+/// version strings, so editing a shader or updating the driver simply misses the cache. If the
+/// driver rejects a cached binary the program is compiled from source and the entry replaced.
+#[derive(Debug, Clone)]
+pub struct ProgramBinaryCache {
+    dir: PathBuf,
+}
+
+impl ProgramBinaryCache {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_owned(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Whether the driver can save and restore program binaries at all
+    pub fn is_supported() -> bool {
+        let mut formats: GLint = 0;
+        unsafe { gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut formats) };
+
+        formats > 0
+    }
+
+    /// Deletes every cached binary
+    pub fn clear(&self) -> io::Result<()> {
+        match fs::remove_dir_all(&self.dir) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+            _ => Ok(()),
+        }
+    }
+
+    pub(super) fn key(&self, sources: &[(ShaderStage, PreprocessedSource)]) -> u64 {
+        let mut hasher = Fnv1a::new();
+
+        for name in [gl::VENDOR, gl::RENDERER, gl::VERSION] {
+            hasher.write(driver_string(name).as_bytes());
+        }
+
+        for (stage, source) in sources {
+            hasher.write(&(*stage as u32).to_le_bytes());
+            hasher.write(source.source.as_bytes());
+        }
+
+        hasher.finish()
+    }
+
+    /// Creates a program from the cached binary, or returns `None` if there is no usable entry
+    pub(super) fn load(&self, key: u64) -> Option<GLuint> {
+        let path = self.path(key);
+        let data = fs::read(&path).ok()?;
+
+        // The file is the little-endian binary format followed by the binary itself
+        if data.len() <= 4 {
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+        let (format, binary) = data.split_at(4);
+        let format = GLenum::from_le_bytes(format.try_into().unwrap());
+
+        unsafe {
+            let program = gl::CreateProgram();
+            gl::ProgramBinary(
+                program,
+                format,
+                binary.as_ptr().cast(),
+                binary.len() as GLsizei,
+            );
+
+            let mut success: GLint = 0;
+            gl::GetProgramiv(program, gl::LINK_STATUS, &mut success);
+
+            if success == 0 {
+                // Usually a driver update the version string did not catch, recompile instead
+                gl::DeleteProgram(program);
+                let _ = fs::remove_file(&path);
+                return None;
+            }
+
+            Some(program)
+        }
+    }
+
+    /// Saves the binary of a linked program
+    ///
+    /// The program should have been linked with `PROGRAM_BINARY_RETRIEVABLE_HINT` set.
+    pub(super) fn store(&self, key: u64, program: GLuint) -> io::Result<()> {
+        let mut length: GLint = 0;
+        unsafe { gl::GetProgramiv(program, gl::PROGRAM_BINARY_LENGTH, &mut length) };
+
+        if length <= 0 {
+            return Ok(());
+        }
+
+        let mut format: GLenum = 0;
+        let mut written: GLsizei = 0;
+        let mut binary = vec![0u8; length as usize];
+        unsafe {
+            gl::GetProgramBinary(
+                program,
+                length,
+                &mut written,
+                &mut format,
+                binary.as_mut_ptr().cast(),
+            );
+        }
+        binary.truncate(written.max(0) as usize);
+
+        let mut data = Vec::with_capacity(4 + binary.len());
+        data.extend_from_slice(&format.to_le_bytes());
+        data.extend_from_slice(&binary);
+
+        // Write to a temporary file first so a crash never leaves a truncated entry behind
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(key);
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, data)?;
+        fs::rename(temp_path, path)
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.bin", key))
+    }
+}
+
+fn driver_string(name: GLenum) -> String {
+    unsafe {
+        let string = gl::GetString(name);
+
+        if string.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(string.cast()).to_string_lossy().into_owned()
+        }
+    }
+}
+
+// 64-bit FNV-1a, used instead of `DefaultHasher` because its output has to stay the same between
+// builds for the cache to be reused
+pub(crate) struct Fnv1a(u64);
+
+impl Fnv1a {
+    pub(crate) fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    // Prefixed with the length so ("ab", "c") and ("a", "bc") hash differently
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        let length = (bytes.len() as u64).to_le_bytes();
+
+        for &byte in length.iter().chain(bytes) {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
@@ -0,0 +1,297 @@
+use std::path::{Path, PathBuf};
+
+use super::{
+    compile_program, PreprocessedSource, ProgramBinaryCache, Shader, ShaderError,
+    ShaderPreprocessor, ShaderStage, ShaderWatcher,
+};
+
+#[derive(Debug, Clone)]
+enum StageSource {
+    Inline(String),
+    File(PathBuf),
+}
+
+/// Assembles a program from any combination of shader stages
+///
+/// Stages added from files are watched for changes, so the built program can be hot-reloaded
+/// with `Shader::reload_if_changed`.
+#[derive(Debug, Clone, Default)]
+pub struct ShaderBuilder {
+    stages: Vec<(ShaderStage, StageSource)>,
+    preprocessor: Option<ShaderPreprocessor>,
+    binary_cache: Option<ProgramBinaryCache>,
+}
+
+macro_rules! impl_stage_setters {
+    ($stage:expr, $source_fn:ident, $file_fn:ident) => {
+        pub fn $source_fn(self, source: &str) -> Self {
+            self.stage($stage, source)
+        }
+
+        pub fn $file_fn<P: AsRef<Path>>(self, path: P) -> Self {
+            self.stage_file($stage, path)
+        }
+    };
+}
+
+impl ShaderBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    impl_stage_setters!(ShaderStage::Vertex, vertex, vertex_file);
+    impl_stage_setters!(ShaderStage::TessControl, tess_control, tess_control_file);
+    impl_stage_setters!(
+        ShaderStage::TessEvaluation,
+        tess_evaluation,
+        tess_evaluation_file
+    );
+    impl_stage_setters!(ShaderStage::Geometry, geometry, geometry_file);
+    impl_stage_setters!(ShaderStage::Fragment, fragment, fragment_file);
+    impl_stage_setters!(ShaderStage::Compute, compute, compute_file);
+
+    /// Runs every stage through the preprocessor
+    ///
+    /// Without one, file stages resolve includes relative to their own directory and inline
+    /// sources are compiled as is.
+    pub fn preprocessor(mut self, preprocessor: &ShaderPreprocessor) -> Self {
+        self.preprocessor = Some(preprocessor.clone());
+        self
+    }
+
+    /// Loads the linked program from the cache when the sources are unchanged
+    ///
+    /// Ignored if the driver does not support program binaries.
+    pub fn binary_cache(mut self, cache: &ProgramBinaryCache) -> Self {
+        self.binary_cache = Some(cache.clone());
+        self
+    }
+
+    pub fn stage(self, stage: ShaderStage, source: &str) -> Self {
+        self.set_stage(stage, StageSource::Inline(source.to_owned()))
+    }
+
+    pub fn stage_file<P: AsRef<Path>>(self, stage: ShaderStage, path: P) -> Self {
+        self.set_stage(stage, StageSource::File(path.as_ref().to_owned()))
+    }
+
+    pub fn stages(&self) -> impl Iterator<Item = ShaderStage> + '_ {
+        self.stages.iter().map(|(stage, _)| *stage)
+    }
+
+    pub fn is_compute(&self) -> bool {
+        self.has_stage(ShaderStage::Compute)
+    }
+
+    pub fn build(&self) -> Result<Shader, ShaderError> {
+        self.validate()?;
+
+        let sources = self.preprocess()?;
+        let mut shader = self.link(&sources)?;
+
+        if self.has_files() {
+            shader.watcher = Some(ShaderWatcher::new(self.clone(), &sources));
+        }
+
+        Ok(shader)
+    }
+
+    // Links the preprocessed stages, going through the binary cache if there is one
+    pub(super) fn link(
+        &self,
+        sources: &[(ShaderStage, PreprocessedSource)],
+    ) -> Result<Shader, ShaderError> {
+        let cache = self
+            .binary_cache
+            .as_ref()
+            .filter(|_| ProgramBinaryCache::is_supported());
+
+        let Some(cache) = cache else {
+            return Ok(Shader::from_program(compile_program(sources, false)?));
+        };
+
+        let key = cache.key(sources);
+        if let Some(program) = cache.load(key) {
+            return Ok(Shader::from_program(program));
+        }
+
+        let program = compile_program(sources, true)?;
+        if let Err(error) = cache.store(key, program) {
+            eprintln!(
+                "Warning: could not write program binary to {}: {}",
+                cache.dir().display(),
+                error
+            );
+        }
+
+        Ok(Shader::from_program(program))
+    }
+
+    // Paths of every stage loaded from a file
+    pub(super) fn files(&self) -> impl Iterator<Item = &Path> {
+        self.stages.iter().filter_map(|(_, source)| match source {
+            StageSource::File(path) => Some(path.as_path()),
+            StageSource::Inline(_) => None,
+        })
+    }
+
+    pub(super) fn preprocess(&self) -> Result<Vec<(ShaderStage, PreprocessedSource)>, ShaderError> {
+        self.stages
+            .iter()
+            .map(|(stage, source)| {
+                let name = format!("<{} source>", stage);
+
+                let processed = match (source, &self.preprocessor) {
+                    (StageSource::Inline(text), Some(preprocessor)) => {
+                        preprocessor.process(text, name)?
+                    }
+                    (StageSource::Inline(text), None) => {
+                        PreprocessedSource::unprocessed(text, name)
+                    }
+                    (StageSource::File(path), Some(preprocessor)) => {
+                        preprocessor.process_file(path)?
+                    }
+                    (StageSource::File(path), None) => {
+                        let root = path.parent().unwrap_or(Path::new(""));
+                        ShaderPreprocessor::new(root).process_file(path)?
+                    }
+                };
+
+                Ok((*stage, processed))
+            })
+            .collect()
+    }
+
+    fn set_stage(mut self, stage: ShaderStage, source: StageSource) -> Self {
+        match self
+            .stages
+            .iter_mut()
+            .find(|(existing, _)| *existing == stage)
+        {
+            Some((_, existing_source)) => *existing_source = source,
+            None => self.stages.push((stage, source)),
+        }
+
+        self
+    }
+
+    fn has_stage(&self, stage: ShaderStage) -> bool {
+        self.stages.iter().any(|(existing, _)| *existing == stage)
+    }
+
+    fn has_files(&self) -> bool {
+        self.files().next().is_some()
+    }
+
+    fn validate(&self) -> Result<(), ShaderError> {
+        let invalid = |message: &str| {
+            Err(ShaderError::InvalidStages {
+                message: message.to_owned(),
+            })
+        };
+
+        if self.stages.is_empty() {
+            return invalid("a program needs at least one shader stage");
+        }
+
+        if self.is_compute() {
+            if self.stages.len() > 1 {
+                return invalid("compute shaders cannot be linked with other stages");
+            }
+        } else {
+            if !self.has_stage(ShaderStage::Vertex) {
+                return invalid("a graphics program needs a vertex stage");
+            }
+
+            if self.has_stage(ShaderStage::TessControl)
+                && !self.has_stage(ShaderStage::TessEvaluation)
+            {
+                return invalid(
+                    "a tessellation control stage needs a tessellation evaluation stage",
+                );
+            }
+        }
+
+        // Make sure the context is new enough for every stage
+        let available = context_version();
+        for (stage, _) in &self.stages {
+            let required = stage.required_version();
+
+            if available < required {
+                return Err(ShaderError::Unsupported {
+                    feature: format!("{} shaders", stage),
+                    required,
+                    available,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The `(major, minor)` version of the current GL context
+pub fn context_version() -> (u32, u32) {
+    let mut major: i32 = 0;
+    let mut minor: i32 = 0;
+
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+    }
+
+    (major.max(0) as u32, minor.max(0) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every rejected combination returns before the context version is queried
+    fn assert_invalid(builder: ShaderBuilder, expected: &str) {
+        match builder.validate() {
+            Err(ShaderError::InvalidStages { message }) => assert_eq!(message, expected),
+            Err(error) => panic!("expected invalid stages, got {}", error),
+            Ok(()) => panic!("expected invalid stages"),
+        }
+    }
+
+    #[test]
+    fn rejects_programs_without_stages() {
+        assert_invalid(
+            ShaderBuilder::new(),
+            "a program needs at least one shader stage",
+        );
+    }
+
+    #[test]
+    fn rejects_compute_with_other_stages() {
+        assert_invalid(
+            ShaderBuilder::new().compute("").fragment(""),
+            "compute shaders cannot be linked with other stages",
+        );
+    }
+
+    #[test]
+    fn rejects_graphics_without_vertex_stage() {
+        assert_invalid(
+            ShaderBuilder::new().fragment(""),
+            "a graphics program needs a vertex stage",
+        );
+        assert_invalid(
+            ShaderBuilder::new().geometry("").fragment(""),
+            "a graphics program needs a vertex stage",
+        );
+    }
+
+    #[test]
+    fn rejects_tess_control_without_evaluation() {
+        assert_invalid(
+            ShaderBuilder::new()
+                .vertex("")
+                .tess_control("")
+                .fragment(""),
+            "a tessellation control stage needs a tessellation evaluation stage",
+        );
+    }
+}
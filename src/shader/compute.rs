@@ -0,0 +1,133 @@
+use std::{
+    ops::{BitOr, BitOrAssign, Deref, DerefMut},
+    path::Path,
+};
+
+use gl::types::*;
+
+use super::{Shader, ShaderBuilder, ShaderError};
+
+/// A program made of a single compute stage (requires GL 4.3)
+///
+/// Derefs to [`Shader`] for setting uniforms and hot-reloading.
+pub struct ComputeShader {
+    shader: Shader,
+}
+
+impl ComputeShader {
+    pub fn new(source: &str) -> Result<Self, ShaderError> {
+        Self::from_builder(&ShaderBuilder::new().compute(source))
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ShaderError> {
+        Self::from_builder(&ShaderBuilder::new().compute_file(path))
+    }
+
+    pub fn from_builder(builder: &ShaderBuilder) -> Result<Self, ShaderError> {
+        if !builder.is_compute() {
+            return Err(ShaderError::InvalidStages {
+                message: "a compute program needs a compute stage".to_owned(),
+            });
+        }
+
+        Ok(Self {
+            shader: builder.build()?,
+        })
+    }
+
+    /// The `layout (local_size_x = ..., ...)` declared by the shader
+    pub fn local_size(&self) -> [u32; 3] {
+        let mut local_size = [0i32; 3];
+
+        unsafe {
+            gl::GetProgramiv(
+                self.shader.id,
+                gl::COMPUTE_WORK_GROUP_SIZE,
+                local_size.as_mut_ptr(),
+            );
+        }
+
+        local_size.map(|size| size.max(1) as u32)
+    }
+
+    /// Dispatches the given number of work groups
+    pub fn dispatch(&self, groups_x: u32, groups_y: u32, groups_z: u32) {
+        self.shader.use_program();
+
+        unsafe {
+            gl::DispatchCompute(groups_x, groups_y, groups_z);
+        }
+    }
+
+    /// Dispatches enough work groups to cover `width * height * depth` invocations
+    pub fn dispatch_for(&self, width: u32, height: u32, depth: u32) {
+        let [local_x, local_y, local_z] = self.local_size();
+
+        self.dispatch(
+            width.div_ceil(local_x),
+            height.div_ceil(local_y),
+            depth.div_ceil(local_z),
+        );
+    }
+}
+
+impl Deref for ComputeShader {
+    type Target = Shader;
+
+    fn deref(&self) -> &Self::Target {
+        &self.shader
+    }
+}
+
+impl DerefMut for ComputeShader {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.shader
+    }
+}
+
+/// A set of `glMemoryBarrier` bits, combined with `|`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryBarrier(GLbitfield);
+
+impl MemoryBarrier {
+    pub const VERTEX_ATTRIB_ARRAY: Self = Self(gl::VERTEX_ATTRIB_ARRAY_BARRIER_BIT);
+    pub const ELEMENT_ARRAY: Self = Self(gl::ELEMENT_ARRAY_BARRIER_BIT);
+    pub const UNIFORM: Self = Self(gl::UNIFORM_BARRIER_BIT);
+    pub const TEXTURE_FETCH: Self = Self(gl::TEXTURE_FETCH_BARRIER_BIT);
+    pub const SHADER_IMAGE_ACCESS: Self = Self(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+    pub const COMMAND: Self = Self(gl::COMMAND_BARRIER_BIT);
+    pub const PIXEL_BUFFER: Self = Self(gl::PIXEL_BUFFER_BARRIER_BIT);
+    pub const TEXTURE_UPDATE: Self = Self(gl::TEXTURE_UPDATE_BARRIER_BIT);
+    pub const BUFFER_UPDATE: Self = Self(gl::BUFFER_UPDATE_BARRIER_BIT);
+    pub const FRAMEBUFFER: Self = Self(gl::FRAMEBUFFER_BARRIER_BIT);
+    pub const TRANSFORM_FEEDBACK: Self = Self(gl::TRANSFORM_FEEDBACK_BARRIER_BIT);
+    pub const ATOMIC_COUNTER: Self = Self(gl::ATOMIC_COUNTER_BARRIER_BIT);
+    pub const SHADER_STORAGE: Self = Self(gl::SHADER_STORAGE_BARRIER_BIT);
+    pub const ALL: Self = Self(gl::ALL_BARRIER_BITS);
+
+    pub fn bits(&self) -> GLbitfield {
+        self.0
+    }
+}
+
+impl BitOr for MemoryBarrier {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for MemoryBarrier {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Orders memory accesses made by shaders (e.g. image stores in a compute dispatch) before the
+/// accesses described by `barriers`
+pub fn memory_barrier(barriers: MemoryBarrier) {
+    unsafe {
+        gl::MemoryBarrier(barriers.0);
+    }
+}
@@ -0,0 +1,169 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
+
+use super::{
+    MissingUniformPolicy, ProgramBinaryCache, Shader, ShaderBuilder, ShaderError,
+    ShaderPreprocessor,
+};
+
+/// Identifies one compiled permutation of a registered shader
+///
+/// Every flag is injected as `#define FLAG 1` ahead of the shader source.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ShaderVariant {
+    name: String,
+    flags: BTreeSet<String>,
+}
+
+impl ShaderVariant {
+    pub fn new<N: Into<String>>(name: N) -> Self {
+        Self {
+            name: name.into(),
+            flags: BTreeSet::new(),
+        }
+    }
+
+    pub fn with_flag<F: Into<String>>(mut self, flag: F) -> Self {
+        self.flags.insert(flag.into());
+        self
+    }
+
+    pub fn with_flags<F: Into<String>, I: IntoIterator<Item = F>>(mut self, flags: I) -> Self {
+        self.flags.extend(flags.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn flags(&self) -> impl Iterator<Item = &str> {
+        self.flags.iter().map(String::as_str)
+    }
+}
+
+struct ShaderSourceFiles {
+    vertex_path: PathBuf,
+    fragment_path: PathBuf,
+}
+
+/// Compiles and caches shader variants on demand from a set of registered uber-shaders
+pub struct ShaderLibrary {
+    preprocessor: ShaderPreprocessor,
+    sources: HashMap<String, ShaderSourceFiles>,
+    variants: HashMap<ShaderVariant, Shader>,
+    missing_uniform_policy: MissingUniformPolicy,
+    binary_cache: Option<ProgramBinaryCache>,
+}
+
+impl ShaderLibrary {
+    /// Creates a library whose shader files and includes are resolved relative to `root`
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            preprocessor: ShaderPreprocessor::new(root),
+            sources: HashMap::new(),
+            variants: HashMap::new(),
+            missing_uniform_policy: MissingUniformPolicy::default(),
+            binary_cache: None,
+        }
+    }
+
+    /// Sets the missing uniform policy of every variant, including ones compiled later
+    pub fn set_missing_uniform_policy(&mut self, policy: MissingUniformPolicy) {
+        self.missing_uniform_policy = policy;
+
+        for shader in self.variants.values_mut() {
+            shader.set_missing_uniform_policy(policy);
+        }
+    }
+
+    /// Caches the binaries of variants compiled from now on, see [`ProgramBinaryCache`]
+    pub fn set_binary_cache(&mut self, cache: Option<ProgramBinaryCache>) {
+        self.binary_cache = cache;
+    }
+
+    /// Adds a define to every variant compiled from now on
+    pub fn set_define<N: Into<String>, V: ToString>(&mut self, name: N, value: V) {
+        self.preprocessor.set_define(name, value);
+    }
+
+    pub fn register<N: Into<String>, P: AsRef<Path>>(
+        &mut self,
+        name: N,
+        vertex_path: P,
+        fragment_path: P,
+    ) {
+        let name = name.into();
+        let root = self.preprocessor.root();
+
+        self.sources.insert(
+            name.clone(),
+            ShaderSourceFiles {
+                vertex_path: root.join(vertex_path),
+                fragment_path: root.join(fragment_path),
+            },
+        );
+
+        // Drop any variants compiled from a previous registration
+        self.variants.retain(|variant, _| variant.name != name);
+    }
+
+    /// Shorthand for `variant(&ShaderVariant::new(name).with_flags(flags))`
+    pub fn get(&mut self, name: &str, flags: &[&str]) -> Result<&mut Shader, ShaderError> {
+        self.variant(&ShaderVariant::new(name).with_flags(flags.iter().copied()))
+    }
+
+    /// Returns the program for a variant, compiling it the first time it is requested
+    pub fn variant(&mut self, variant: &ShaderVariant) -> Result<&mut Shader, ShaderError> {
+        if !self.variants.contains_key(variant) {
+            let shader = self.compile(variant)?;
+            self.variants.insert(variant.clone(), shader);
+        }
+
+        Ok(self
+            .variants
+            .get_mut(variant)
+            .expect("Shader variant was just inserted"))
+    }
+
+    /// Iterates over every variant compiled so far
+    pub fn compiled_variants(&self) -> impl Iterator<Item = &ShaderVariant> {
+        self.variants.keys()
+    }
+
+    /// Hot-reloads every compiled variant whose files changed on disk
+    pub fn reload_if_changed(&mut self) {
+        for shader in self.variants.values_mut() {
+            shader.reload_if_changed();
+        }
+    }
+
+    fn compile(&self, variant: &ShaderVariant) -> Result<Shader, ShaderError> {
+        let files = self
+            .sources
+            .get(&variant.name)
+            .ok_or_else(|| ShaderError::UnknownShader {
+                name: variant.name.clone(),
+            })?;
+
+        let mut preprocessor = self.preprocessor.clone();
+        for flag in &variant.flags {
+            preprocessor.set_define(flag.as_str(), 1);
+        }
+
+        let mut builder = ShaderBuilder::new()
+            .preprocessor(&preprocessor)
+            .vertex_file(&files.vertex_path)
+            .fragment_file(&files.fragment_path);
+        if let Some(cache) = &self.binary_cache {
+            builder = builder.binary_cache(cache);
+        }
+
+        let mut shader = builder.build()?;
+        shader.set_missing_uniform_policy(self.missing_uniform_policy);
+
+        Ok(shader)
+    }
+}
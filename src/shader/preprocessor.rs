@@ -0,0 +1,410 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::ShaderError;
+
+/// Expands `#include "file"` directives and injects `#define`s before a source is compiled
+///
+/// Includes are resolved relative to the including file, falling back to the shader root. Every
+/// expanded file is assigned a GLSL source string number and `#line` directives are emitted around
+/// it, so the line numbers in a driver's error log can be mapped back to the original file (see
+/// [`PreprocessedSource`]).
+#[derive(Debug, Clone, Default)]
+pub struct ShaderPreprocessor {
+    root: PathBuf,
+    defines: Vec<(String, String)>,
+}
+
+/// A single file that contributed to a preprocessed source
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub text: String,
+}
+
+/// The expanded source along with every file that went into it
+///
+/// The index of a file in `files` is the source string number used in its `#line` directives.
+#[derive(Debug, Clone)]
+pub struct PreprocessedSource {
+    pub source: String,
+    pub files: Vec<SourceFile>,
+}
+
+impl PreprocessedSource {
+    /// Wraps a source that did not go through the preprocessor
+    pub fn unprocessed<P: Into<PathBuf>>(source: &str, name: P) -> Self {
+        Self {
+            source: source.to_owned(),
+            files: vec![SourceFile {
+                path: name.into(),
+                text: source.to_owned(),
+            }],
+        }
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|file| file.path.as_path())
+    }
+
+    /// Looks up the original text of a line by its source string number and 1-based line number
+    pub fn line(&self, source_string: usize, line: usize) -> Option<(&Path, &str)> {
+        let file = self.files.get(source_string)?;
+        let text = file.text.lines().nth(line.checked_sub(1)?)?;
+
+        Some((file.path.as_path(), text))
+    }
+}
+
+struct Expansion {
+    output: String,
+    files: Vec<SourceFile>,
+    include_stack: Vec<PathBuf>,
+    included_once: HashSet<PathBuf>,
+}
+
+impl ShaderPreprocessor {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_owned(),
+            defines: Vec::new(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Adds `#define name value` to every processed source
+    pub fn define<N: Into<String>, V: ToString>(mut self, name: N, value: V) -> Self {
+        self.set_define(name, value);
+        self
+    }
+
+    pub fn set_define<N: Into<String>, V: ToString>(&mut self, name: N, value: V) {
+        let name = name.into();
+        let value = value.to_string();
+
+        match self
+            .defines
+            .iter_mut()
+            .find(|(existing, _)| *existing == name)
+        {
+            Some((_, existing_value)) => *existing_value = value,
+            None => self.defines.push((name, value)),
+        }
+    }
+
+    pub fn remove_define(&mut self, name: &str) {
+        self.defines.retain(|(existing, _)| existing != name);
+    }
+
+    pub fn defines(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.defines
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn process_file<P: AsRef<Path>>(&self, path: P) -> Result<PreprocessedSource, ShaderError> {
+        let path = path.as_ref();
+        let source = read_file(path)?;
+
+        self.process(&source, path)
+    }
+
+    pub fn process<P: AsRef<Path>>(
+        &self,
+        source: &str,
+        path: P,
+    ) -> Result<PreprocessedSource, ShaderError> {
+        let path = path.as_ref();
+        let mut expansion = Expansion {
+            output: String::with_capacity(source.len()),
+            files: Vec::new(),
+            include_stack: vec![canonical(path)],
+            included_once: HashSet::new(),
+        };
+
+        // `#version` has to stay the very first directive, so hoist it above the injected defines
+        let lines: Vec<&str> = source.lines().collect();
+        let version_index = lines
+            .iter()
+            .position(|line| directive(line).is_some_and(|(name, _)| name == "version"));
+
+        if let Some(version_index) = version_index {
+            expansion.output.push_str(lines[version_index].trim());
+            expansion.output.push('\n');
+        }
+
+        for (name, value) in &self.defines {
+            expansion
+                .output
+                .push_str(&format!("#define {} {}\n", name, value));
+        }
+
+        expansion.output.push_str("#line 1 0\n");
+        self.expand(&mut expansion, source, path, version_index)?;
+
+        Ok(PreprocessedSource {
+            source: expansion.output,
+            files: expansion.files,
+        })
+    }
+
+    fn expand(
+        &self,
+        expansion: &mut Expansion,
+        source: &str,
+        path: &Path,
+        skip_line: Option<usize>,
+    ) -> Result<(), ShaderError> {
+        let source_string = expansion.files.len();
+        expansion.files.push(SourceFile {
+            path: path.to_owned(),
+            text: source.to_owned(),
+        });
+
+        for (index, line) in source.lines().enumerate() {
+            // Blank out handled directives instead of removing them to keep line numbers intact
+            if Some(index) == skip_line {
+                expansion.output.push('\n');
+                continue;
+            }
+
+            match directive(line) {
+                Some(("pragma", "once")) => {
+                    expansion.included_once.insert(canonical(path));
+                    expansion.output.push('\n');
+                }
+                Some(("version", _)) if source_string != 0 => {
+                    // Included files may declare a version for standalone tooling, drop it
+                    expansion.output.push('\n');
+                }
+                Some(("include", arguments)) => {
+                    let include_name = parse_include(arguments).ok_or_else(|| {
+                        preprocess_error(path, index + 1, "expected #include \"file\"")
+                    })?;
+                    let include_path = self.resolve_include(path, include_name);
+                    let include_key = canonical(&include_path);
+
+                    if expansion.include_stack.contains(&include_key) {
+                        let cycle = expansion
+                            .include_stack
+                            .iter()
+                            .chain(std::iter::once(&include_key))
+                            .map(|path| path.display().to_string())
+                            .collect::<Vec<_>>()
+                            .join(" -> ");
+
+                        return Err(preprocess_error(
+                            path,
+                            index + 1,
+                            format!("include cycle detected: {}", cycle),
+                        ));
+                    }
+
+                    if expansion.included_once.contains(&include_key) {
+                        expansion.output.push('\n');
+                        continue;
+                    }
+
+                    let include_source = read_file(&include_path)?;
+
+                    expansion
+                        .output
+                        .push_str(&format!("#line 1 {}\n", expansion.files.len()));
+
+                    expansion.include_stack.push(include_key);
+                    self.expand(expansion, &include_source, &include_path, None)?;
+                    expansion.include_stack.pop();
+
+                    expansion
+                        .output
+                        .push_str(&format!("#line {} {}\n", index + 2, source_string));
+                }
+                _ => {
+                    expansion.output.push_str(line);
+                    expansion.output.push('\n');
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Looks for an include next to the file that includes it first, then in the shader root
+    fn resolve_include(&self, includer: &Path, name: &str) -> PathBuf {
+        includer
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map(|dir| dir.join(name))
+            .filter(|path| path.is_file())
+            .unwrap_or_else(|| self.root.join(name))
+    }
+}
+
+// Splits `#name arguments` into its parts
+pub(super) fn directive(line: &str) -> Option<(&str, &str)> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start();
+    let name_end = rest
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(rest.len());
+
+    Some((&rest[..name_end], rest[name_end..].trim()))
+}
+
+fn parse_include(arguments: &str) -> Option<&str> {
+    let name = arguments.strip_prefix('"')?;
+    let end = name.find('"')?;
+
+    Some(&name[..end]).filter(|name| !name.is_empty())
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
+
+fn read_file(path: &Path) -> Result<String, ShaderError> {
+    fs::read_to_string(path).map_err(|error| ShaderError::Io {
+        path: path.to_owned(),
+        error,
+    })
+}
+
+fn preprocess_error<M: Into<String>>(path: &Path, line: usize, message: M) -> ShaderError {
+    ShaderError::Preprocess {
+        path: path.to_owned(),
+        line,
+        message: message.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes `files` into a fresh directory under the system temp directory
+    fn shader_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join("shader-preprocessor-test")
+            .join(name);
+        let _ = fs::remove_dir_all(&dir);
+
+        for (path, text) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn emits_line_directives_around_includes() {
+        let dir = shader_dir("lines", &[("common.glsl", "float half = 0.5;\n")]);
+        let source = ShaderPreprocessor::new(&dir)
+            .define("GAMMA", 2.2)
+            .process(
+                "#version 330 core\n#include \"common.glsl\"\nvoid main() {}\n",
+                "<test>",
+            )
+            .unwrap();
+
+        assert_eq!(
+            source.source,
+            "#version 330 core\n\
+             #define GAMMA 2.2\n\
+             #line 1 0\n\
+             \n\
+             #line 1 1\n\
+             float half = 0.5;\n\
+             #line 3 0\n\
+             void main() {}\n"
+        );
+        assert_eq!(source.files.len(), 2);
+        assert_eq!(source.line(1, 1).unwrap().1, "float half = 0.5;");
+        assert_eq!(source.line(0, 3).unwrap().1, "void main() {}");
+    }
+
+    #[test]
+    fn resolves_nested_includes_next_to_the_includer() {
+        let dir = shader_dir(
+            "nested",
+            &[
+                ("main.glsl", "#include \"lighting/blinn.glsl\"\n"),
+                (
+                    "lighting/blinn.glsl",
+                    "#include \"specular.glsl\"\n#include \"color.glsl\"\n",
+                ),
+                ("lighting/specular.glsl", "float specular;\n"),
+                ("color.glsl", "vec3 color;\n"),
+            ],
+        );
+        let source = ShaderPreprocessor::new(&dir)
+            .process_file(dir.join("main.glsl"))
+            .unwrap();
+
+        let paths: Vec<_> = source
+            .paths()
+            .map(|path| path.strip_prefix(&dir).unwrap())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new("main.glsl"),
+                Path::new("lighting/blinn.glsl"),
+                Path::new("lighting/specular.glsl"),
+                Path::new("color.glsl"),
+            ]
+        );
+        assert!(source.source.contains("float specular;\n#line 2 1\n"));
+        assert!(source.source.contains("vec3 color;\n#line 3 1\n"));
+    }
+
+    #[test]
+    fn includes_pragma_once_files_once() {
+        let dir = shader_dir(
+            "once",
+            &[("common.glsl", "#pragma once\nfloat half = 0.5;\n")],
+        );
+        let source = ShaderPreprocessor::new(&dir)
+            .process(
+                "#include \"common.glsl\"\n#include \"common.glsl\"\n",
+                "<test>",
+            )
+            .unwrap();
+
+        assert_eq!(source.source.matches("float half").count(), 1);
+        assert_eq!(source.files.len(), 2);
+    }
+
+    #[test]
+    fn detects_include_cycles() {
+        let dir = shader_dir(
+            "cycle",
+            &[
+                ("a.glsl", "#include \"b.glsl\"\n"),
+                ("b.glsl", "// b\n#include \"a.glsl\"\n"),
+            ],
+        );
+        let error = ShaderPreprocessor::new(&dir)
+            .process_file(dir.join("a.glsl"))
+            .unwrap_err();
+
+        match error {
+            ShaderError::Preprocess {
+                path,
+                line,
+                message,
+            } => {
+                assert!(path.ends_with("b.glsl"));
+                assert_eq!(line, 2);
+                assert!(message.contains("include cycle"), "{}", message);
+            }
+            error => panic!("expected a preprocess error, got {}", error),
+        }
+    }
+}
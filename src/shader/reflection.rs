@@ -0,0 +1,311 @@
+use std::{collections::HashMap, ffi::CString};
+
+use gl::types::*;
+
+/// An active uniform as reported by the driver after linking
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UniformInfo {
+    pub location: GLint,
+    /// The GLSL type (`gl::FLOAT_VEC3`, `gl::SAMPLER_2D`, ...)
+    pub type_: GLenum,
+    /// Number of array elements (1 for non-arrays)
+    pub size: GLint,
+}
+
+impl UniformInfo {
+    pub fn type_name(&self) -> &'static str {
+        glsl_type_name(self.type_)
+    }
+}
+
+/// Enumerates every active uniform with a location (uniform block members are skipped)
+///
+/// Arrays are reachable by their plain name (`lights`), their first element (`lights[0]`) and every
+/// other element (`lights[3]`).
+pub(super) unsafe fn reflect_uniforms(program: GLuint) -> HashMap<String, UniformInfo> {
+    let mut count: GLint = 0;
+    gl::GetProgramiv(program, gl::ACTIVE_UNIFORMS, &mut count);
+
+    let mut max_name_length: GLint = 0;
+    gl::GetProgramiv(program, gl::ACTIVE_UNIFORM_MAX_LENGTH, &mut max_name_length);
+
+    let mut uniforms = HashMap::with_capacity(count.max(0) as usize);
+    let mut name_buffer = vec![0u8; max_name_length.max(1) as usize];
+
+    for index in 0..count.max(0) as GLuint {
+        let mut name_length: GLsizei = 0;
+        let mut size: GLint = 0;
+        let mut type_: GLenum = 0;
+
+        gl::GetActiveUniform(
+            program,
+            index,
+            name_buffer.len() as GLsizei,
+            &mut name_length,
+            &mut size,
+            &mut type_,
+            name_buffer.as_mut_ptr().cast(),
+        );
+
+        let name =
+            String::from_utf8_lossy(&name_buffer[..name_length.max(0) as usize]).into_owned();
+        let location = uniform_location(program, &name);
+
+        if location < 0 {
+            continue;
+        }
+
+        let info = UniformInfo {
+            location,
+            type_,
+            size,
+        };
+
+        match name.strip_suffix("[0]") {
+            Some(base_name) => {
+                for element in 1..size {
+                    let element_name = format!("{}[{}]", base_name, element);
+                    let element_location = uniform_location(program, &element_name);
+
+                    if element_location >= 0 {
+                        let element_info = UniformInfo {
+                            location: element_location,
+                            size: size - element,
+                            ..info
+                        };
+                        uniforms.insert(element_name, element_info);
+                    }
+                }
+
+                uniforms.insert(base_name.to_owned(), info);
+                uniforms.insert(name, info);
+            }
+            None => {
+                uniforms.insert(name, info);
+            }
+        }
+    }
+
+    uniforms
+}
+
+unsafe fn uniform_location(program: GLuint, name: &str) -> GLint {
+    match CString::new(name) {
+        Ok(name) => gl::GetUniformLocation(program, name.as_ptr()),
+        Err(_) => -1,
+    }
+}
+
+/// The GLSL spelling of a uniform/attribute type enum
+pub fn glsl_type_name(type_: GLenum) -> &'static str {
+    match type_ {
+        gl::FLOAT => "float",
+        gl::FLOAT_VEC2 => "vec2",
+        gl::FLOAT_VEC3 => "vec3",
+        gl::FLOAT_VEC4 => "vec4",
+        gl::DOUBLE => "double",
+        gl::DOUBLE_VEC2 => "dvec2",
+        gl::DOUBLE_VEC3 => "dvec3",
+        gl::DOUBLE_VEC4 => "dvec4",
+        gl::INT => "int",
+        gl::INT_VEC2 => "ivec2",
+        gl::INT_VEC3 => "ivec3",
+        gl::INT_VEC4 => "ivec4",
+        gl::UNSIGNED_INT => "uint",
+        gl::UNSIGNED_INT_VEC2 => "uvec2",
+        gl::UNSIGNED_INT_VEC3 => "uvec3",
+        gl::UNSIGNED_INT_VEC4 => "uvec4",
+        gl::BOOL => "bool",
+        gl::BOOL_VEC2 => "bvec2",
+        gl::BOOL_VEC3 => "bvec3",
+        gl::BOOL_VEC4 => "bvec4",
+        gl::FLOAT_MAT2 => "mat2",
+        gl::FLOAT_MAT3 => "mat3",
+        gl::FLOAT_MAT4 => "mat4",
+        gl::FLOAT_MAT2x3 => "mat2x3",
+        gl::FLOAT_MAT2x4 => "mat2x4",
+        gl::FLOAT_MAT3x2 => "mat3x2",
+        gl::FLOAT_MAT3x4 => "mat3x4",
+        gl::FLOAT_MAT4x2 => "mat4x2",
+        gl::FLOAT_MAT4x3 => "mat4x3",
+        gl::SAMPLER_1D => "sampler1D",
+        gl::SAMPLER_2D => "sampler2D",
+        gl::SAMPLER_3D => "sampler3D",
+        gl::SAMPLER_CUBE => "samplerCube",
+        gl::SAMPLER_1D_SHADOW => "sampler1DShadow",
+        gl::SAMPLER_2D_SHADOW => "sampler2DShadow",
+        gl::SAMPLER_1D_ARRAY => "sampler1DArray",
+        gl::SAMPLER_2D_ARRAY => "sampler2DArray",
+        gl::SAMPLER_2D_ARRAY_SHADOW => "sampler2DArrayShadow",
+        gl::SAMPLER_CUBE_SHADOW => "samplerCubeShadow",
+        gl::SAMPLER_2D_MULTISAMPLE => "sampler2DMS",
+        gl::SAMPLER_2D_RECT => "sampler2DRect",
+        gl::SAMPLER_BUFFER => "samplerBuffer",
+        gl::INT_SAMPLER_2D => "isampler2D",
+        gl::INT_SAMPLER_3D => "isampler3D",
+        gl::INT_SAMPLER_CUBE => "isamplerCube",
+        gl::INT_SAMPLER_2D_ARRAY => "isampler2DArray",
+        gl::UNSIGNED_INT_SAMPLER_2D => "usampler2D",
+        gl::UNSIGNED_INT_SAMPLER_3D => "usampler3D",
+        gl::UNSIGNED_INT_SAMPLER_CUBE => "usamplerCube",
+        gl::UNSIGNED_INT_SAMPLER_2D_ARRAY => "usampler2DArray",
+        _ => "<unknown>",
+    }
+}
+
+/// An active uniform block as reported by the driver after linking
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UniformBlockInfo {
+    pub index: GLuint,
+    /// Minimum buffer size in bytes needed to back the block
+    pub size: usize,
+}
+
+pub(super) unsafe fn reflect_uniform_blocks(program: GLuint) -> HashMap<String, UniformBlockInfo> {
+    let mut count: GLint = 0;
+    gl::GetProgramiv(program, gl::ACTIVE_UNIFORM_BLOCKS, &mut count);
+
+    let mut max_name_length: GLint = 0;
+    gl::GetProgramiv(
+        program,
+        gl::ACTIVE_UNIFORM_BLOCK_MAX_NAME_LENGTH,
+        &mut max_name_length,
+    );
+
+    let mut blocks = HashMap::with_capacity(count.max(0) as usize);
+    let mut name_buffer = vec![0u8; max_name_length.max(1) as usize];
+
+    for index in 0..count.max(0) as GLuint {
+        let mut name_length: GLsizei = 0;
+        gl::GetActiveUniformBlockName(
+            program,
+            index,
+            name_buffer.len() as GLsizei,
+            &mut name_length,
+            name_buffer.as_mut_ptr().cast(),
+        );
+
+        let mut size: GLint = 0;
+        gl::GetActiveUniformBlockiv(program, index, gl::UNIFORM_BLOCK_DATA_SIZE, &mut size);
+
+        let name =
+            String::from_utf8_lossy(&name_buffer[..name_length.max(0) as usize]).into_owned();
+        blocks.insert(
+            name,
+            UniformBlockInfo {
+                index,
+                size: size.max(0) as usize,
+            },
+        );
+    }
+
+    blocks
+}
+
+/// An active vertex shader input as reported by the driver after linking
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttributeInfo {
+    pub location: GLint,
+    /// The GLSL type (`gl::FLOAT_VEC3`, `gl::FLOAT_MAT4`, ...)
+    pub type_: GLenum,
+    /// Number of array elements (1 for non-arrays)
+    pub size: GLint,
+}
+
+impl AttributeInfo {
+    pub fn type_name(&self) -> &'static str {
+        glsl_type_name(self.type_)
+    }
+
+    /// The scalar type, component count and number of consecutive locations of one element
+    ///
+    /// Matrices take one location per column.
+    pub fn shape(&self) -> (GLenum, GLint, GLint) {
+        match self.type_ {
+            gl::FLOAT => (gl::FLOAT, 1, 1),
+            gl::FLOAT_VEC2 => (gl::FLOAT, 2, 1),
+            gl::FLOAT_VEC3 => (gl::FLOAT, 3, 1),
+            gl::FLOAT_VEC4 => (gl::FLOAT, 4, 1),
+            gl::DOUBLE => (gl::DOUBLE, 1, 1),
+            gl::DOUBLE_VEC2 => (gl::DOUBLE, 2, 1),
+            gl::DOUBLE_VEC3 => (gl::DOUBLE, 3, 1),
+            gl::DOUBLE_VEC4 => (gl::DOUBLE, 4, 1),
+            gl::INT => (gl::INT, 1, 1),
+            gl::INT_VEC2 => (gl::INT, 2, 1),
+            gl::INT_VEC3 => (gl::INT, 3, 1),
+            gl::INT_VEC4 => (gl::INT, 4, 1),
+            gl::UNSIGNED_INT => (gl::UNSIGNED_INT, 1, 1),
+            gl::UNSIGNED_INT_VEC2 => (gl::UNSIGNED_INT, 2, 1),
+            gl::UNSIGNED_INT_VEC3 => (gl::UNSIGNED_INT, 3, 1),
+            gl::UNSIGNED_INT_VEC4 => (gl::UNSIGNED_INT, 4, 1),
+            gl::FLOAT_MAT2 => (gl::FLOAT, 2, 2),
+            gl::FLOAT_MAT3 => (gl::FLOAT, 3, 3),
+            gl::FLOAT_MAT4 => (gl::FLOAT, 4, 4),
+            gl::FLOAT_MAT2x3 => (gl::FLOAT, 3, 2),
+            gl::FLOAT_MAT2x4 => (gl::FLOAT, 4, 2),
+            gl::FLOAT_MAT3x2 => (gl::FLOAT, 2, 3),
+            gl::FLOAT_MAT3x4 => (gl::FLOAT, 4, 3),
+            gl::FLOAT_MAT4x2 => (gl::FLOAT, 2, 4),
+            gl::FLOAT_MAT4x3 => (gl::FLOAT, 3, 4),
+            _ => (self.type_, 1, 1),
+        }
+    }
+}
+
+/// Enumerates the active vertex inputs (built-ins like `gl_VertexID` are skipped)
+pub(super) unsafe fn reflect_attributes(program: GLuint) -> HashMap<String, AttributeInfo> {
+    let mut count: GLint = 0;
+    gl::GetProgramiv(program, gl::ACTIVE_ATTRIBUTES, &mut count);
+
+    let mut max_name_length: GLint = 0;
+    gl::GetProgramiv(
+        program,
+        gl::ACTIVE_ATTRIBUTE_MAX_LENGTH,
+        &mut max_name_length,
+    );
+
+    let mut attributes = HashMap::with_capacity(count.max(0) as usize);
+    let mut name_buffer = vec![0u8; max_name_length.max(1) as usize];
+
+    for index in 0..count.max(0) as GLuint {
+        let mut name_length: GLsizei = 0;
+        let mut size: GLint = 0;
+        let mut type_: GLenum = 0;
+
+        gl::GetActiveAttrib(
+            program,
+            index,
+            name_buffer.len() as GLsizei,
+            &mut name_length,
+            &mut size,
+            &mut type_,
+            name_buffer.as_mut_ptr().cast(),
+        );
+
+        let name =
+            String::from_utf8_lossy(&name_buffer[..name_length.max(0) as usize]).into_owned();
+        let location = match CString::new(name.as_str()) {
+            Ok(c_name) => gl::GetAttribLocation(program, c_name.as_ptr()),
+            Err(_) => -1,
+        };
+
+        if location < 0 {
+            continue;
+        }
+
+        let name = match name.strip_suffix("[0]") {
+            Some(base_name) => base_name.to_owned(),
+            None => name,
+        };
+        attributes.insert(
+            name,
+            AttributeInfo {
+                location,
+                type_,
+                size,
+            },
+        );
+    }
+
+    attributes
+}
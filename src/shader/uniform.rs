@@ -0,0 +1,287 @@
+use gl::types::*;
+use nalgebra_glm as glm;
+
+use crate::texture::{ActiveTextureSlot, TextureUnit};
+
+/// A Rust value that can be written to a GLSL uniform with [`Shader::set_uniform`]
+///
+/// [`Shader::set_uniform`]: super::Shader::set_uniform
+pub trait Uniform {
+    /// The GLSL types a value of this type may be assigned to
+    const GLSL_TYPES: &'static [GLenum];
+
+    /// The number of array elements the value covers
+    fn element_count(&self) -> usize {
+        1
+    }
+
+    /// # Safety
+    /// The owning program must be in use and `location` must belong to it
+    unsafe fn upload(&self, location: GLint);
+}
+
+/// A uniform type that can also be uploaded as a contiguous GLSL array
+pub trait UniformElement: Uniform + Sized {
+    /// # Safety
+    /// The owning program must be in use and `location` must belong to it
+    unsafe fn upload_array(location: GLint, values: &[Self]);
+}
+
+impl<T: UniformElement, const N: usize> Uniform for [T; N] {
+    const GLSL_TYPES: &'static [GLenum] = T::GLSL_TYPES;
+
+    fn element_count(&self) -> usize {
+        N
+    }
+
+    unsafe fn upload(&self, location: GLint) {
+        T::upload_array(location, self);
+    }
+}
+
+impl<T: UniformElement> Uniform for &[T] {
+    const GLSL_TYPES: &'static [GLenum] = T::GLSL_TYPES;
+
+    fn element_count(&self) -> usize {
+        self.len()
+    }
+
+    unsafe fn upload(&self, location: GLint) {
+        T::upload_array(location, self);
+    }
+}
+
+// Scalars and vectors whose memory layout is already a flat run of `$component`s
+macro_rules! impl_uniform_vector {
+    ($type:ty, $component:ty, $gl_func:ident, [$($glsl_type:expr),+]) => {
+        impl_uniform_vector!($type, $component, $gl_func, &[$($glsl_type),+]);
+    };
+    ($type:ty, $component:ty, $gl_func:ident, $glsl_types:expr) => {
+        impl Uniform for $type {
+            const GLSL_TYPES: &'static [GLenum] = $glsl_types;
+
+            unsafe fn upload(&self, location: GLint) {
+                Self::upload_array(location, std::slice::from_ref(self));
+            }
+        }
+
+        impl UniformElement for $type {
+            unsafe fn upload_array(location: GLint, values: &[Self]) {
+                gl::$gl_func(
+                    location,
+                    values.len() as GLsizei,
+                    values.as_ptr().cast::<$component>(),
+                );
+            }
+        }
+    };
+}
+
+// Matrices are column-major in both nalgebra and GLSL, so no transpose is needed
+macro_rules! impl_uniform_matrix {
+    ($type:ty, $gl_func:ident, $glsl_type:expr) => {
+        impl Uniform for $type {
+            const GLSL_TYPES: &'static [GLenum] = &[$glsl_type];
+
+            unsafe fn upload(&self, location: GLint) {
+                Self::upload_array(location, std::slice::from_ref(self));
+            }
+        }
+
+        impl UniformElement for $type {
+            unsafe fn upload_array(location: GLint, values: &[Self]) {
+                gl::$gl_func(
+                    location,
+                    values.len() as GLsizei,
+                    gl::FALSE,
+                    values.as_ptr().cast::<f32>(),
+                );
+            }
+        }
+    };
+}
+
+// Booleans have no fixed GL representation and have to be widened to ints first
+macro_rules! impl_uniform_bool_vector {
+    ($type:ty, $gl_func:ident, $glsl_type:expr) => {
+        impl Uniform for $type {
+            const GLSL_TYPES: &'static [GLenum] = &[$glsl_type];
+
+            unsafe fn upload(&self, location: GLint) {
+                Self::upload_array(location, std::slice::from_ref(self));
+            }
+        }
+
+        impl UniformElement for $type {
+            unsafe fn upload_array(location: GLint, values: &[Self]) {
+                let ints: Vec<i32> = values
+                    .iter()
+                    .flat_map(|value| value.iter().map(|&component| component as i32))
+                    .collect();
+
+                gl::$gl_func(location, values.len() as GLsizei, ints.as_ptr());
+            }
+        }
+    };
+}
+
+// Every sampler type, after `$extra` types that accept the same values
+macro_rules! sampler_types {
+    ($($extra:expr),*) => {
+        &[
+            $($extra,)*
+            gl::SAMPLER_1D,
+            gl::SAMPLER_2D,
+            gl::SAMPLER_3D,
+            gl::SAMPLER_CUBE,
+            gl::SAMPLER_1D_SHADOW,
+            gl::SAMPLER_2D_SHADOW,
+            gl::SAMPLER_1D_ARRAY,
+            gl::SAMPLER_2D_ARRAY,
+            gl::SAMPLER_1D_ARRAY_SHADOW,
+            gl::SAMPLER_2D_ARRAY_SHADOW,
+            gl::SAMPLER_CUBE_SHADOW,
+            gl::SAMPLER_2D_MULTISAMPLE,
+            gl::SAMPLER_2D_MULTISAMPLE_ARRAY,
+            gl::SAMPLER_2D_RECT,
+            gl::SAMPLER_2D_RECT_SHADOW,
+            gl::SAMPLER_BUFFER,
+            gl::INT_SAMPLER_1D,
+            gl::INT_SAMPLER_2D,
+            gl::INT_SAMPLER_3D,
+            gl::INT_SAMPLER_CUBE,
+            gl::INT_SAMPLER_1D_ARRAY,
+            gl::INT_SAMPLER_2D_ARRAY,
+            gl::INT_SAMPLER_2D_MULTISAMPLE,
+            gl::INT_SAMPLER_2D_MULTISAMPLE_ARRAY,
+            gl::INT_SAMPLER_2D_RECT,
+            gl::INT_SAMPLER_BUFFER,
+            gl::UNSIGNED_INT_SAMPLER_1D,
+            gl::UNSIGNED_INT_SAMPLER_2D,
+            gl::UNSIGNED_INT_SAMPLER_3D,
+            gl::UNSIGNED_INT_SAMPLER_CUBE,
+            gl::UNSIGNED_INT_SAMPLER_1D_ARRAY,
+            gl::UNSIGNED_INT_SAMPLER_2D_ARRAY,
+            gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE,
+            gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE_ARRAY,
+            gl::UNSIGNED_INT_SAMPLER_2D_RECT,
+            gl::UNSIGNED_INT_SAMPLER_BUFFER,
+        ]
+    };
+}
+
+const SAMPLER_TYPES: &[GLenum] = sampler_types![];
+
+// Samplers are set to a texture unit index, so plain ints are accepted for them too (as in
+// `set_uniform("texture1", 0)`)
+const INT_TYPES: &[GLenum] = sampler_types![gl::INT, gl::BOOL];
+
+impl_uniform_vector!(f32, f32, Uniform1fv, [gl::FLOAT]);
+impl_uniform_vector!(i32, i32, Uniform1iv, INT_TYPES);
+impl_uniform_vector!(u32, u32, Uniform1uiv, [gl::UNSIGNED_INT, gl::BOOL]);
+
+impl Uniform for bool {
+    const GLSL_TYPES: &'static [GLenum] = &[gl::BOOL];
+
+    unsafe fn upload(&self, location: GLint) {
+        Self::upload_array(location, std::slice::from_ref(self));
+    }
+}
+
+impl UniformElement for bool {
+    unsafe fn upload_array(location: GLint, values: &[Self]) {
+        let ints: Vec<i32> = values.iter().map(|&value| value as i32).collect();
+
+        gl::Uniform1iv(location, ints.len() as GLsizei, ints.as_ptr());
+    }
+}
+
+impl_uniform_vector!(glm::Vec1, f32, Uniform1fv, [gl::FLOAT]);
+impl_uniform_vector!(glm::Vec2, f32, Uniform2fv, [gl::FLOAT_VEC2]);
+impl_uniform_vector!(glm::Vec3, f32, Uniform3fv, [gl::FLOAT_VEC3]);
+impl_uniform_vector!(glm::Vec4, f32, Uniform4fv, [gl::FLOAT_VEC4]);
+
+impl_uniform_vector!(glm::IVec2, i32, Uniform2iv, [gl::INT_VEC2, gl::BOOL_VEC2]);
+impl_uniform_vector!(glm::IVec3, i32, Uniform3iv, [gl::INT_VEC3, gl::BOOL_VEC3]);
+impl_uniform_vector!(glm::IVec4, i32, Uniform4iv, [gl::INT_VEC4, gl::BOOL_VEC4]);
+
+impl_uniform_vector!(
+    glm::UVec2,
+    u32,
+    Uniform2uiv,
+    [gl::UNSIGNED_INT_VEC2, gl::BOOL_VEC2]
+);
+impl_uniform_vector!(
+    glm::UVec3,
+    u32,
+    Uniform3uiv,
+    [gl::UNSIGNED_INT_VEC3, gl::BOOL_VEC3]
+);
+impl_uniform_vector!(
+    glm::UVec4,
+    u32,
+    Uniform4uiv,
+    [gl::UNSIGNED_INT_VEC4, gl::BOOL_VEC4]
+);
+
+impl_uniform_bool_vector!(glm::BVec2, Uniform2iv, gl::BOOL_VEC2);
+impl_uniform_bool_vector!(glm::BVec3, Uniform3iv, gl::BOOL_VEC3);
+impl_uniform_bool_vector!(glm::BVec4, Uniform4iv, gl::BOOL_VEC4);
+
+impl_uniform_matrix!(glm::Mat2, UniformMatrix2fv, gl::FLOAT_MAT2);
+impl_uniform_matrix!(glm::Mat3, UniformMatrix3fv, gl::FLOAT_MAT3);
+impl_uniform_matrix!(glm::Mat4, UniformMatrix4fv, gl::FLOAT_MAT4);
+
+// nalgebra names matrices rows x columns while GLSL uses columns x rows
+impl_uniform_matrix!(glm::Mat2x3, UniformMatrix3x2fv, gl::FLOAT_MAT3x2);
+impl_uniform_matrix!(glm::Mat3x2, UniformMatrix2x3fv, gl::FLOAT_MAT2x3);
+impl_uniform_matrix!(glm::Mat2x4, UniformMatrix4x2fv, gl::FLOAT_MAT4x2);
+impl_uniform_matrix!(glm::Mat4x2, UniformMatrix2x4fv, gl::FLOAT_MAT2x4);
+impl_uniform_matrix!(glm::Mat3x4, UniformMatrix4x3fv, gl::FLOAT_MAT4x3);
+impl_uniform_matrix!(glm::Mat4x3, UniformMatrix3x4fv, gl::FLOAT_MAT3x4);
+
+impl Uniform for TextureUnit {
+    const GLSL_TYPES: &'static [GLenum] = SAMPLER_TYPES;
+
+    unsafe fn upload(&self, location: GLint) {
+        Self::upload_array(location, std::slice::from_ref(self));
+    }
+}
+
+impl UniformElement for TextureUnit {
+    unsafe fn upload_array(location: GLint, values: &[Self]) {
+        let units: Vec<i32> = values.iter().map(|unit| unit.0 as i32).collect();
+
+        gl::Uniform1iv(location, units.len() as GLsizei, units.as_ptr());
+    }
+}
+
+impl Uniform for ActiveTextureSlot {
+    const GLSL_TYPES: &'static [GLenum] = SAMPLER_TYPES;
+
+    unsafe fn upload(&self, location: GLint) {
+        TextureUnit::from(*self).upload(location);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ints_can_set_samplers() {
+        for sampler in [
+            gl::SAMPLER_2D,
+            gl::SAMPLER_CUBE,
+            gl::SAMPLER_2D_ARRAY,
+            gl::SAMPLER_3D,
+        ] {
+            assert!(i32::GLSL_TYPES.contains(&sampler));
+            assert!(<[i32; 2]>::GLSL_TYPES.contains(&sampler));
+            assert!(TextureUnit::GLSL_TYPES.contains(&sampler));
+        }
+        assert!(i32::GLSL_TYPES.contains(&gl::INT));
+        assert!(!TextureUnit::GLSL_TYPES.contains(&gl::INT));
+        assert!(!u32::GLSL_TYPES.contains(&gl::SAMPLER_2D));
+    }
+}
@@ -0,0 +1,1034 @@
+use std::{
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+};
+
+use super::{preprocessor::directive, PreprocessedSource, ShaderStage};
+
+pub use frontend::compile;
+
+mod frontend;
+
+// Offline checks for mistakes drivers only report at runtime. Shaders go through two passes:
+//
+// - `validate` is a scanner checking the source against the `#version` it declares: built-ins
+//   removed from core profiles, built-ins and keywords newer than the version, a missing
+//   `#version` or `main`, fragment shaders without outputs, and the global `in`/`out`
+//   declarations that `validate_interface` matches between stages. It only understands global
+//   declarations and treats both sides of every `#ifdef` as live code.
+// - `compile` parses and type checks the stage with naga's GLSL frontend (see `frontend.rs`),
+//   once per set of defines the shader is built with.
+
+/// A problem found in a shader, pointing at the original file and line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub path: PathBuf,
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path.display(), self.line, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    Core,
+    Compatibility,
+    Es,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlslVersion {
+    pub number: u32,
+    pub profile: Profile,
+}
+
+impl GlslVersion {
+    // Whether the deprecated fixed-function built-ins are gone
+    fn is_core(&self) -> bool {
+        match self.profile {
+            Profile::Core => self.number >= 140,
+            Profile::Compatibility => false,
+            Profile::Es => self.number >= 300,
+        }
+    }
+}
+
+impl Display for GlslVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.profile {
+            Profile::Core if self.number >= 150 => write!(f, "{} core", self.number),
+            Profile::Core => write!(f, "{}", self.number),
+            Profile::Compatibility => write!(f, "{} compatibility", self.number),
+            Profile::Es => write!(f, "{} es", self.number),
+        }
+    }
+}
+
+/// A global `in` or `out` variable (or interface block)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceVariable {
+    pub name: String,
+    pub type_: String,
+    pub path: PathBuf,
+    pub line: usize,
+}
+
+/// The version and global inputs/outputs of a stage that passed validation
+#[derive(Debug, Clone)]
+pub struct ShaderInterface {
+    pub stage: ShaderStage,
+    pub version: GlslVersion,
+    pub inputs: Vec<InterfaceVariable>,
+    pub outputs: Vec<InterfaceVariable>,
+}
+
+const DESKTOP_VERSIONS: &[u32] = &[
+    110, 120, 130, 140, 150, 330, 400, 410, 420, 430, 440, 450, 460,
+];
+const ES_VERSIONS: &[u32] = &[100, 300, 310, 320];
+
+// Built-ins removed from core profiles, with what to use instead
+const REMOVED_IN_CORE: &[(&str, &str)] = &[
+    ("gl_FragColor", "declare an `out vec4` instead"),
+    ("gl_FragData", "declare `out` variables instead"),
+    ("attribute", "use `in` instead"),
+    ("varying", "use `in`/`out` instead"),
+    ("texture1D", "use `texture` instead"),
+    ("texture2D", "use `texture` instead"),
+    ("texture3D", "use `texture` instead"),
+    ("textureCube", "use `texture` instead"),
+    ("texture2DProj", "use `textureProj` instead"),
+    ("texture2DLod", "use `textureLod` instead"),
+    ("textureCubeLod", "use `textureLod` instead"),
+    ("shadow1D", "use `texture` instead"),
+    ("shadow2D", "use `texture` instead"),
+    ("ftransform", "transform the position explicitly"),
+    ("gl_Vertex", "declare a vertex input instead"),
+    ("gl_Normal", "declare a vertex input instead"),
+    ("gl_Color", "declare a vertex input instead"),
+    ("gl_MultiTexCoord0", "declare a vertex input instead"),
+    ("gl_TexCoord", "declare `out` variables instead"),
+    ("gl_FrontColor", "declare `out` variables instead"),
+    ("gl_ModelViewMatrix", "pass matrices as uniforms"),
+    ("gl_ProjectionMatrix", "pass matrices as uniforms"),
+    ("gl_ModelViewProjectionMatrix", "pass matrices as uniforms"),
+    ("gl_NormalMatrix", "pass matrices as uniforms"),
+];
+
+// Built-in functions and keywords that only exist from a desktop GLSL version onwards
+const MIN_VERSIONS: &[(&str, u32)] = &[
+    ("texture", 130),
+    ("textureLod", 130),
+    ("textureGrad", 130),
+    ("textureSize", 130),
+    ("texelFetch", 130),
+    ("round", 130),
+    ("trunc", 130),
+    ("isnan", 130),
+    ("isinf", 130),
+    ("transpose", 120),
+    ("inverse", 140),
+    ("layout", 140),
+    ("floatBitsToInt", 330),
+    ("floatBitsToUint", 330),
+    ("intBitsToFloat", 330),
+    ("uintBitsToFloat", 330),
+    ("fma", 400),
+    ("imageLoad", 420),
+    ("imageStore", 420),
+    ("memoryBarrier", 420),
+    ("dFdxFine", 450),
+    ("dFdyFine", 450),
+];
+
+const QUALIFIERS: &[&str] = &[
+    "flat",
+    "smooth",
+    "noperspective",
+    "centroid",
+    "sample",
+    "patch",
+    "invariant",
+    "precise",
+    "highp",
+    "mediump",
+    "lowp",
+    "const",
+];
+
+#[derive(Debug)]
+struct Token {
+    text: String,
+    source_string: usize,
+    line: usize,
+}
+
+struct Scanner<'a> {
+    source: &'a PreprocessedSource,
+    errors: Vec<ValidationError>,
+}
+
+impl Scanner<'_> {
+    fn path(&self, source_string: usize) -> PathBuf {
+        self.source
+            .files
+            .get(source_string)
+            .map(|file| file.path.clone())
+            .unwrap_or_default()
+    }
+
+    fn error<M: Into<String>>(&mut self, source_string: usize, line: usize, message: M) {
+        self.errors.push(ValidationError {
+            path: self.path(source_string),
+            line,
+            message: message.into(),
+        });
+    }
+
+    fn error_at<M: Into<String>>(&mut self, token: &Token, message: M) {
+        self.error(token.source_string, token.line, message);
+    }
+
+    fn variable(&self, name: &Token, type_: String) -> InterfaceVariable {
+        InterfaceVariable {
+            name: name.text.clone(),
+            type_,
+            path: self.path(name.source_string),
+            line: name.line,
+        }
+    }
+}
+
+/// Checks a preprocessed stage against its declared `#version` and profile
+///
+/// Returns the stage's interface so it can be matched against the neighbouring stages with
+/// [`validate_interface`].
+pub fn validate(
+    stage: ShaderStage,
+    source: &PreprocessedSource,
+) -> Result<ShaderInterface, Vec<ValidationError>> {
+    let mut scanner = Scanner {
+        source,
+        errors: Vec::new(),
+    };
+
+    let (version, tokens) = tokenize(&mut scanner);
+    let version = version.unwrap_or_else(|| {
+        scanner.error(0, 1, "missing #version directive (GLSL 110 is assumed)");
+        GlslVersion {
+            number: 110,
+            profile: Profile::Compatibility,
+        }
+    });
+
+    check_identifiers(&mut scanner, version, &tokens);
+    let (inputs, outputs) = check_declarations(&mut scanner, stage, version, &tokens);
+
+    if stage == ShaderStage::Fragment && version.is_core() && outputs.is_empty() {
+        scanner.error(0, 1, "fragment shader declares no `out` variables");
+    }
+
+    if scanner.errors.is_empty() {
+        Ok(ShaderInterface {
+            stage,
+            version,
+            inputs,
+            outputs,
+        })
+    } else {
+        Err(scanner.errors)
+    }
+}
+
+/// Checks that every input of `next` is written by `previous` with the same type
+pub fn validate_interface(
+    previous: &ShaderInterface,
+    next: &ShaderInterface,
+) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+
+    for input in &next.inputs {
+        let message = match previous.outputs.iter().find(|out| out.name == input.name) {
+            None => format!(
+                "{} input `{}` is not written by the {} shader",
+                next.stage, input.name, previous.stage
+            ),
+            Some(output) if output.type_ != input.type_ => format!(
+                "{} input `{}` is declared as `{}` but the {} shader writes `{}` ({}:{})",
+                next.stage,
+                input.name,
+                input.type_,
+                previous.stage,
+                output.type_,
+                output.path.display(),
+                output.line
+            ),
+            Some(_) => continue,
+        };
+
+        errors.push(ValidationError {
+            path: input.path.clone(),
+            line: input.line,
+            message,
+        });
+    }
+
+    errors
+}
+
+// Splits the source into tokens (without comments or directives), following `#line` directives
+// to track where each came from
+fn tokenize(scanner: &mut Scanner) -> (Option<GlslVersion>, Vec<Token>) {
+    let mut version = None;
+    let mut tokens = Vec::new();
+
+    let mut source_string = 0;
+    let mut line = 1;
+    let mut in_comment = false;
+
+    for raw_line in scanner.source.source.lines() {
+        let code = strip_comments(raw_line, &mut in_comment);
+
+        match directive(&code) {
+            Some(("line", arguments)) => {
+                let mut numbers = arguments.split_whitespace().map(str::parse::<usize>);
+                if let Some(Ok(next_line)) = numbers.next() {
+                    line = next_line;
+                }
+                if let Some(Ok(next_source_string)) = numbers.next() {
+                    source_string = next_source_string;
+                }
+                continue;
+            }
+            Some(("version", arguments)) => {
+                version = parse_version(scanner, arguments, source_string, line);
+            }
+            Some(_) => {}
+            None => tokens.extend(split_tokens(&code).map(|text| Token {
+                text: text.to_owned(),
+                source_string,
+                line,
+            })),
+        }
+
+        line += 1;
+    }
+
+    (version, tokens)
+}
+
+fn strip_comments(line: &str, in_comment: &mut bool) -> String {
+    let mut code = String::with_capacity(line.len());
+    let mut rest = line;
+
+    loop {
+        if *in_comment {
+            match rest.find("*/") {
+                Some(end) => {
+                    *in_comment = false;
+                    rest = &rest[end + 2..];
+                    code.push(' ');
+                }
+                None => return code,
+            }
+        }
+
+        let line_comment = rest.find("//");
+        let block_comment = rest.find("/*");
+
+        match (line_comment, block_comment) {
+            (Some(start), block) if block.is_none_or(|block| start < block) => {
+                code.push_str(&rest[..start]);
+                return code;
+            }
+            (_, Some(start)) => {
+                code.push_str(&rest[..start]);
+                *in_comment = true;
+                rest = &rest[start + 2..];
+            }
+            _ => {
+                code.push_str(rest);
+                return code;
+            }
+        }
+    }
+}
+
+fn split_tokens(code: &str) -> impl Iterator<Item = &str> {
+    let mut rest = code;
+
+    std::iter::from_fn(move || {
+        rest = rest.trim_start();
+        let first = rest.chars().next()?;
+
+        let length = if first.is_ascii_alphanumeric() || first == '_' || first == '.' {
+            rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_' && c != '.')
+                .unwrap_or(rest.len())
+        } else {
+            first.len_utf8()
+        };
+
+        let (token, remaining) = rest.split_at(length);
+        rest = remaining;
+
+        Some(token)
+    })
+}
+
+fn parse_version(
+    scanner: &mut Scanner,
+    arguments: &str,
+    source_string: usize,
+    line: usize,
+) -> Option<GlslVersion> {
+    let mut parts = arguments.split_whitespace();
+
+    let Some(number) = parts.next().and_then(|number| number.parse::<u32>().ok()) else {
+        scanner.error(
+            source_string,
+            line,
+            "expected a version number after #version",
+        );
+        return None;
+    };
+
+    let profile = match parts.next() {
+        None if ES_VERSIONS.contains(&number) && number != 100 => {
+            scanner.error(
+                source_string,
+                line,
+                format!("#version {} needs `es`", number),
+            );
+            Profile::Es
+        }
+        None if number == 100 => Profile::Es,
+        None => Profile::Core,
+        Some("core") => Profile::Core,
+        Some("compatibility") => Profile::Compatibility,
+        Some("es") => Profile::Es,
+        Some(profile) => {
+            scanner.error(
+                source_string,
+                line,
+                format!("unknown GLSL profile `{}`", profile),
+            );
+            Profile::Core
+        }
+    };
+
+    let known = match profile {
+        Profile::Es => ES_VERSIONS.contains(&number),
+        _ => DESKTOP_VERSIONS.contains(&number),
+    };
+
+    if !known {
+        scanner.error(
+            source_string,
+            line,
+            format!("unknown GLSL version {}", arguments),
+        );
+    } else if profile != Profile::Es && number < 150 && arguments.split_whitespace().count() > 1 {
+        scanner.error(
+            source_string,
+            line,
+            format!("GLSL {} does not have profiles", number),
+        );
+    }
+
+    Some(GlslVersion { number, profile })
+}
+
+fn check_identifiers(scanner: &mut Scanner, version: GlslVersion, tokens: &[Token]) {
+    for token in tokens {
+        if version.is_core() {
+            if let Some((_, hint)) = REMOVED_IN_CORE.iter().find(|(name, _)| *name == token.text) {
+                scanner.error_at(
+                    token,
+                    format!(
+                        "`{}` is not available in GLSL {}, {}",
+                        token.text, version, hint
+                    ),
+                );
+            }
+        }
+
+        if version.profile != Profile::Es {
+            if let Some((_, required)) = MIN_VERSIONS
+                .iter()
+                .find(|(name, required)| *name == token.text && version.number < *required)
+            {
+                scanner.error_at(
+                    token,
+                    format!("`{}` requires GLSL {}", token.text, required),
+                );
+            }
+        }
+    }
+}
+
+// Walks the global declarations, collecting `in`/`out` variables and checking that braces match
+// and `main` is defined
+fn check_declarations(
+    scanner: &mut Scanner,
+    stage: ShaderStage,
+    version: GlslVersion,
+    tokens: &[Token],
+) -> (Vec<InterfaceVariable>, Vec<InterfaceVariable>) {
+    let mut inputs = Vec::new();
+    let mut outputs = Vec::new();
+    let mut has_main = false;
+
+    let mut depth = 0usize;
+    let mut start = 0;
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token.text.as_str() {
+            "{" | "(" | "[" => depth += 1,
+            "}" | ")" | "]" => {
+                if depth == 0 {
+                    scanner.error_at(token, format!("unmatched `{}`", token.text));
+                    continue;
+                }
+                depth -= 1;
+
+                // A function body ends its definition, blocks and structs still need a `;`
+                if depth == 0 && token.text == "}" && is_function(&tokens[start..index]) {
+                    has_main |= tokens[start..index]
+                        .windows(2)
+                        .any(|pair| pair[0].text == "main" && pair[1].text == "(");
+                    start = index + 1;
+                }
+            }
+            ";" if depth == 0 => {
+                if let Some((storage, variables)) =
+                    parse_declaration(scanner, version, &tokens[start..index])
+                {
+                    match (storage, stage) {
+                        ("in" | "attribute", _) | ("varying", ShaderStage::Fragment) => {
+                            inputs.extend(variables)
+                        }
+                        _ => outputs.extend(variables),
+                    }
+                }
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if depth != 0 {
+        if let Some(last) = tokens.last() {
+            scanner.error_at(last, "unexpected end of file, missing closing bracket");
+        }
+    }
+
+    if !has_main {
+        scanner.error(0, 1, "no `main` function defined");
+    }
+
+    (inputs, outputs)
+}
+
+fn is_function(statement: &[Token]) -> bool {
+    let Some(body) = statement.iter().position(|token| token.text == "{") else {
+        return false;
+    };
+
+    statement[..body]
+        .last()
+        .is_some_and(|token| token.text == ")")
+        && !statement[..body].iter().any(|token| {
+            matches!(
+                token.text.as_str(),
+                "in" | "out" | "uniform" | "buffer" | "struct"
+            )
+        })
+}
+
+// Parses `[qualifiers] in|out type name[, name...]` (or an interface block) into its variables
+fn parse_declaration<'a>(
+    scanner: &mut Scanner,
+    version: GlslVersion,
+    statement: &'a [Token],
+) -> Option<(&'a str, Vec<InterfaceVariable>)> {
+    let mut index = 0;
+    let mut storage = None;
+
+    while let Some(token) = statement.get(index) {
+        match token.text.as_str() {
+            "layout" => {
+                let close = statement[index..]
+                    .iter()
+                    .position(|token| token.text == ")")?;
+                let arguments = &statement[index..index + close];
+
+                if let Some(location) = arguments.iter().find(|token| token.text == "location") {
+                    if version.profile != Profile::Es && version.number < 330 {
+                        scanner.error_at(location, "layout locations require GLSL 330");
+                    }
+                }
+                index += close + 1;
+                continue;
+            }
+            "in" | "out" | "attribute" | "varying" => {
+                if matches!(token.text.as_str(), "in" | "out")
+                    && version.profile != Profile::Es
+                    && version.number < 130
+                {
+                    scanner.error_at(
+                        token,
+                        format!("global `{}` variables require GLSL 130", token.text),
+                    );
+                }
+                storage = Some(token.text.as_str());
+            }
+            text if QUALIFIERS.contains(&text) => {}
+            _ => break,
+        }
+        index += 1;
+    }
+
+    let storage = storage?;
+    let type_name = statement.get(index)?;
+    let rest = &statement[index + 1..];
+
+    // Interface blocks are matched by block name
+    if rest.first().is_some_and(|token| token.text == "{") {
+        let variable = scanner.variable(type_name, "block".to_owned());
+        return Some((storage, vec![variable]));
+    }
+
+    let (type_suffix, rest) = array_suffix(rest);
+    let mut variables = Vec::new();
+
+    for declarator in rest.split(|token| token.text == ",") {
+        let Some((name, rest)) = declarator.split_first() else {
+            continue;
+        };
+        let (name_suffix, _) = array_suffix(rest);
+
+        let type_ = format!("{}{}{}", type_name.text, type_suffix, name_suffix);
+        variables.push(scanner.variable(name, type_));
+    }
+
+    Some((storage, variables))
+}
+
+// Collects a leading `[N]...` into a string
+fn array_suffix(tokens: &[Token]) -> (String, &[Token]) {
+    let mut suffix = String::new();
+    let mut index = 0;
+
+    while tokens.get(index).is_some_and(|token| token.text == "[") {
+        let Some(close) = tokens[index..].iter().position(|token| token.text == "]") else {
+            break;
+        };
+
+        for token in &tokens[index..=index + close] {
+            suffix.push_str(&token.text);
+        }
+        index += close + 1;
+    }
+
+    (suffix, &tokens[index..])
+}
+
+/// Guesses the stage of a shader file from its `name.<stage>.glsl` extension
+pub fn stage_from_path(path: &Path) -> Option<ShaderStage> {
+    let name = path.file_name()?.to_str()?;
+    let stem = name.strip_suffix(".glsl")?;
+
+    match stem.rsplit_once('.')?.1 {
+        "vert" => Some(ShaderStage::Vertex),
+        "tesc" => Some(ShaderStage::TessControl),
+        "tese" => Some(ShaderStage::TessEvaluation),
+        "geom" => Some(ShaderStage::Geometry),
+        "frag" => Some(ShaderStage::Fragment),
+        "comp" => Some(ShaderStage::Compute),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        fs,
+    };
+
+    use super::*;
+    use crate::shader::ShaderPreprocessor;
+
+    const SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/shaders");
+
+    // The defines shaders are built with (by main.rs and as shader library flags), covering both
+    // sides of every `#ifdef`
+    const DEFINE_SETS: &[&[(&str, &str)]] = &[
+        &[],
+        &[
+            ("GAMMA", "2.2"),
+            ("SRGB_FRAMEBUFFER", "1"),
+            ("BLINN", "1"),
+            ("HDR", "1"),
+        ],
+    ];
+
+    fn shader_files() -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = fs::read_dir(SHADER_DIR)
+            .expect("Failed to read the shader directory")
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "glsl")
+            })
+            .collect();
+        files.sort();
+
+        files
+    }
+
+    fn process(source: &str) -> PreprocessedSource {
+        ShaderPreprocessor::new(SHADER_DIR)
+            .process(source, "<test>")
+            .unwrap()
+    }
+
+    fn messages(result: Result<ShaderInterface, Vec<ValidationError>>) -> Vec<String> {
+        match result {
+            Ok(_) => Vec::new(),
+            Err(errors) => errors.into_iter().map(|error| error.message).collect(),
+        }
+    }
+
+    fn report(errors: &[ValidationError]) -> String {
+        errors
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn shader_files_are_valid() {
+        let preprocessor = ShaderPreprocessor::new(SHADER_DIR);
+        let mut errors = Vec::new();
+
+        for path in shader_files() {
+            let Some(stage) = stage_from_path(&path) else {
+                continue;
+            };
+
+            let source = preprocessor.process_file(&path).unwrap();
+            if let Err(stage_errors) = validate(stage, &source) {
+                errors.extend(stage_errors);
+            }
+
+            for defines in DEFINE_SETS {
+                let preprocessor = defines
+                    .iter()
+                    .fold(preprocessor.clone(), |preprocessor, (name, value)| {
+                        preprocessor.define(*name, value)
+                    });
+                let source = preprocessor.process_file(&path).unwrap();
+
+                for error in compile(stage, &source).err().unwrap_or_default() {
+                    if !errors.contains(&error) {
+                        errors.push(error);
+                    }
+                }
+            }
+        }
+
+        assert!(errors.is_empty(), "\n{}", report(&errors));
+    }
+
+    #[test]
+    fn vertex_outputs_match_fragment_inputs() {
+        let preprocessor = ShaderPreprocessor::new(SHADER_DIR);
+
+        // Pair up `name.vert.glsl` and `name.frag.glsl`. A vertex stage shared by several programs
+        // also pairs with every `name.program.frag.glsl`.
+        let mut programs: BTreeMap<String, Vec<ShaderInterface>> = BTreeMap::new();
+        let mut invalid = BTreeSet::new();
+        for path in shader_files() {
+            let Some(stage) = stage_from_path(&path) else {
+                continue;
+            };
+
+            let name = path.file_name().unwrap().to_string_lossy();
+            let name = name.rsplitn(3, '.').nth(2).unwrap().to_owned();
+
+            // Invalid stages are reported by `shader_files_are_valid`
+            let source = preprocessor.process_file(&path).unwrap();
+            match validate(stage, &source) {
+                Ok(interface) => programs.entry(name).or_default().push(interface),
+                Err(_) => {
+                    invalid.insert(name);
+                }
+            }
+        }
+
+        let mut errors = Vec::new();
+        fn find(stages: &[ShaderInterface], stage: ShaderStage) -> Option<&ShaderInterface> {
+            stages.iter().find(|interface| interface.stage == stage)
+        }
+
+        for (name, stages) in programs.iter().filter(|(name, _)| !invalid.contains(*name)) {
+            let shared = name
+                .split_once('.')
+                .filter(|(base, _)| !invalid.contains(*base))
+                .and_then(|(base, _)| programs.get(base));
+            let vertex = find(stages, ShaderStage::Vertex)
+                .or_else(|| shared.and_then(|shared| find(shared, ShaderStage::Vertex)));
+            let is_shared = programs
+                .keys()
+                .any(|other| other.starts_with(&format!("{}.", name)));
+
+            match (vertex, find(stages, ShaderStage::Fragment)) {
+                (Some(vertex), Some(fragment)) => {
+                    errors.extend(validate_interface(vertex, fragment));
+                }
+                (Some(_), None) if is_shared => {}
+                _ => panic!("Shader {} is missing a vertex or fragment stage", name),
+            }
+        }
+
+        assert!(errors.is_empty(), "\n{}", report(&errors));
+    }
+
+    #[test]
+    fn include_files_are_used() {
+        let preprocessor = ShaderPreprocessor::new(SHADER_DIR);
+        let files = shader_files();
+
+        let mut included = Vec::new();
+        for path in files.iter().filter(|path| stage_from_path(path).is_some()) {
+            let source = preprocessor.process_file(path).unwrap();
+            included.extend(source.paths().map(|path| fs::canonicalize(path).unwrap()));
+        }
+
+        // Only files reached from a stage get validated
+        for path in files.iter().filter(|path| stage_from_path(path).is_none()) {
+            assert!(
+                included.contains(&fs::canonicalize(path).unwrap()),
+                "{} is not included by any shader",
+                path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_removed_builtins_in_core() {
+        let source =
+            process("#version 330 core\nvoid main()\n{\n    gl_FragColor = vec4(1.0);\n}\n");
+        let errors = validate(ShaderStage::Fragment, &source).unwrap_err();
+
+        assert_eq!(errors[0].line, 4);
+        assert!(errors[0].message.contains("gl_FragColor"));
+        assert!(errors
+            .iter()
+            .any(|error| error.message.contains("no `out`")));
+    }
+
+    #[test]
+    fn allows_removed_builtins_in_compatibility() {
+        let compatibility = "#version 330 compatibility\nvoid main() { gl_FragColor = vec4(1.0); }";
+        let legacy =
+            "#version 120\nvarying vec3 color;\nvoid main() { gl_FragColor = vec4(color, 1.0); }";
+
+        assert!(messages(validate(ShaderStage::Fragment, &process(compatibility))).is_empty());
+        assert!(messages(validate(ShaderStage::Fragment, &process(legacy))).is_empty());
+    }
+
+    #[test]
+    fn rejects_features_newer_than_the_version() {
+        let source =
+            process("#version 120\nlayout (location = 0) in vec3 position;\nvoid main() {}\n");
+        let errors = messages(validate(ShaderStage::Vertex, &source));
+
+        assert!(errors
+            .iter()
+            .any(|error| error.contains("`layout` requires")));
+        assert!(errors
+            .iter()
+            .any(|error| error.contains("locations require")));
+        assert!(errors.iter().any(|error| error.contains("`in` variables")));
+    }
+
+    #[test]
+    fn reports_missing_version_and_main() {
+        let errors = messages(validate(
+            ShaderStage::Vertex,
+            &process("void helper() {}\n"),
+        ));
+
+        assert!(errors.iter().any(|error| error.contains("#version")));
+        assert!(errors.iter().any(|error| error.contains("`main`")));
+    }
+
+    #[test]
+    fn reports_lines_in_included_files() {
+        let dir = std::env::temp_dir().join("shader-validation-test");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("legacy.glsl"),
+            "// Legacy helper\n\nvoid shade() { gl_FragColor = vec4(0.0); }\n",
+        )
+        .unwrap();
+
+        let source = ShaderPreprocessor::new(&dir)
+            .process(
+                concat!(
+                    "#version 330 core\n",
+                    "out vec4 FragColor;\n",
+                    "#include \"legacy.glsl\"\n",
+                    "void main() {}\n",
+                ),
+                "<test>",
+            )
+            .unwrap();
+        let errors = validate(ShaderStage::Fragment, &source).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].path.ends_with("legacy.glsl"));
+        assert_eq!(errors[0].line, 3);
+    }
+
+    #[test]
+    fn parses_interface_declarations() {
+        let source = process(
+            "#version 330 core
+            layout (location = 0) in vec3 position;
+            flat out int id, ids[2];
+            out Block { vec2 uv; } block;
+            uniform mat4 model;
+            void main() {}",
+        );
+        let interface = validate(ShaderStage::Vertex, &source).unwrap();
+
+        assert_eq!(
+            interface.version,
+            GlslVersion {
+                number: 330,
+                profile: Profile::Core
+            }
+        );
+
+        let describe = |variables: &[InterfaceVariable]| {
+            variables
+                .iter()
+                .map(|variable| format!("{} {}", variable.type_, variable.name))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(describe(&interface.inputs), ["vec3 position"]);
+        assert_eq!(
+            describe(&interface.outputs),
+            ["int id", "int[2] ids", "block Block"]
+        );
+    }
+
+    #[test]
+    fn detects_interface_mismatches() {
+        let vertex = process("#version 330 core\nout vec3 normal;\nout vec2 uv;\nvoid main() {}");
+        let fragment = process(concat!(
+            "#version 330 core\n",
+            "in vec4 normal;\n",
+            "in vec3 color;\n",
+            "out vec4 FragColor;\n",
+            "void main() {}",
+        ));
+
+        let vertex = validate(ShaderStage::Vertex, &vertex).unwrap();
+        let fragment = validate(ShaderStage::Fragment, &fragment).unwrap();
+        let errors = validate_interface(&vertex, &fragment);
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].line, 2);
+        assert!(errors[0].message.contains("`vec4`"));
+        assert!(errors[1].message.contains("`color` is not written"));
+    }
+
+    fn compile_errors(stage: ShaderStage, source: &str) -> Vec<String> {
+        match compile(stage, &process(source)) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.into_iter().map(|error| error.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn compiles_desktop_glsl() {
+        let errors = compile_errors(
+            ShaderStage::Fragment,
+            "#version 330 core
+            in vec3 direction;
+            out vec4 FragColor;
+            uniform samplerCube skybox;
+            layout (std140) uniform Material { vec4 tint; };
+            uniform float exposure;
+            void main() {
+                FragColor = texture(skybox, direction) * tint * exposure;
+            }",
+        );
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let errors = compile_errors(
+            ShaderStage::Vertex,
+            "#version 330 core
+            void main() { gl_Position = vec4(float(gl_VertexID), 0.0, 0.0, 1.0); }",
+        );
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn compile_rejects_broken_shaders() {
+        let shaders = [
+            // Syntax error
+            "out vec4 FragColor;\nvoid main() { FragColor = vec4(1.0) }",
+            // Type error
+            "out vec4 FragColor;\nvoid main() { FragColor = vec3(1.0); }",
+            // Undeclared identifier
+            "out vec4 FragColor;\nvoid main() { FragColor = color; }",
+            // Bad swizzle
+            "out vec4 FragColor;\nvoid main() { FragColor = vec2(1.0).xyzq; }",
+        ];
+
+        for shader in shaders {
+            let source = format!("#version 330 core\n{}\n", shader);
+            let errors = compile_errors(ShaderStage::Fragment, &source);
+
+            assert!(!errors.is_empty(), "{} compiled", shader);
+            assert!(errors[0].starts_with("<test>:3: "), "{:?}", errors);
+        }
+    }
+
+    #[test]
+    fn compile_reports_lines_in_included_files() {
+        let dir = std::env::temp_dir().join("shader-compile-test");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("broken.glsl"),
+            "// Broken helper\n\nvec3 shade() { return undefinedColor; }\n",
+        )
+        .unwrap();
+
+        let source = ShaderPreprocessor::new(&dir)
+            .process(
+                concat!(
+                    "#version 330 core\n",
+                    "out vec4 FragColor;\n",
+                    "#include \"broken.glsl\"\n",
+                    "void main() {}\n",
+                ),
+                "<test>",
+            )
+            .unwrap();
+        let errors = compile(ShaderStage::Fragment, &source).unwrap_err();
+
+        assert!(errors[0].path.ends_with("broken.glsl"));
+        assert_eq!(errors[0].line, 3);
+    }
+}
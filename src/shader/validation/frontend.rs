@@ -0,0 +1,218 @@
+use std::error::Error;
+
+use naga::{
+    front::glsl::{Frontend, Options},
+    valid::{Capabilities, ValidationFlags, Validator},
+    SourceLocation,
+};
+
+use super::{PreprocessedSource, ShaderStage, ValidationError};
+use crate::shader::preprocessor::directive;
+
+// Parses and type checks a stage with naga's GLSL frontend, which catches what the scanner cannot:
+// syntax errors, type errors, undeclared identifiers, bad swizzles and calls with wrong arguments.
+//
+// naga only reads Vulkan flavored GLSL 440+, so the source is translated first, keeping every line
+// in place: the version is raised to 450, uniforms get binding points, inputs and outputs get
+// locations, combined samplers are split into a texture and a sampler and `gl_VertexID` becomes
+// `gl_VertexIndex`. The scanner still checks the source against the version it actually declares.
+
+/// Compiles a preprocessed stage, reporting errors at their original file and line
+///
+/// Geometry and tessellation stages are skipped since naga cannot parse them.
+pub fn compile(
+    stage: ShaderStage,
+    source: &PreprocessedSource,
+) -> Result<(), Vec<ValidationError>> {
+    let naga_stage = match stage {
+        ShaderStage::Vertex => naga::ShaderStage::Vertex,
+        ShaderStage::Fragment => naga::ShaderStage::Fragment,
+        ShaderStage::Compute => naga::ShaderStage::Compute,
+        _ => return Ok(()),
+    };
+
+    let glsl = translate(&source.source);
+    let error = |location: Option<SourceLocation>, message: String| {
+        let line = location.map_or(1, |location| location.line_number as usize);
+        let (source_string, line) = original_line(&glsl, line);
+
+        ValidationError {
+            path: source
+                .files
+                .get(source_string)
+                .map(|file| file.path.clone())
+                .unwrap_or_default(),
+            line,
+            message,
+        }
+    };
+
+    let module = Frontend::default()
+        .parse(&Options::from(naga_stage), &glsl)
+        .map_err(|errors| {
+            errors
+                .errors
+                .iter()
+                .map(|parse_error| error(parse_error.location(&glsl), parse_error.kind.to_string()))
+                .collect::<Vec<_>>()
+        })?;
+
+    Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|validation_error| {
+            let mut message = validation_error.as_inner().to_string();
+            let mut cause = validation_error.as_inner().source();
+            while let Some(inner) = cause {
+                message.push_str(&format!(": {}", inner));
+                cause = inner.source();
+            }
+
+            vec![error(validation_error.location(&glsl), message)]
+        })?;
+
+    Ok(())
+}
+
+// Rewrites the desktop GLSL the preprocessor produced into GLSL naga accepts, line by line
+fn translate(source: &str) -> String {
+    let mut output = String::with_capacity(source.len());
+    let mut replacements = vec![("gl_VertexID".to_owned(), "gl_VertexIndex".to_owned())];
+    let mut binding = 0;
+
+    // Give inputs and outputs without a location the ones nothing was explicitly assigned to
+    let explicit: Vec<usize> = source
+        .lines()
+        .filter_map(|line| {
+            let (_, rest) = line.split_once("location")?;
+            let digits = rest.trim_start().strip_prefix('=')?.trim_start();
+            let end = digits
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(digits.len());
+
+            digits[..end].parse().ok()
+        })
+        .collect();
+    let mut free_locations = [0, 0].map(|_| (0..).filter(|location| !explicit.contains(location)));
+
+    for line in source.lines() {
+        if directive(line).is_some_and(|(name, _)| name == "version") {
+            output.push_str("#version 450 core");
+        } else if let Some(direction) = interface_direction(line) {
+            let location = free_locations[direction].next().unwrap_or_default();
+            output.push_str(&format!("layout (location = {}) {}", location, line.trim()));
+        } else if let Some((layout, declaration)) = uniform_declaration(line) {
+            let mut words = declaration.split_whitespace();
+            let type_ = words.next().unwrap_or_default();
+            let name = words.next().unwrap_or_default().trim_end_matches(';');
+
+            if let Some(dimension) = type_.strip_prefix("sampler") {
+                output.push_str(&format!(
+                    "layout (binding = {}) uniform texture{} {}_texture; \
+                     layout (binding = {}) uniform sampler {}_sampler;",
+                    binding,
+                    dimension,
+                    name,
+                    binding + 1,
+                    name
+                ));
+                replacements.push((
+                    name.to_owned(),
+                    format!("{}({}_texture, {}_sampler)", type_, name, name),
+                ));
+                binding += 2;
+            } else {
+                let layout = layout.map_or(String::new(), |layout| format!("{}, ", layout));
+                output.push_str(&format!(
+                    "layout ({}binding = {}) uniform {}",
+                    layout, binding, declaration
+                ));
+                binding += 1;
+            }
+        } else {
+            output.push_str(&replace_identifiers(line, &replacements));
+        }
+
+        output.push('\n');
+    }
+
+    output
+}
+
+// Splits `[layout (...)] uniform <declaration>` into the layout arguments and the declaration
+fn uniform_declaration(line: &str) -> Option<(Option<&str>, &str)> {
+    let line = line.trim();
+
+    if let Some(rest) = line.strip_prefix("layout") {
+        let rest = rest.trim_start().strip_prefix('(')?;
+        let (arguments, rest) = rest.split_once(')')?;
+        let declaration = rest.trim_start().strip_prefix("uniform ")?;
+
+        Some((Some(arguments.trim()), declaration.trim()))
+    } else {
+        let declaration = line.strip_prefix("uniform ")?;
+
+        Some((None, declaration.trim()))
+    }
+}
+
+// Whether a line declares a global input (0) or output (1) without a layout
+fn interface_direction(line: &str) -> Option<usize> {
+    const INTERPOLATION: &[&str] = &["flat", "smooth", "noperspective", "centroid"];
+
+    let line = line.trim();
+    if !line.ends_with(';') {
+        return None;
+    }
+
+    let mut words = line
+        .split_whitespace()
+        .skip_while(|word| INTERPOLATION.contains(word));
+    match words.next()? {
+        "in" => Some(0),
+        "out" => Some(1),
+        _ => None,
+    }
+}
+
+// Replaces whole identifiers, leaving longer identifiers that contain them alone
+fn replace_identifiers(line: &str, replacements: &[(String, String)]) -> String {
+    let mut output = String::with_capacity(line.len());
+    let mut rest = line;
+
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+        let end = rest[start..]
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .map_or(rest.len(), |length| start + length);
+        let word = &rest[start..end];
+
+        output.push_str(&rest[..start]);
+        match replacements.iter().find(|(name, _)| name == word) {
+            Some((_, replacement)) => output.push_str(replacement),
+            None => output.push_str(word),
+        }
+        rest = &rest[end..];
+    }
+    output.push_str(rest);
+
+    output
+}
+
+// Maps a line of the preprocessed source to its `(source string, line)` by following the `#line`
+// directives before it
+fn original_line(source: &str, line: usize) -> (usize, usize) {
+    let mut mapped = (0, line);
+
+    for (index, text) in source.lines().take(line.saturating_sub(1)).enumerate() {
+        let Some(("line", arguments)) = directive(text) else {
+            continue;
+        };
+
+        let mut numbers = arguments.split_whitespace().map(str::parse::<usize>);
+        if let (Some(Ok(next_line)), Some(Ok(source_string))) = (numbers.next(), numbers.next()) {
+            // `#line` sets the number of the line after it
+            mapped = (source_string, next_line + line - (index + 2));
+        }
+    }
+
+    mapped
+}
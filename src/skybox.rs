@@ -0,0 +1,127 @@
+use std::rc::Rc;
+
+use crate::{
+    buffer::{Buffer, BufferTarget, BufferUsage, VertexArray, VertexLayout},
+    shader::{ShaderError, ShaderLibrary, ShaderVariant},
+    texture::TextureCube,
+};
+
+// A cube around the origin, also used to render into cube map faces
+#[rustfmt::skip]
+pub(crate) const CUBE_VERTICES: [f32; 108] = [
+   -1.0,  1.0, -1.0,  -1.0, -1.0, -1.0,   1.0, -1.0, -1.0,
+    1.0, -1.0, -1.0,   1.0,  1.0, -1.0,  -1.0,  1.0, -1.0,
+
+   -1.0, -1.0,  1.0,  -1.0, -1.0, -1.0,  -1.0,  1.0, -1.0,
+   -1.0,  1.0, -1.0,  -1.0,  1.0,  1.0,  -1.0, -1.0,  1.0,
+
+    1.0, -1.0, -1.0,   1.0, -1.0,  1.0,   1.0,  1.0,  1.0,
+    1.0,  1.0,  1.0,   1.0,  1.0, -1.0,   1.0, -1.0, -1.0,
+
+   -1.0, -1.0,  1.0,  -1.0,  1.0,  1.0,   1.0,  1.0,  1.0,
+    1.0,  1.0,  1.0,   1.0, -1.0,  1.0,  -1.0, -1.0,  1.0,
+
+   -1.0,  1.0, -1.0,   1.0,  1.0, -1.0,   1.0,  1.0,  1.0,
+    1.0,  1.0,  1.0,  -1.0,  1.0,  1.0,  -1.0,  1.0, -1.0,
+
+   -1.0, -1.0, -1.0,  -1.0, -1.0,  1.0,   1.0, -1.0, -1.0,
+    1.0, -1.0, -1.0,  -1.0, -1.0,  1.0,   1.0, -1.0,  1.0
+];
+
+const HDR_FLAG: &str = "HDR";
+
+/// Draws a cube map around the camera as the scene's background
+///
+/// The shader variant reads the `Camera` block and samples the cube map through a `skybox`
+/// uniform. It has to place the cube on the far plane (`gl_Position = clip.xyww`) so the skybox
+/// only shows where nothing else was drawn, which is why it is drawn after the scene.
+///
+/// HDR cube maps are drawn with the `HDR` flag set on the variant, which tone maps them with an
+/// `exposure` uniform.
+pub struct Skybox {
+    texture: Rc<TextureCube>,
+    variant: ShaderVariant,
+    // Set for HDR cube maps only
+    exposure: Option<f32>,
+    vao: VertexArray,
+    // Kept alive for the VAO
+    _vbo: Buffer,
+}
+
+impl Skybox {
+    pub fn new(texture: Rc<TextureCube>, variant: ShaderVariant) -> Self {
+        let vbo = Buffer::with_data(BufferTarget::Array, &CUBE_VERTICES, BufferUsage::Static);
+        let mut vao = VertexArray::new();
+        vao.set_layout(&vbo, &VertexLayout::new().float(0, 3));
+
+        Self {
+            texture,
+            variant,
+            exposure: None,
+            vao,
+            _vbo: vbo,
+        }
+    }
+
+    pub fn texture(&self) -> &Rc<TextureCube> {
+        &self.texture
+    }
+
+    /// Replaces the cube map, for example with the one of an
+    /// [`Environment`](crate::environment::Environment)
+    pub fn set_texture(&mut self, texture: Rc<TextureCube>) {
+        self.texture = texture;
+        self.set_exposure(None);
+    }
+
+    /// Replaces the cube map with an HDR one, tone mapped after scaling it by `exposure`
+    pub fn set_hdr_texture(&mut self, texture: Rc<TextureCube>, exposure: f32) {
+        self.texture = texture;
+        self.set_exposure(Some(exposure));
+    }
+
+    pub fn exposure(&self) -> Option<f32> {
+        self.exposure
+    }
+
+    fn set_exposure(&mut self, exposure: Option<f32>) {
+        let flags = self.variant.flags().filter(|flag| *flag != HDR_FLAG);
+        let variant = ShaderVariant::new(self.variant.name()).with_flags(flags);
+
+        self.variant = match exposure {
+            Some(_) => variant.with_flag(HDR_FLAG),
+            None => variant,
+        };
+        self.exposure = exposure;
+    }
+
+    pub fn variant(&self) -> &ShaderVariant {
+        &self.variant
+    }
+
+    pub fn vertex_array(&self) -> &VertexArray {
+        &self.vao
+    }
+
+    /// Draws the skybox, call this after every other opaque object
+    pub fn draw(&self, library: &mut ShaderLibrary) -> Result<(), ShaderError> {
+        let shader = library.variant(&self.variant)?;
+        shader.use_program();
+        shader.reset_texture_units();
+        shader.set_texture("skybox", self.texture.as_ref())?;
+        if let Some(exposure) = self.exposure {
+            shader.set_uniform("exposure", exposure)?;
+        }
+
+        self.vao.bind();
+        unsafe {
+            // The skybox sits exactly on the far plane, which fails the default LESS test against
+            // a cleared depth buffer
+            gl::DepthFunc(gl::LEQUAL);
+            gl::DrawArrays(gl::TRIANGLES, 0, 36);
+            gl::DepthFunc(gl::LESS);
+        }
+
+        Ok(())
+    }
+}
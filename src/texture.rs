@@ -1,18 +1,301 @@
-use std::path::Path;
+use std::{
+    borrow::Cow,
+    error::Error,
+    fmt::{self, Display},
+    io,
+    marker::PhantomData,
+    mem,
+    path::{Path, PathBuf},
+    ptr,
+};
 
 use gl::types::*;
+use image::{ColorType, DynamicImage, ImageError, Rgba, RgbaImage};
 
+pub use array::{max_array_texture_layers, Texture2dArray};
+pub use builder::TextureBuilder;
+pub use compressed::{CompressedFormat, CompressedImage};
+pub use cube::{max_cube_map_size, CubeFace, CubeLayout, TextureCube};
+pub use sampler::{Sampler, SamplerDescriptor};
+pub use volume::{max_3d_texture_size, Texture3d};
+
+mod array;
+mod builder;
+mod compressed;
+mod cube;
+mod sampler;
+mod volume;
+
+#[derive(Debug)]
+pub enum TextureError {
+    NotFound {
+        path: PathBuf,
+    },
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Decode {
+        path: PathBuf,
+        error: ImageError,
+    },
+    Unsupported {
+        description: String,
+    },
+    TooLarge {
+        width: u32,
+        height: u32,
+        max: u32,
+    },
+    InvalidCubeMap {
+        message: String,
+    },
+    NoLayers,
+    LayerSizeMismatch {
+        layer: usize,
+        width: u32,
+        height: u32,
+        expected_width: u32,
+        expected_height: u32,
+    },
+    TooManyLayers {
+        layers: u32,
+        max: u32,
+    },
+    VolumeSize {
+        path: PathBuf,
+        expected: usize,
+        found: usize,
+    },
+    Container {
+        path: PathBuf,
+        message: String,
+    },
+}
+
+impl Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextureError::NotFound { path } => {
+                write!(f, "Texture file not found: {}", path.display())
+            }
+            TextureError::Io { path, error } => {
+                write!(
+                    f,
+                    "Could not read texture file {}: {}",
+                    path.display(),
+                    error
+                )
+            }
+            TextureError::Decode { path, error } => {
+                write!(f, "Failed to decode texture {}: {}", path.display(), error)
+            }
+            TextureError::Unsupported { description } => {
+                write!(f, "Unsupported texture format: {}", description)
+            }
+            TextureError::TooLarge { width, height, max } => write!(
+                f,
+                "Texture is {}x{} but the driver supports at most {}x{}",
+                width, height, max, max
+            ),
+            TextureError::InvalidCubeMap { message } => {
+                write!(f, "Invalid cube map: {}", message)
+            }
+            TextureError::NoLayers => write!(f, "Layered textures need at least one image"),
+            TextureError::LayerSizeMismatch {
+                layer,
+                width,
+                height,
+                expected_width,
+                expected_height,
+            } => write!(
+                f,
+                "Layer {} is {}x{}, expected {}x{} like the first layer",
+                layer, width, height, expected_width, expected_height
+            ),
+            TextureError::TooManyLayers { layers, max } => write!(
+                f,
+                "Texture has {} layers but the driver supports at most {}",
+                layers, max
+            ),
+            TextureError::VolumeSize {
+                path,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Raw volume {} is {} bytes, expected {}",
+                path.display(),
+                found,
+                expected
+            ),
+            TextureError::Container { path, message } => {
+                write!(f, "Invalid texture file {}: {}", path.display(), message)
+            }
+        }
+    }
+}
+
+impl Error for TextureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TextureError::Io { error, .. } => Some(error),
+            TextureError::Decode { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// An owned 2D texture, deleted when dropped
 pub struct Texture2d {
     id: GLuint,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    compression: Option<CompressedFormat>,
+    sampler: SamplerDescriptor,
+    // Textures belong to the context's thread
+    _not_send: PhantomData<*const ()>,
 }
 
+/// The channels and component type a texture is stored with
+///
+/// Single channel (and single channel + alpha) formats are swizzled so they sample as grey. The
+/// `SRGB` formats hold sRGB encoded color, which the hardware converts to linear when sampling.
 #[allow(clippy::upper_case_acronyms)]
-#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TextureFormat {
-    RGB = gl::RGB,
-    RGBA = gl::RGBA,
+    R8,
+    RG8,
+    RGB8,
+    RGBA8,
+    SRGB8,
+    SRGBA8,
+    R16,
+    RG16,
+    RGB16,
+    RGBA16,
+    RGB16F,
+    RGBA16F,
+    RGB32F,
+    RGBA32F,
+}
+
+impl TextureFormat {
+    /// The format matching a decoded image's pixel layout
+    pub fn from_color_type(color_type: ColorType) -> Option<Self> {
+        match color_type {
+            ColorType::L8 => Some(TextureFormat::R8),
+            ColorType::La8 => Some(TextureFormat::RG8),
+            ColorType::Rgb8 => Some(TextureFormat::RGB8),
+            ColorType::Rgba8 => Some(TextureFormat::RGBA8),
+            ColorType::L16 => Some(TextureFormat::R16),
+            ColorType::La16 => Some(TextureFormat::RG16),
+            ColorType::Rgb16 => Some(TextureFormat::RGB16),
+            ColorType::Rgba16 => Some(TextureFormat::RGBA16),
+            ColorType::Rgb32F => Some(TextureFormat::RGB32F),
+            ColorType::Rgba32F => Some(TextureFormat::RGBA32F),
+            _ => None,
+        }
+    }
+
+    /// The sRGB version of an 8-bit color format
+    ///
+    /// Grey images are expanded to RGB since there are no single channel sRGB formats. Higher
+    /// precision formats are assumed to already be linear and are returned as is.
+    pub fn to_srgb(self) -> Self {
+        match self {
+            TextureFormat::R8 | TextureFormat::RGB8 => TextureFormat::SRGB8,
+            TextureFormat::RG8 | TextureFormat::RGBA8 => TextureFormat::SRGBA8,
+            format => format,
+        }
+    }
+
+    pub fn is_srgb(&self) -> bool {
+        matches!(self, TextureFormat::SRGB8 | TextureFormat::SRGBA8)
+    }
+
+    /// The size of one pixel of the data uploaded for this format, see [`gl_formats`]
+    ///
+    /// [`gl_formats`]: Self::gl_formats
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            TextureFormat::R8 => 1,
+            TextureFormat::RG8 | TextureFormat::R16 => 2,
+            TextureFormat::RGB8 | TextureFormat::SRGB8 => 3,
+            TextureFormat::RGBA8 | TextureFormat::SRGBA8 | TextureFormat::RG16 => 4,
+            TextureFormat::RGB16 => 6,
+            TextureFormat::RGBA16 => 8,
+            TextureFormat::RGB16F | TextureFormat::RGB32F => 12,
+            TextureFormat::RGBA16F | TextureFormat::RGBA32F => 16,
+        }
+    }
+
+    // The format whose images hold the pixel data uploaded for this one
+    fn pixel_layout(self) -> Self {
+        match self {
+            TextureFormat::SRGB8 => TextureFormat::RGB8,
+            TextureFormat::SRGBA8 => TextureFormat::RGBA8,
+            TextureFormat::RGB16F => TextureFormat::RGB32F,
+            TextureFormat::RGBA16F => TextureFormat::RGBA32F,
+            format => format,
+        }
+    }
+
+    /// The `(internal format, pixel format, pixel type)` passed to `glTexImage2D`
+    pub fn gl_formats(&self) -> (GLenum, GLenum, GLenum) {
+        match self {
+            TextureFormat::R8 => (gl::R8, gl::RED, gl::UNSIGNED_BYTE),
+            TextureFormat::RG8 => (gl::RG8, gl::RG, gl::UNSIGNED_BYTE),
+            TextureFormat::RGB8 => (gl::RGB8, gl::RGB, gl::UNSIGNED_BYTE),
+            TextureFormat::RGBA8 => (gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE),
+            TextureFormat::SRGB8 => (gl::SRGB8, gl::RGB, gl::UNSIGNED_BYTE),
+            TextureFormat::SRGBA8 => (gl::SRGB8_ALPHA8, gl::RGBA, gl::UNSIGNED_BYTE),
+            TextureFormat::R16 => (gl::R16, gl::RED, gl::UNSIGNED_SHORT),
+            TextureFormat::RG16 => (gl::RG16, gl::RG, gl::UNSIGNED_SHORT),
+            TextureFormat::RGB16 => (gl::RGB16, gl::RGB, gl::UNSIGNED_SHORT),
+            TextureFormat::RGBA16 => (gl::RGBA16, gl::RGBA, gl::UNSIGNED_SHORT),
+            TextureFormat::RGB16F => (gl::RGB16F, gl::RGB, gl::FLOAT),
+            TextureFormat::RGBA16F => (gl::RGBA16F, gl::RGBA, gl::FLOAT),
+            TextureFormat::RGB32F => (gl::RGB32F, gl::RGB, gl::FLOAT),
+            TextureFormat::RGBA32F => (gl::RGBA32F, gl::RGBA, gl::FLOAT),
+        }
+    }
+
+    // How the stored channels map to the RGBA seen by shaders
+    fn swizzle(&self) -> [GLenum; 4] {
+        match self {
+            TextureFormat::R8 | TextureFormat::R16 => [gl::RED, gl::RED, gl::RED, gl::ONE],
+            TextureFormat::RG8 | TextureFormat::RG16 => [gl::RED, gl::RED, gl::RED, gl::GREEN],
+            _ => [gl::RED, gl::GREEN, gl::BLUE, gl::ALPHA],
+        }
+    }
+
+    /// Converts an image to this format's pixel layout
+    pub fn convert(&self, image: &DynamicImage) -> DynamicImage {
+        match self {
+            TextureFormat::R8 => DynamicImage::ImageLuma8(image.to_luma8()),
+            TextureFormat::RG8 => DynamicImage::ImageLumaA8(image.to_luma_alpha8()),
+            TextureFormat::RGB8 | TextureFormat::SRGB8 => DynamicImage::ImageRgb8(image.to_rgb8()),
+            TextureFormat::RGBA8 | TextureFormat::SRGBA8 => {
+                DynamicImage::ImageRgba8(image.to_rgba8())
+            }
+            TextureFormat::R16 => DynamicImage::ImageLuma16(image.to_luma16()),
+            TextureFormat::RG16 => DynamicImage::ImageLumaA16(image.to_luma_alpha16()),
+            TextureFormat::RGB16 => DynamicImage::ImageRgb16(image.to_rgb16()),
+            TextureFormat::RGBA16 => DynamicImage::ImageRgba16(image.to_rgba16()),
+            // Half floats are uploaded as floats and converted by the driver
+            TextureFormat::RGB16F | TextureFormat::RGB32F => {
+                DynamicImage::ImageRgb32F(image.to_rgb32f())
+            }
+            TextureFormat::RGBA16F | TextureFormat::RGBA32F => {
+                DynamicImage::ImageRgba32F(image.to_rgba32f())
+            }
+        }
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
 pub enum TextureWrap {
     Repeat = gl::REPEAT,
@@ -21,6 +304,7 @@ pub enum TextureWrap {
     ClampToBorder = gl::CLAMP_TO_BORDER,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
 pub enum TextureFilter {
     Nearest = gl::NEAREST,
@@ -31,6 +315,7 @@ pub enum TextureFilter {
     LinearMipmapLinear = gl::LINEAR_MIPMAP_LINEAR,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
 pub enum ActiveTextureSlot {
     Texture0 = gl::TEXTURE0,
@@ -51,8 +336,119 @@ pub enum ActiveTextureSlot {
     Texture15 = gl::TEXTURE15,
 }
 
+/// The index of a texture unit, as assigned to a sampler uniform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TextureUnit(pub u32);
+
+impl From<ActiveTextureSlot> for TextureUnit {
+    fn from(slot: ActiveTextureSlot) -> Self {
+        Self(slot as u32 - gl::TEXTURE0)
+    }
+}
+
+/// A texture object that can be bound to a texture unit
+pub trait Texture {
+    /// The bind target (`gl::TEXTURE_2D`, `gl::TEXTURE_CUBE_MAP`, ...)
+    fn target(&self) -> GLenum;
+
+    /// # Safety
+    /// The id must not be deleted or outlive the texture
+    unsafe fn id(&self) -> GLuint;
+
+    fn bind_to_unit(&self, unit: TextureUnit) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit.0);
+            gl::BindTexture(self.target(), self.id());
+        }
+    }
+}
+
 impl Texture2d {
-    pub fn new<P: AsRef<Path>>(path: P, format: TextureFormat) -> Self {
+    /// Loads an image, storing it in the format matching its pixel layout
+    ///
+    /// Use [`TextureBuilder`] to pick the format or sampling state.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, TextureError> {
+        TextureBuilder::new().load(path)
+    }
+
+    /// Loads an image, converting it to `format` first if needed
+    pub fn with_format<P: AsRef<Path>>(
+        path: P,
+        format: TextureFormat,
+    ) -> Result<Self, TextureError> {
+        TextureBuilder::new().format(format).load(path)
+    }
+
+    /// Loads an image, or prints the error and returns the [`missing`](Self::missing) texture
+    pub fn new_or_missing<P: AsRef<Path>>(path: P) -> Self {
+        TextureBuilder::new().load_or_missing(path)
+    }
+
+    /// A magenta and black checkerboard that stands in for textures that failed to load
+    pub fn missing() -> Self {
+        TextureBuilder::new()
+            .filters(TextureFilter::Nearest, TextureFilter::Nearest)
+            .mipmaps(false)
+            .upload(&missing_image())
+            .expect("The missing texture is always uploadable")
+    }
+
+    /// Uploads a decoded image, detecting the format from its color type unless one is given
+    pub fn from_image(
+        img: &DynamicImage,
+        format: Option<TextureFormat>,
+    ) -> Result<Self, TextureError> {
+        match format {
+            Some(format) => TextureBuilder::new().format(format).upload(img),
+            None => TextureBuilder::new().upload(img),
+        }
+    }
+
+    /// Creates a texture without contents (or mipmaps), for rendering into
+    pub fn allocate(
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        sampler: SamplerDescriptor,
+    ) -> Self {
+        let mut texture: u32 = 0;
+
+        unsafe {
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+
+            sampler.apply_to_bound_texture(gl::TEXTURE_2D);
+            set_bound_texture_storage(gl::TEXTURE_2D, format, 1);
+            allocate_image(gl::TEXTURE_2D, 0, format, width, height);
+        }
+
+        Self {
+            id: texture,
+            width,
+            height,
+            format,
+            compression: None,
+            sampler,
+            _not_send: PhantomData,
+        }
+    }
+
+    fn upload(
+        img: &DynamicImage,
+        format: Option<TextureFormat>,
+        sampler: SamplerDescriptor,
+        mipmaps: bool,
+    ) -> Result<Self, TextureError> {
+        let max_size = max_texture_size();
+        if img.width() > max_size || img.height() > max_size {
+            return Err(TextureError::TooLarge {
+                width: img.width(),
+                height: img.height(),
+                max: max_size,
+            });
+        }
+
+        let (format, img) = prepare_image(img, format)?;
         let mut texture: u32 = 0;
 
         unsafe {
@@ -61,48 +457,97 @@ impl Texture2d {
             gl::BindTexture(gl::TEXTURE_2D, texture);
 
             // Set the texture wrapping/filtering options (on the currently bound texture object)
-            gl::TexParameteri(
-                gl::TEXTURE_2D,
-                gl::TEXTURE_WRAP_S,
-                TextureWrap::Repeat as i32,
-            );
-            gl::TexParameteri(
-                gl::TEXTURE_2D,
-                gl::TEXTURE_WRAP_T,
-                TextureWrap::Repeat as i32,
-            );
-            gl::TexParameteri(
-                gl::TEXTURE_2D,
-                gl::TEXTURE_MIN_FILTER,
-                TextureFilter::LinearMipmapLinear as i32,
-            );
-            gl::TexParameteri(
-                gl::TEXTURE_2D,
-                gl::TEXTURE_MAG_FILTER,
-                TextureFilter::Linear as i32,
-            );
+            sampler.apply_to_bound_texture(gl::TEXTURE_2D);
+            let levels = if mipmaps {
+                mip_levels(img.width(), img.height())
+            } else {
+                1
+            };
+            set_bound_texture_storage(gl::TEXTURE_2D, format, levels);
+
+            // Upload the texture data
+            tex_image(gl::TEXTURE_2D, format, &img);
+            if mipmaps {
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
+        }
 
-            // Load and generate the texture data
-            let img = image::io::Reader::open(path)
-                .expect("Failed to load texture file")
-                .decode()
-                .expect("Failed to decode texture file");
+        Ok(Self {
+            id: texture,
+            width: img.width(),
+            height: img.height(),
+            format,
+            compression: None,
+            sampler,
+            _not_send: PhantomData,
+        })
+    }
 
-            gl::TexImage2D(
-                gl::TEXTURE_2D,
-                0,
-                TextureFormat::RGB as i32,
-                img.width() as i32,
-                img.height() as i32,
-                0,
-                format as u32,
-                gl::UNSIGNED_BYTE,
-                img.as_bytes().as_ptr().cast(),
-            );
-            gl::GenerateMipmap(gl::TEXTURE_2D);
+    fn upload_compressed(
+        image: &CompressedImage,
+        format: CompressedFormat,
+        sampler: SamplerDescriptor,
+        mipmaps: bool,
+    ) -> Result<Self, TextureError> {
+        if image.faces() != 1 {
+            return Err(TextureError::Unsupported {
+                description: "cube maps as 2D textures".to_owned(),
+            });
         }
 
-        Self { id: texture }
+        let max_size = max_texture_size();
+        if image.width() > max_size || image.height() > max_size {
+            return Err(TextureError::TooLarge {
+                width: image.width(),
+                height: image.height(),
+                max: max_size,
+            });
+        }
+
+        let mut texture: u32 = 0;
+        let upload = unsafe {
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+
+            sampler.apply_to_bound_texture(gl::TEXTURE_2D);
+            compressed::tex_compressed_image(
+                gl::TEXTURE_2D,
+                &[gl::TEXTURE_2D],
+                image,
+                format,
+                mipmaps,
+            )
+        };
+
+        Ok(Self {
+            id: texture,
+            width: image.width(),
+            height: image.height(),
+            format: upload.format,
+            compression: upload.compression,
+            sampler,
+            _not_send: PhantomData,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The storage format, or for block compressed textures the uncompressed format with the
+    /// same channels
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    /// The block compression the texture is stored with, `None` for uncompressed textures and
+    /// compressed images the driver could not sample (which are decompressed when loading)
+    pub fn compressed_format(&self) -> Option<CompressedFormat> {
+        self.compression
     }
 
     pub fn bind_texture(&self) {
@@ -118,27 +563,480 @@ impl Texture2d {
         }
     }
 
-    pub fn set_wrap_s(wrap: TextureWrap) {
+    /// Gives up ownership of the texture without deleting it
+    pub fn leak(mut self) -> GLuint {
+        mem::take(&mut self.id)
+    }
+
+    /// The texture's own sampling state (used when no [`Sampler`] is bound to its unit)
+    pub fn sampler(&self) -> &SamplerDescriptor {
+        &self.sampler
+    }
+
+    pub fn set_sampler(&mut self, sampler: SamplerDescriptor) {
+        self.sampler = sampler;
+
         unsafe {
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, wrap as i32);
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            self.sampler.apply_to_bound_texture(gl::TEXTURE_2D);
         }
     }
 
-    pub fn set_wrap_t(wrap: TextureWrap) {
-        unsafe {
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, wrap as i32);
+    pub fn set_wrap_s(&mut self, wrap: TextureWrap) {
+        self.set_sampler(SamplerDescriptor {
+            wrap_s: wrap,
+            ..self.sampler
+        });
+    }
+
+    pub fn set_wrap_t(&mut self, wrap: TextureWrap) {
+        self.set_sampler(SamplerDescriptor {
+            wrap_t: wrap,
+            ..self.sampler
+        });
+    }
+
+    pub fn set_min_filter(&mut self, filter: TextureFilter) {
+        self.set_sampler(SamplerDescriptor {
+            min_filter: filter,
+            ..self.sampler
+        });
+    }
+
+    pub fn set_mag_filter(&mut self, filter: TextureFilter) {
+        self.set_sampler(SamplerDescriptor {
+            mag_filter: filter,
+            ..self.sampler
+        });
+    }
+}
+
+fn read_error(path: &Path, error: io::Error) -> TextureError {
+    match error.kind() {
+        io::ErrorKind::NotFound => TextureError::NotFound {
+            path: path.to_owned(),
+        },
+        _ => TextureError::Io {
+            path: path.to_owned(),
+            error,
+        },
+    }
+}
+
+fn decode_file(path: &Path) -> Result<DynamicImage, TextureError> {
+    let reader = image::io::Reader::open(path).map_err(|error| read_error(path, error))?;
+
+    reader.decode().map_err(|error| match error {
+        ImageError::Unsupported(error) => TextureError::Unsupported {
+            description: format!("{}: {}", path.display(), error),
+        },
+        error => TextureError::Decode {
+            path: path.to_owned(),
+            error,
+        },
+    })
+}
+
+// A 64x64 magenta and black checkerboard
+fn missing_image() -> DynamicImage {
+    const SIZE: u32 = 64;
+    const CELL: u32 = 8;
+
+    let img = RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+        if (x / CELL + y / CELL).is_multiple_of(2) {
+            Rgba([255, 0, 255, 255])
+        } else {
+            Rgba([0, 0, 0, 255])
         }
+    });
+
+    DynamicImage::ImageRgba8(img)
+}
+
+// Picks the storage format of an image (detected from its color type unless given) and converts
+// the pixels to that layout if needed
+fn prepare_image(
+    img: &DynamicImage,
+    format: Option<TextureFormat>,
+) -> Result<(TextureFormat, Cow<'_, DynamicImage>), TextureError> {
+    let detected = TextureFormat::from_color_type(img.color());
+    let format = format
+        .or(detected)
+        .ok_or_else(|| TextureError::Unsupported {
+            description: format!("{:?} images", img.color()),
+        })?;
+
+    if Some(format.pixel_layout()) == detected {
+        Ok((format, Cow::Borrowed(img)))
+    } else {
+        Ok((format, Cow::Owned(format.convert(img))))
     }
+}
 
-    pub fn set_min_filter(filter: TextureFilter) {
-        unsafe {
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filter as i32);
+// The number of levels in a full mip chain
+fn mip_levels(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+// Sets up the mip range and swizzle of the bound texture
+unsafe fn set_bound_texture_storage(target: GLenum, format: TextureFormat, levels: u32) {
+    gl::TexParameteri(target, gl::TEXTURE_MAX_LEVEL, levels as GLint - 1);
+
+    // Make grey and grey + alpha images sample as grey instead of red
+    let swizzle = format.swizzle().map(|channel| channel as GLint);
+    gl::TexParameteriv(target, gl::TEXTURE_SWIZZLE_RGBA, swizzle.as_ptr());
+}
+
+// Turns an image upside down when `flip` is set, so that its first row lands at GL's bottom-left
+// texture origin
+fn flip_image(img: &DynamicImage, flip: bool) -> Cow<'_, DynamicImage> {
+    if flip {
+        Cow::Owned(img.flipv())
+    } else {
+        Cow::Borrowed(img)
+    }
+}
+
+// Turns every layer (or slice) upside down when `flip` is set, keeping their order
+fn flip_layers(layers: &[DynamicImage], flip: bool) -> Cow<'_, [DynamicImage]> {
+    if flip {
+        Cow::Owned(layers.iter().map(DynamicImage::flipv).collect())
+    } else {
+        Cow::Borrowed(layers)
+    }
+}
+
+// Uploads the base level of a 2D image target (`GL_TEXTURE_2D` or a cube map face)
+unsafe fn tex_image(target: GLenum, format: TextureFormat, img: &DynamicImage) {
+    let (internal_format, pixel_format, pixel_type) = format.gl_formats();
+
+    with_unpack_alignment(format, img.width(), || {
+        gl::TexImage2D(
+            target,
+            0,
+            internal_format as i32,
+            img.width() as i32,
+            img.height() as i32,
+            0,
+            pixel_format,
+            pixel_type,
+            img.as_bytes().as_ptr().cast(),
+        )
+    });
+}
+
+// Allocates one level of a 2D image target without filling it
+unsafe fn allocate_image(
+    target: GLenum,
+    level: u32,
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+) {
+    let (internal_format, pixel_format, pixel_type) = format.gl_formats();
+
+    gl::TexImage2D(
+        target,
+        level as GLint,
+        internal_format as i32,
+        width as i32,
+        height as i32,
+        0,
+        pixel_format,
+        pixel_type,
+        ptr::null(),
+    );
+}
+
+// Checks that there is at least one layer and that every layer has the size of the first
+fn check_layers(layers: &[DynamicImage]) -> Result<(u32, u32), TextureError> {
+    let first = layers.first().ok_or(TextureError::NoLayers)?;
+    let (width, height) = (first.width(), first.height());
+
+    for (layer, img) in layers.iter().enumerate() {
+        if img.width() != width || img.height() != height {
+            return Err(TextureError::LayerSizeMismatch {
+                layer,
+                width: img.width(),
+                height: img.height(),
+                expected_width: width,
+                expected_height: height,
+            });
         }
     }
 
-    pub fn set_mag_filter(filter: TextureFilter) {
-        unsafe {
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter as i32);
+    Ok((width, height))
+}
+
+// GL's initial unpack alignment, restored after every upload so pixel data uploaded elsewhere
+// is not read with the alignment of the previous texture
+const DEFAULT_UNPACK_ALIGNMENT: GLint = 4;
+
+// The largest unpack alignment GL accepts (1, 2, 4 or 8) that every row of `row_bytes` bytes
+// starts on. Images are tightly packed, so odd sized RGB rows often only allow 1.
+fn unpack_alignment(row_bytes: usize) -> GLint {
+    [8, 4, 2]
+        .into_iter()
+        .find(|&alignment| row_bytes.is_multiple_of(alignment))
+        .unwrap_or(1) as GLint
+}
+
+// The unpack alignment of tightly packed `format` rows of `width` texels
+fn row_alignment(format: TextureFormat, width: u32) -> GLint {
+    unpack_alignment(width as usize * format.bytes_per_pixel())
+}
+
+// Runs `upload` with the row alignment of tightly packed `format` pixel data, then restores the
+// default. GL assumes rows padded to 4 bytes, which skews RGB images whose width is not a
+// multiple of 4.
+pub(crate) unsafe fn with_unpack_alignment<R>(
+    format: TextureFormat,
+    width: u32,
+    upload: impl FnOnce() -> R,
+) -> R {
+    gl::PixelStorei(gl::UNPACK_ALIGNMENT, row_alignment(format, width));
+    let result = upload();
+    gl::PixelStorei(gl::UNPACK_ALIGNMENT, DEFAULT_UNPACK_ALIGNMENT);
+
+    result
+}
+
+// Allocates the first level of a bound array or 3D texture without filling it
+unsafe fn allocate_layers(
+    target: GLenum,
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+    depth: u32,
+    data: *const GLvoid,
+) {
+    let (internal_format, pixel_format, pixel_type) = format.gl_formats();
+
+    with_unpack_alignment(format, width, || {
+        gl::TexImage3D(
+            target,
+            0,
+            internal_format as i32,
+            width as i32,
+            height as i32,
+            depth as i32,
+            0,
+            pixel_format,
+            pixel_type,
+            data,
+        )
+    });
+}
+
+// Uploads one layer (or slice) of the first level of a bound array or 3D texture
+unsafe fn tex_layer(target: GLenum, layer: u32, format: TextureFormat, img: &DynamicImage) {
+    let (_, pixel_format, pixel_type) = format.gl_formats();
+
+    with_unpack_alignment(format, img.width(), || {
+        gl::TexSubImage3D(
+            target,
+            0,
+            0,
+            0,
+            layer as i32,
+            img.width() as i32,
+            img.height() as i32,
+            1,
+            pixel_format,
+            pixel_type,
+            img.as_bytes().as_ptr().cast(),
+        )
+    });
+}
+
+/// The largest width or height the driver accepts for 2D textures (`GL_MAX_TEXTURE_SIZE`)
+pub fn max_texture_size() -> u32 {
+    let mut size: GLint = 0;
+    unsafe { gl::GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut size) };
+
+    size.max(0) as u32
+}
+
+impl Texture for Texture2d {
+    fn target(&self) -> GLenum {
+        gl::TEXTURE_2D
+    }
+
+    unsafe fn id(&self) -> GLuint {
+        self.id
+    }
+}
+
+impl Drop for Texture2d {
+    fn drop(&mut self) {
+        if self.id != 0 {
+            unsafe { gl::DeleteTextures(1, &self.id) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    // A 3x2 RGB image whose pixels encode their position
+    fn odd_rgb_image() -> DynamicImage {
+        let img = RgbImage::from_fn(3, 2, |x, y| Rgb([x as u8, y as u8, (y * 3 + x) as u8]));
+
+        DynamicImage::ImageRgb8(img)
+    }
+
+    #[test]
+    fn odd_rgb_rows_are_byte_aligned() {
+        let img = odd_rgb_image();
+        let row_bytes = img.width() as usize * TextureFormat::RGB8.bytes_per_pixel();
+
+        // Decoded images have no row padding for GL to skip
+        assert_eq!(img.as_bytes().len(), row_bytes * img.height() as usize);
+        assert_eq!(unpack_alignment(row_bytes), 1);
+    }
+
+    #[test]
+    fn unpack_alignment_matches_row_size() {
+        let alignment = |format: TextureFormat, width: usize| {
+            unpack_alignment(width * format.bytes_per_pixel())
+        };
+
+        assert_eq!(alignment(TextureFormat::RGB8, 1), 1);
+        assert_eq!(alignment(TextureFormat::RGB8, 2), 2);
+        assert_eq!(alignment(TextureFormat::RGB8, 4), 4);
+        assert_eq!(alignment(TextureFormat::RGB8, 8), 8);
+        assert_eq!(alignment(TextureFormat::R8, 7), 1);
+        assert_eq!(alignment(TextureFormat::RG8, 5), 2);
+        assert_eq!(alignment(TextureFormat::RGBA8, 5), 4);
+        assert_eq!(alignment(TextureFormat::RGB16, 3), 2);
+        assert_eq!(alignment(TextureFormat::RGB32F, 3), 4);
+        assert_eq!(alignment(TextureFormat::RGBA32F, 3), 8);
+    }
+
+    #[test]
+    fn cached_environment_rows_set_their_own_alignment() {
+        // Odd sized prefiltered levels have RGB float rows that are only 4 byte aligned, reading
+        // them with the 8 an RGBA float upload needs would skip 4 bytes after every row
+        assert_eq!(row_alignment(TextureFormat::RGB32F, 25), 4);
+        assert_eq!(row_alignment(TextureFormat::RGBA32F, 25), 8);
+        assert_eq!(row_alignment(TextureFormat::RGB32F, 128), 8);
+    }
+
+    #[test]
+    fn bytes_per_pixel_matches_decoded_images() {
+        let color_types = [
+            ColorType::L8,
+            ColorType::La8,
+            ColorType::Rgb8,
+            ColorType::Rgba8,
+            ColorType::L16,
+            ColorType::La16,
+            ColorType::Rgb16,
+            ColorType::Rgba16,
+            ColorType::Rgb32F,
+            ColorType::Rgba32F,
+        ];
+
+        for color_type in color_types {
+            let format = TextureFormat::from_color_type(color_type).unwrap();
+            assert_eq!(
+                format.bytes_per_pixel(),
+                color_type.bytes_per_pixel() as usize,
+                "{:?}",
+                color_type
+            );
+        }
+
+        // Formats without a color type of their own upload the pixels of another one
+        assert_eq!(TextureFormat::SRGB8.bytes_per_pixel(), 3);
+        assert_eq!(TextureFormat::SRGBA8.bytes_per_pixel(), 4);
+        assert_eq!(TextureFormat::RGB16F.bytes_per_pixel(), 12);
+        assert_eq!(TextureFormat::RGBA16F.bytes_per_pixel(), 16);
+    }
+
+    #[test]
+    fn check_layers_returns_shared_size() {
+        let layers = [odd_rgb_image(), odd_rgb_image()];
+
+        assert_eq!(check_layers(&layers).unwrap(), (3, 2));
+    }
+
+    #[test]
+    fn check_layers_rejects_empty_and_mismatched_layers() {
+        assert!(matches!(check_layers(&[]), Err(TextureError::NoLayers)));
+
+        let layers = [odd_rgb_image(), odd_rgb_image(), odd_rgb_image().rotate90()];
+        match check_layers(&layers) {
+            Err(TextureError::LayerSizeMismatch {
+                layer,
+                width,
+                height,
+                expected_width,
+                expected_height,
+            }) => assert_eq!(
+                (layer, width, height, expected_width, expected_height),
+                (2, 2, 3, 3, 2)
+            ),
+            result => panic!("expected a layer size mismatch, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn raw_volume_size_is_checked_before_upload() {
+        let path = std::env::temp_dir().join(format!("volume-size-{}.raw", std::process::id()));
+        std::fs::write(&path, [0u8; 2 * 2 * 2 * 3 - 1]).unwrap();
+
+        let result = TextureBuilder::new().load_raw_volume(&path, [2, 2, 2], TextureFormat::RGB8);
+        std::fs::remove_file(&path).unwrap();
+
+        match result {
+            Err(TextureError::VolumeSize {
+                path: error_path,
+                expected,
+                found,
+            }) => {
+                assert_eq!(error_path, path);
+                assert_eq!((expected, found), (24, 23));
+            }
+            Err(error) => panic!("expected a volume size error, got {}", error),
+            Ok(_) => panic!("expected a volume size error"),
+        }
+    }
+
+    #[test]
+    fn flip_reverses_rows() {
+        let img = odd_rgb_image();
+        let flipped = flip_image(&img, true);
+
+        assert_eq!((flipped.width(), flipped.height()), (3, 2));
+        let rows: Vec<_> = img.as_bytes().chunks(9).collect();
+        let flipped_rows: Vec<_> = flipped.as_bytes().chunks(9).collect();
+        assert_eq!(flipped_rows, [rows[1], rows[0]]);
+    }
+
+    #[test]
+    fn no_flip_borrows_image() {
+        let img = odd_rgb_image();
+
+        assert!(matches!(flip_image(&img, false), Cow::Borrowed(_)));
+        assert!(matches!(flip_layers(&[img], false), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn flip_layers_keeps_layer_order() {
+        let layers = [odd_rgb_image(), odd_rgb_image().fliph()];
+        let flipped = flip_layers(&layers, true);
+
+        assert_eq!(flipped.len(), 2);
+        for (layer, flipped) in layers.iter().zip(flipped.iter()) {
+            assert_eq!(flipped.as_bytes(), layer.flipv().as_bytes());
+            assert_eq!(
+                flipped.to_rgb8().get_pixel(2, 0),
+                layer.to_rgb8().get_pixel(2, 1)
+            );
         }
     }
 }
@@ -0,0 +1,173 @@
+use std::{marker::PhantomData, mem, path::Path, ptr};
+
+use gl::types::*;
+use image::DynamicImage;
+
+use super::{
+    allocate_layers, check_layers, max_texture_size, mip_levels, prepare_image,
+    set_bound_texture_storage, tex_layer, SamplerDescriptor, Texture, TextureBuilder, TextureError,
+    TextureFormat,
+};
+
+/// An owned array of equally sized 2D layers, deleted when dropped
+///
+/// Sampled with `sampler2DArray` and a `vec3(u, v, layer)` coordinate. Layers are never filtered
+/// into each other, which makes arrays a good fit for terrain splat layers and animation frames.
+pub struct Texture2dArray {
+    id: GLuint,
+    width: u32,
+    height: u32,
+    layers: u32,
+    format: TextureFormat,
+    sampler: SamplerDescriptor,
+    // Textures belong to the context's thread
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Texture2dArray {
+    /// Loads one layer per image, see [`TextureBuilder::load_array`]
+    pub fn new<P: AsRef<Path>>(paths: &[P]) -> Result<Self, TextureError> {
+        TextureBuilder::new().load_array(paths)
+    }
+
+    pub(super) fn upload(
+        layers: &[DynamicImage],
+        format: Option<TextureFormat>,
+        sampler: SamplerDescriptor,
+        mipmaps: bool,
+    ) -> Result<Self, TextureError> {
+        let (width, height) = check_layers(layers)?;
+
+        let max_size = max_texture_size();
+        if width > max_size || height > max_size {
+            return Err(TextureError::TooLarge {
+                width,
+                height,
+                max: max_size,
+            });
+        }
+
+        let max_layers = max_array_texture_layers();
+        if layers.len() as u32 > max_layers {
+            return Err(TextureError::TooManyLayers {
+                layers: layers.len() as u32,
+                max: max_layers,
+            });
+        }
+
+        // Every layer is stored in the format picked for the first one
+        let (format, first) = prepare_image(&layers[0], format)?;
+        let mut prepared = vec![first];
+        for img in &layers[1..] {
+            prepared.push(prepare_image(img, Some(format))?.1);
+        }
+
+        let levels = if mipmaps {
+            mip_levels(width, height)
+        } else {
+            1
+        };
+        let mut texture: u32 = 0;
+
+        unsafe {
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, texture);
+
+            sampler.apply_to_bound_texture(gl::TEXTURE_2D_ARRAY);
+            set_bound_texture_storage(gl::TEXTURE_2D_ARRAY, format, levels);
+
+            allocate_layers(
+                gl::TEXTURE_2D_ARRAY,
+                format,
+                width,
+                height,
+                prepared.len() as u32,
+                ptr::null(),
+            );
+            for (layer, img) in prepared.iter().enumerate() {
+                tex_layer(gl::TEXTURE_2D_ARRAY, layer as u32, format, img);
+            }
+            if mipmaps {
+                gl::GenerateMipmap(gl::TEXTURE_2D_ARRAY);
+            }
+        }
+
+        Ok(Self {
+            id: texture,
+            width,
+            height,
+            layers: prepared.len() as u32,
+            format,
+            sampler,
+            _not_send: PhantomData,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn layers(&self) -> u32 {
+        self.layers
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn bind_texture(&self) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
+        }
+    }
+
+    /// Gives up ownership of the texture without deleting it
+    pub fn leak(mut self) -> GLuint {
+        mem::take(&mut self.id)
+    }
+
+    /// The texture's own sampling state (used when no [`Sampler`](super::Sampler) is bound to
+    /// its unit)
+    pub fn sampler(&self) -> &SamplerDescriptor {
+        &self.sampler
+    }
+
+    pub fn set_sampler(&mut self, sampler: SamplerDescriptor) {
+        self.sampler = sampler;
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
+            self.sampler.apply_to_bound_texture(gl::TEXTURE_2D_ARRAY);
+        }
+    }
+}
+
+/// The most layers the driver accepts for array textures (`GL_MAX_ARRAY_TEXTURE_LAYERS`)
+pub fn max_array_texture_layers() -> u32 {
+    let mut layers: GLint = 0;
+    unsafe { gl::GetIntegerv(gl::MAX_ARRAY_TEXTURE_LAYERS, &mut layers) };
+
+    layers.max(0) as u32
+}
+
+impl Texture for Texture2dArray {
+    fn target(&self) -> GLenum {
+        gl::TEXTURE_2D_ARRAY
+    }
+
+    unsafe fn id(&self) -> GLuint {
+        self.id
+    }
+}
+
+impl Drop for Texture2dArray {
+    fn drop(&mut self) {
+        if self.id != 0 {
+            unsafe { gl::DeleteTextures(1, &self.id) };
+        }
+    }
+}
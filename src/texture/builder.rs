@@ -0,0 +1,365 @@
+use std::{borrow::Cow, fs, path::Path};
+
+use image::DynamicImage;
+
+use super::{
+    compressed, decode_file, flip_image, flip_layers, read_error, CompressedFormat,
+    CompressedImage, CubeLayout, SamplerDescriptor, Texture2d, Texture2dArray, Texture3d,
+    TextureCube, TextureError, TextureFilter, TextureFormat, TextureWrap,
+};
+
+/// Configures how a texture ([`Texture2d`], [`TextureCube`], [`Texture2dArray`] or [`Texture3d`])
+/// is stored and sampled before it is loaded
+///
+/// ```ignore
+/// let texture = TextureBuilder::new()
+///     .srgb(true)
+///     .wrap(TextureWrap::ClampToEdge)
+///     .filters(TextureFilter::Nearest, TextureFilter::Nearest)
+///     .mipmaps(false)
+///     .load("assets/awesomeface.png")?;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureBuilder {
+    format: Option<TextureFormat>,
+    srgb: bool,
+    sampler: SamplerDescriptor,
+    mipmaps: bool,
+    flip: bool,
+}
+
+impl Default for TextureBuilder {
+    fn default() -> Self {
+        Self {
+            format: None,
+            srgb: false,
+            sampler: SamplerDescriptor::default(),
+            mipmaps: true,
+            flip: false,
+        }
+    }
+}
+
+impl TextureBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forces the storage format instead of detecting it from the image
+    pub fn format(mut self, format: TextureFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Marks the image as sRGB encoded color (albedo, diffuse maps, UI) rather than linear data
+    /// (normal maps, roughness, masks), see [`TextureFormat::to_srgb`]
+    pub fn srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    /// Replaces the whole sampling state
+    pub fn sampler(mut self, sampler: SamplerDescriptor) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    pub fn wrap(mut self, wrap: TextureWrap) -> Self {
+        self.sampler = self.sampler.wrap(wrap);
+        self
+    }
+
+    pub fn filters(mut self, min_filter: TextureFilter, mag_filter: TextureFilter) -> Self {
+        self.sampler = self.sampler.filters(min_filter, mag_filter);
+        self
+    }
+
+    pub fn border_color(mut self, border_color: [f32; 4]) -> Self {
+        self.sampler = self.sampler.border_color(border_color);
+        self
+    }
+
+    pub fn lod_bias(mut self, lod_bias: f32) -> Self {
+        self.sampler = self.sampler.lod_bias(lod_bias);
+        self
+    }
+
+    /// Whether to generate mipmaps (on by default)
+    ///
+    /// Without mipmaps a mipmapped minification filter falls back to its non-mipmapped version.
+    pub fn mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    /// Whether to flip images vertically before uploading them (off by default)
+    ///
+    /// Images start with their top row while GL treats the first row it receives as the bottom
+    /// (t = 0), so unflipped images appear upside down with bottom-left texture coordinates.
+    /// Flipping applies to 2D textures and each layer of array and 3D textures. Cube map faces,
+    /// block compressed files and raw volumes are uploaded as stored.
+    pub fn flip_vertically(mut self, flip: bool) -> Self {
+        self.flip = flip;
+        self
+    }
+
+    /// Loads an image, or a block compressed `.ktx2` or `.dds` file (see
+    /// [`upload_compressed`](Self::upload_compressed))
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<Texture2d, TextureError> {
+        let path = path.as_ref();
+        if compressed::is_container(path) {
+            return self.upload_compressed(&CompressedImage::read(path)?);
+        }
+
+        self.upload(&decode_file(path)?)
+    }
+
+    /// Loads the texture, or prints the error and returns the missing texture
+    pub fn load_or_missing<P: AsRef<Path>>(&self, path: P) -> Texture2d {
+        self.load(path).unwrap_or_else(|error| {
+            eprintln!("{} (using the missing texture instead)", error);
+            Texture2d::missing()
+        })
+    }
+
+    pub fn upload(&self, img: &DynamicImage) -> Result<Texture2d, TextureError> {
+        let (img, format) = self.upload_source(img);
+
+        Texture2d::upload(&img, format, self.resolve_sampler(), self.mipmaps)
+    }
+
+    /// Uploads a block compressed image with the mip levels stored in it
+    ///
+    /// The format comes from the image, `srgb` switches color formats to their sRGB versions.
+    /// Images without a mip chain get no mipmaps, since compressed levels cannot be generated.
+    pub fn upload_compressed(&self, image: &CompressedImage) -> Result<Texture2d, TextureError> {
+        let (format, builder) = self.resolve_compressed(image);
+
+        Texture2d::upload_compressed(image, format, builder.resolve_sampler(), builder.mipmaps)
+    }
+
+    /// Loads a cube map from a single cross or strip image (see [`CubeLayout`]), or from a
+    /// `.ktx2` or `.dds` cube map
+    pub fn load_cube<P: AsRef<Path>>(&self, path: P) -> Result<TextureCube, TextureError> {
+        let path = path.as_ref();
+        if compressed::is_container(path) {
+            return self.upload_compressed_cube(&CompressedImage::read(path)?);
+        }
+
+        let img = decode_file(path)?;
+        let layout = CubeLayout::detect(img.width(), img.height()).ok_or_else(|| {
+            TextureError::InvalidCubeMap {
+                message: format!(
+                    "{} is {}x{}, expected a 4:3 or 3:4 cross or a 6:1 or 1:6 strip",
+                    path.display(),
+                    img.width(),
+                    img.height()
+                ),
+            }
+        })?;
+
+        self.upload_cube(&layout.split(&img))
+    }
+
+    /// Loads a cube map from six images in [`CubeFace::ALL`](super::CubeFace::ALL) order
+    pub fn load_cube_faces<P: AsRef<Path>>(
+        &self,
+        paths: &[P],
+    ) -> Result<TextureCube, TextureError> {
+        self.upload_cube(&decode_files(paths)?)
+    }
+
+    /// Loads a cube map image, or prints the error and returns the missing cube map
+    pub fn load_cube_or_missing<P: AsRef<Path>>(&self, path: P) -> TextureCube {
+        self.load_cube(path).unwrap_or_else(|error| {
+            eprintln!("{} (using the missing texture instead)", error);
+            TextureCube::missing()
+        })
+    }
+
+    /// Uploads six square faces of the same size as a cube map
+    pub fn upload_cube(&self, faces: &[DynamicImage]) -> Result<TextureCube, TextureError> {
+        let format = faces.first().and_then(|face| self.resolve_format(face));
+
+        TextureCube::upload(faces, format, self.resolve_sampler(), self.mipmaps)
+    }
+
+    /// Uploads the six faces of a block compressed cube map, see
+    /// [`upload_compressed`](Self::upload_compressed)
+    pub fn upload_compressed_cube(
+        &self,
+        image: &CompressedImage,
+    ) -> Result<TextureCube, TextureError> {
+        let (format, builder) = self.resolve_compressed(image);
+
+        TextureCube::upload_compressed(image, format, builder.resolve_sampler(), builder.mipmaps)
+    }
+
+    /// Loads an array texture with one layer per image, all images must have the same size
+    pub fn load_array<P: AsRef<Path>>(&self, paths: &[P]) -> Result<Texture2dArray, TextureError> {
+        self.upload_array(&decode_files(paths)?)
+    }
+
+    pub fn upload_array(&self, layers: &[DynamicImage]) -> Result<Texture2dArray, TextureError> {
+        let format = layers.first().and_then(|layer| self.resolve_format(layer));
+
+        Texture2dArray::upload(
+            &flip_layers(layers, self.flip),
+            format,
+            self.resolve_sampler(),
+            self.mipmaps,
+        )
+    }
+
+    /// Loads a 3D texture with one slice per image (front to back), all images must have the same
+    /// size
+    pub fn load_3d<P: AsRef<Path>>(&self, paths: &[P]) -> Result<Texture3d, TextureError> {
+        self.upload_3d(&decode_files(paths)?)
+    }
+
+    pub fn upload_3d(&self, slices: &[DynamicImage]) -> Result<Texture3d, TextureError> {
+        let format = slices.first().and_then(|slice| self.resolve_format(slice));
+
+        Texture3d::upload(
+            &flip_layers(slices, self.flip),
+            format,
+            self.resolve_sampler(),
+            self.mipmaps,
+        )
+    }
+
+    /// Loads a 3D texture from a file of tightly packed texels without a header
+    ///
+    /// Texels are stored row by row and slice by slice, in the layout `format` uploads (see
+    /// [`TextureFormat::gl_formats`]) and the machine's byte order. The builder's format is
+    /// ignored, and `srgb` only applies to `RGB8` and `RGBA8` data.
+    pub fn load_raw_volume<P: AsRef<Path>>(
+        &self,
+        path: P,
+        size: [u32; 3],
+        format: TextureFormat,
+    ) -> Result<Texture3d, TextureError> {
+        let path = path.as_ref();
+        let data = fs::read(path).map_err(|error| read_error(path, error))?;
+
+        let [width, height, depth] = size;
+        let expected = width as usize * height as usize * depth as usize * format.bytes_per_pixel();
+        if data.len() != expected {
+            return Err(TextureError::VolumeSize {
+                path: path.to_owned(),
+                expected,
+                found: data.len(),
+            });
+        }
+
+        // Only switch to sRGB where that keeps the pixel layout
+        let format = match format {
+            TextureFormat::RGB8 | TextureFormat::RGBA8 if self.srgb => format.to_srgb(),
+            format => format,
+        };
+
+        Texture3d::upload_raw(&data, size, format, self.resolve_sampler(), self.mipmaps)
+    }
+
+    // The image (flipped if asked to) and format `upload` hands to the texture
+    fn upload_source<'a>(
+        &self,
+        img: &'a DynamicImage,
+    ) -> (Cow<'a, DynamicImage>, Option<TextureFormat>) {
+        (flip_image(img, self.flip), self.resolve_format(img))
+    }
+
+    // The forced or detected format, switched to sRGB for color images
+    fn resolve_format(&self, img: &DynamicImage) -> Option<TextureFormat> {
+        let format = self
+            .format
+            .or_else(|| TextureFormat::from_color_type(img.color()));
+
+        match format {
+            Some(format) if self.srgb => Some(format.to_srgb()),
+            format => format,
+        }
+    }
+
+    // The format to upload a compressed image in, and the builder with mipmaps turned off when
+    // the image has no mip chain
+    fn resolve_compressed(&self, image: &CompressedImage) -> (CompressedFormat, Self) {
+        let format = if self.srgb {
+            image.format().to_srgb()
+        } else {
+            image.format()
+        };
+
+        (format, self.mipmaps(self.mipmaps && image.levels() > 1))
+    }
+
+    // Without mipmaps a mipmapped minification filter would leave the texture incomplete
+    fn resolve_sampler(&self) -> SamplerDescriptor {
+        let mut sampler = self.sampler;
+
+        if !self.mipmaps {
+            sampler.min_filter = match sampler.min_filter {
+                TextureFilter::NearestMipmapNearest | TextureFilter::NearestMipmapLinear => {
+                    TextureFilter::Nearest
+                }
+                TextureFilter::LinearMipmapNearest | TextureFilter::LinearMipmapLinear => {
+                    TextureFilter::Linear
+                }
+                filter => filter,
+            };
+        }
+
+        sampler
+    }
+}
+
+fn decode_files<P: AsRef<Path>>(paths: &[P]) -> Result<Vec<DynamicImage>, TextureError> {
+    paths
+        .iter()
+        .map(|path| decode_file(path.as_ref()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+    use crate::texture::{prepare_image, row_alignment};
+
+    #[test]
+    fn upload_flips_odd_rgb_images_and_keeps_their_layout() {
+        let img =
+            DynamicImage::ImageRgb8(RgbImage::from_fn(3, 2, |x, y| Rgb([x as u8, y as u8, 0])));
+        let builder = TextureBuilder::new().srgb(true).flip_vertically(true);
+
+        let (source, format) = builder.upload_source(&img);
+        let (format, pixels) = prepare_image(&source, format).unwrap();
+
+        assert_eq!(format, TextureFormat::SRGB8);
+        assert_eq!((pixels.width(), pixels.height()), (3, 2));
+        assert_eq!(
+            pixels.as_bytes(),
+            [
+                [0, 1, 0],
+                [1, 1, 0],
+                [2, 1, 0],
+                [0, 0, 0],
+                [1, 0, 0],
+                [2, 0, 0]
+            ]
+            .concat()
+        );
+        // 9 byte rows, GL has to read them without padding
+        assert_eq!(row_alignment(format, pixels.width()), 1);
+    }
+
+    #[test]
+    fn upload_leaves_images_unflipped_by_default() {
+        let img = DynamicImage::ImageRgb8(RgbImage::new(3, 2));
+
+        let (source, format) = TextureBuilder::new().upload_source(&img);
+        assert!(matches!(source, Cow::Borrowed(_)));
+        assert_eq!(format, Some(TextureFormat::RGB8));
+    }
+}
@@ -0,0 +1,675 @@
+use std::{ffi::CStr, fs, path::Path};
+
+use ddsfile::{Caps2, Dds, DxgiFormat, FourCC, MiscFlag};
+use gl::types::*;
+
+use super::{mip_levels, read_error, with_unpack_alignment, TextureError, TextureFormat};
+
+mod decode;
+
+// S3TC formats are not part of core OpenGL, so the bindings leave them out
+const COMPRESSED_RGB_S3TC_DXT1: GLenum = 0x83F0;
+const COMPRESSED_RGBA_S3TC_DXT1: GLenum = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT3: GLenum = 0x83F2;
+const COMPRESSED_RGBA_S3TC_DXT5: GLenum = 0x83F3;
+const COMPRESSED_SRGB_S3TC_DXT1: GLenum = 0x8C4C;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT1: GLenum = 0x8C4D;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT3: GLenum = 0x8C4E;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT5: GLenum = 0x8C4F;
+
+// A legacy DDS four character code that ddsfile has no constant for
+const FOURCC_BC5_UNORM: u32 = u32::from_le_bytes(*b"BC5U");
+
+/// A block compression format, storing every 4x4 texel block in a fixed number of bytes
+///
+/// BC1-BC3 (S3TC or DXT1-DXT5) need `GL_EXT_texture_compression_s3tc`, BC4 and BC5 (RGTC) are
+/// part of OpenGL 3.0 and BC6H and BC7 (BPTC) of OpenGL 4.2. Formats the driver cannot sample are
+/// decompressed when uploading, see [`is_supported`](Self::is_supported).
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressedFormat {
+    /// RGB with 5:6:5 endpoints
+    BC1,
+    BC1Srgb,
+    /// BC1 with 1-bit alpha
+    BC1Alpha,
+    BC1AlphaSrgb,
+    /// BC1 color with explicit 4-bit alpha
+    BC2,
+    BC2Srgb,
+    /// BC1 color with interpolated alpha
+    BC3,
+    BC3Srgb,
+    /// A single channel, sampled as grey like [`TextureFormat::R8`]
+    BC4,
+    BC4Signed,
+    /// Two channels, usually the X and Y of a normal map
+    BC5,
+    BC5Signed,
+    /// HDR RGB stored as half floats
+    BC6H,
+    BC6HSigned,
+    BC7,
+    BC7Srgb,
+}
+
+impl CompressedFormat {
+    /// The bytes of one 4x4 block
+    pub fn block_size(&self) -> usize {
+        match self {
+            CompressedFormat::BC1
+            | CompressedFormat::BC1Srgb
+            | CompressedFormat::BC1Alpha
+            | CompressedFormat::BC1AlphaSrgb
+            | CompressedFormat::BC4
+            | CompressedFormat::BC4Signed => 8,
+            _ => 16,
+        }
+    }
+
+    /// The bytes of a `width` x `height` image, rounded up to whole blocks
+    pub fn image_size(&self, width: u32, height: u32) -> usize {
+        width.div_ceil(4) as usize * height.div_ceil(4) as usize * self.block_size()
+    }
+
+    /// The sRGB version of a color format, other formats are returned as is
+    pub fn to_srgb(self) -> Self {
+        match self {
+            CompressedFormat::BC1 => CompressedFormat::BC1Srgb,
+            CompressedFormat::BC1Alpha => CompressedFormat::BC1AlphaSrgb,
+            CompressedFormat::BC2 => CompressedFormat::BC2Srgb,
+            CompressedFormat::BC3 => CompressedFormat::BC3Srgb,
+            CompressedFormat::BC7 => CompressedFormat::BC7Srgb,
+            format => format,
+        }
+    }
+
+    pub fn is_srgb(&self) -> bool {
+        matches!(
+            self,
+            CompressedFormat::BC1Srgb
+                | CompressedFormat::BC1AlphaSrgb
+                | CompressedFormat::BC2Srgb
+                | CompressedFormat::BC3Srgb
+                | CompressedFormat::BC7Srgb
+        )
+    }
+
+    /// The uncompressed format with the same channels, which the format decompresses to when
+    /// the driver cannot sample it
+    ///
+    /// BC4 and BC5 are expanded to RGBA (with zero blue and opaque alpha) so every decompressed
+    /// row stays a multiple of four bytes.
+    pub fn uncompressed(&self) -> TextureFormat {
+        match self {
+            CompressedFormat::BC4Signed | CompressedFormat::BC5Signed => TextureFormat::RGBA16F,
+            CompressedFormat::BC6H | CompressedFormat::BC6HSigned => TextureFormat::RGB16F,
+            format if format.is_srgb() => TextureFormat::SRGBA8,
+            _ => TextureFormat::RGBA8,
+        }
+    }
+
+    /// The internal format passed to `glCompressedTexImage2D`
+    pub fn gl_internal_format(&self) -> GLenum {
+        match self {
+            CompressedFormat::BC1 => COMPRESSED_RGB_S3TC_DXT1,
+            CompressedFormat::BC1Srgb => COMPRESSED_SRGB_S3TC_DXT1,
+            CompressedFormat::BC1Alpha => COMPRESSED_RGBA_S3TC_DXT1,
+            CompressedFormat::BC1AlphaSrgb => COMPRESSED_SRGB_ALPHA_S3TC_DXT1,
+            CompressedFormat::BC2 => COMPRESSED_RGBA_S3TC_DXT3,
+            CompressedFormat::BC2Srgb => COMPRESSED_SRGB_ALPHA_S3TC_DXT3,
+            CompressedFormat::BC3 => COMPRESSED_RGBA_S3TC_DXT5,
+            CompressedFormat::BC3Srgb => COMPRESSED_SRGB_ALPHA_S3TC_DXT5,
+            CompressedFormat::BC4 => gl::COMPRESSED_RED_RGTC1,
+            CompressedFormat::BC4Signed => gl::COMPRESSED_SIGNED_RED_RGTC1,
+            CompressedFormat::BC5 => gl::COMPRESSED_RG_RGTC2,
+            CompressedFormat::BC5Signed => gl::COMPRESSED_SIGNED_RG_RGTC2,
+            CompressedFormat::BC6H => gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT,
+            CompressedFormat::BC6HSigned => gl::COMPRESSED_RGB_BPTC_SIGNED_FLOAT,
+            CompressedFormat::BC7 => gl::COMPRESSED_RGBA_BPTC_UNORM,
+            CompressedFormat::BC7Srgb => gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM,
+        }
+    }
+
+    /// Whether the current context can sample the format without decompressing it first
+    pub fn is_supported(&self) -> bool {
+        match self {
+            CompressedFormat::BC4
+            | CompressedFormat::BC4Signed
+            | CompressedFormat::BC5
+            | CompressedFormat::BC5Signed => true,
+            CompressedFormat::BC6H
+            | CompressedFormat::BC6HSigned
+            | CompressedFormat::BC7
+            | CompressedFormat::BC7Srgb => {
+                gl_version() >= (4, 2) || has_extension("GL_ARB_texture_compression_bptc")
+            }
+            format => {
+                has_extension("GL_EXT_texture_compression_s3tc")
+                    && (!format.is_srgb()
+                        || has_extension("GL_EXT_texture_sRGB")
+                        || has_extension("GL_EXT_texture_compression_s3tc_srgb"))
+            }
+        }
+    }
+
+    // How the stored channels map to the RGBA seen by shaders
+    fn swizzle(&self) -> [GLenum; 4] {
+        match self {
+            CompressedFormat::BC4 | CompressedFormat::BC4Signed => {
+                [gl::RED, gl::RED, gl::RED, gl::ONE]
+            }
+            _ => [gl::RED, gl::GREEN, gl::BLUE, gl::ALPHA],
+        }
+    }
+
+    fn from_dxgi(format: DxgiFormat) -> Option<Self> {
+        match format {
+            DxgiFormat::BC1_Typeless | DxgiFormat::BC1_UNorm => Some(CompressedFormat::BC1Alpha),
+            DxgiFormat::BC1_UNorm_sRGB => Some(CompressedFormat::BC1AlphaSrgb),
+            DxgiFormat::BC2_Typeless | DxgiFormat::BC2_UNorm => Some(CompressedFormat::BC2),
+            DxgiFormat::BC2_UNorm_sRGB => Some(CompressedFormat::BC2Srgb),
+            DxgiFormat::BC3_Typeless | DxgiFormat::BC3_UNorm => Some(CompressedFormat::BC3),
+            DxgiFormat::BC3_UNorm_sRGB => Some(CompressedFormat::BC3Srgb),
+            DxgiFormat::BC4_Typeless | DxgiFormat::BC4_UNorm => Some(CompressedFormat::BC4),
+            DxgiFormat::BC4_SNorm => Some(CompressedFormat::BC4Signed),
+            DxgiFormat::BC5_Typeless | DxgiFormat::BC5_UNorm => Some(CompressedFormat::BC5),
+            DxgiFormat::BC5_SNorm => Some(CompressedFormat::BC5Signed),
+            DxgiFormat::BC6H_Typeless | DxgiFormat::BC6H_UF16 => Some(CompressedFormat::BC6H),
+            DxgiFormat::BC6H_SF16 => Some(CompressedFormat::BC6HSigned),
+            DxgiFormat::BC7_Typeless | DxgiFormat::BC7_UNorm => Some(CompressedFormat::BC7),
+            DxgiFormat::BC7_UNorm_sRGB => Some(CompressedFormat::BC7Srgb),
+            _ => None,
+        }
+    }
+
+    // DDS files without the DX10 header name their format with a four character code
+    fn from_fourcc(fourcc: u32) -> Option<Self> {
+        match fourcc {
+            FourCC::DXT1 => Some(CompressedFormat::BC1Alpha),
+            FourCC::DXT2 | FourCC::DXT3 => Some(CompressedFormat::BC2),
+            FourCC::DXT4 | FourCC::DXT5 => Some(CompressedFormat::BC3),
+            FourCC::ATI1 | FourCC::BC4_UNORM => Some(CompressedFormat::BC4),
+            FourCC::BC4_SNORM => Some(CompressedFormat::BC4Signed),
+            FourCC::ATI2 | FOURCC_BC5_UNORM => Some(CompressedFormat::BC5),
+            FourCC::BC5_SNORM => Some(CompressedFormat::BC5Signed),
+            _ => None,
+        }
+    }
+
+    fn from_ktx2(format: ktx2::Format) -> Option<Self> {
+        match format {
+            ktx2::Format::BC1_RGB_UNORM_BLOCK => Some(CompressedFormat::BC1),
+            ktx2::Format::BC1_RGB_SRGB_BLOCK => Some(CompressedFormat::BC1Srgb),
+            ktx2::Format::BC1_RGBA_UNORM_BLOCK => Some(CompressedFormat::BC1Alpha),
+            ktx2::Format::BC1_RGBA_SRGB_BLOCK => Some(CompressedFormat::BC1AlphaSrgb),
+            ktx2::Format::BC2_UNORM_BLOCK => Some(CompressedFormat::BC2),
+            ktx2::Format::BC2_SRGB_BLOCK => Some(CompressedFormat::BC2Srgb),
+            ktx2::Format::BC3_UNORM_BLOCK => Some(CompressedFormat::BC3),
+            ktx2::Format::BC3_SRGB_BLOCK => Some(CompressedFormat::BC3Srgb),
+            ktx2::Format::BC4_UNORM_BLOCK => Some(CompressedFormat::BC4),
+            ktx2::Format::BC4_SNORM_BLOCK => Some(CompressedFormat::BC4Signed),
+            ktx2::Format::BC5_UNORM_BLOCK => Some(CompressedFormat::BC5),
+            ktx2::Format::BC5_SNORM_BLOCK => Some(CompressedFormat::BC5Signed),
+            ktx2::Format::BC6H_UFLOAT_BLOCK => Some(CompressedFormat::BC6H),
+            ktx2::Format::BC6H_SFLOAT_BLOCK => Some(CompressedFormat::BC6HSigned),
+            ktx2::Format::BC7_UNORM_BLOCK => Some(CompressedFormat::BC7),
+            ktx2::Format::BC7_SRGB_BLOCK => Some(CompressedFormat::BC7Srgb),
+            _ => None,
+        }
+    }
+}
+
+/// A block compressed image read from a KTX2 or DDS file, with the mip chain stored in the file
+///
+/// Holds a single 2D image or the six faces of a cube map (in
+/// [`CubeFace::ALL`](super::CubeFace::ALL) order). Array and 3D textures and supercompressed KTX2
+/// files are not supported.
+#[derive(Debug, Clone)]
+pub struct CompressedImage {
+    format: CompressedFormat,
+    width: u32,
+    height: u32,
+    faces: u32,
+    levels: u32,
+    // Every level of the first face, then every level of the next one
+    data: Vec<u8>,
+}
+
+impl CompressedImage {
+    /// Reads a `.ktx2` or `.dds` file, picked by the extension
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, TextureError> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|error| read_error(path, error))?;
+
+        let image = match extension(path).as_deref() {
+            Some("ktx2") => Self::parse_ktx2(&bytes),
+            Some("dds") => Self::parse_dds(&bytes),
+            _ => Err("expected a .ktx2 or .dds file".to_owned()),
+        };
+
+        image.map_err(|message| TextureError::Container {
+            path: path.to_owned(),
+            message,
+        })
+    }
+
+    fn parse_ktx2(bytes: &[u8]) -> Result<Self, String> {
+        let reader = ktx2::Reader::new(bytes).map_err(|error| error.to_string())?;
+        let header = reader.header();
+
+        if let Some(scheme) = header.supercompression_scheme {
+            return Err(format!("{:?} supercompression is not supported", scheme));
+        }
+        let format = header
+            .format
+            .and_then(CompressedFormat::from_ktx2)
+            .ok_or_else(|| format!("{:?} is not a supported block format", header.format))?;
+        if header.pixel_depth > 1 || header.layer_count > 1 {
+            return Err("array and 3D textures are not supported".to_owned());
+        }
+
+        let mut image = Self::new(
+            format,
+            header.pixel_width,
+            header.pixel_height.max(1),
+            header.face_count,
+            header.level_count.max(1),
+        )?;
+
+        // KTX2 stores every face of a level together, reorder them to face by face
+        let mut faces = vec![Vec::new(); image.faces as usize];
+        for (level, data) in reader.levels().enumerate() {
+            let (width, height) = image.level_size(level as u32);
+            let face_size = format.image_size(width, height);
+            if data.data.len() != face_size * faces.len() {
+                return Err(format!(
+                    "level {} is {} bytes, expected {}",
+                    level,
+                    data.data.len(),
+                    face_size * faces.len()
+                ));
+            }
+
+            for (face, face_data) in faces.iter_mut().zip(data.data.chunks_exact(face_size)) {
+                face.extend_from_slice(face_data);
+            }
+        }
+
+        image.data = faces.concat();
+        Ok(image)
+    }
+
+    fn parse_dds(bytes: &[u8]) -> Result<Self, String> {
+        let dds = Dds::read(bytes).map_err(|error| error.to_string())?;
+
+        let (format, faces) = match &dds.header10 {
+            Some(header10) => {
+                if header10.array_size > 1 {
+                    return Err("array textures are not supported".to_owned());
+                }
+                let faces = if header10.misc_flag.contains(MiscFlag::TEXTURECUBE) {
+                    6
+                } else {
+                    1
+                };
+
+                (CompressedFormat::from_dxgi(header10.dxgi_format), faces)
+            }
+            None => {
+                let faces = if dds.header.caps2.contains(Caps2::CUBEMAP) {
+                    6
+                } else {
+                    1
+                };
+                let format = dds
+                    .header
+                    .spf
+                    .fourcc
+                    .as_ref()
+                    .and_then(|fourcc| CompressedFormat::from_fourcc(fourcc.0));
+
+                (format, faces)
+            }
+        };
+        let format = format.ok_or_else(|| "not a supported block format".to_owned())?;
+        if dds.header.depth.is_some_and(|depth| depth > 1) {
+            return Err("3D textures are not supported".to_owned());
+        }
+
+        let mut image = Self::new(
+            format,
+            dds.header.width,
+            dds.header.height,
+            faces,
+            dds.header.mip_map_count.unwrap_or(1).max(1),
+        )?;
+
+        // DDS already stores the faces one after another, each with its whole mip chain
+        let size = image.face_size() * faces as usize;
+        if dds.data.len() < size {
+            return Err(format!(
+                "the image data is {} bytes, expected {}",
+                dds.data.len(),
+                size
+            ));
+        }
+
+        image.data = dds.data[..size].to_vec();
+        Ok(image)
+    }
+
+    // An image without data yet, checking the size, faces and levels
+    fn new(
+        format: CompressedFormat,
+        width: u32,
+        height: u32,
+        faces: u32,
+        levels: u32,
+    ) -> Result<Self, String> {
+        if width == 0 || height == 0 {
+            return Err("the image is empty".to_owned());
+        }
+        if faces != 1 && faces != 6 {
+            return Err(format!("expected 1 or 6 faces, got {}", faces));
+        }
+        if levels > mip_levels(width, height) {
+            return Err(format!(
+                "{} mip levels is too many for a {}x{} image",
+                levels, width, height
+            ));
+        }
+
+        Ok(Self {
+            format,
+            width,
+            height,
+            faces,
+            levels,
+            data: Vec::new(),
+        })
+    }
+
+    pub fn format(&self) -> CompressedFormat {
+        self.format
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// 1 for a 2D image, 6 for a cube map
+    pub fn faces(&self) -> u32 {
+        self.faces
+    }
+
+    /// The number of mip levels stored for every face
+    pub fn levels(&self) -> u32 {
+        self.levels
+    }
+
+    /// The width and height of a mip level
+    pub fn level_size(&self, level: u32) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    /// The blocks of one level of one face
+    pub fn data(&self, level: u32, face: u32) -> &[u8] {
+        let offset = self.face_size() * face as usize
+            + (0..level)
+                .map(|level| self.level_bytes(level))
+                .sum::<usize>();
+
+        &self.data[offset..offset + self.level_bytes(level)]
+    }
+
+    fn level_bytes(&self, level: u32) -> usize {
+        let (width, height) = self.level_size(level);
+        self.format.image_size(width, height)
+    }
+
+    // The bytes of a face's whole mip chain
+    fn face_size(&self) -> usize {
+        (0..self.levels).map(|level| self.level_bytes(level)).sum()
+    }
+}
+
+// The format a texture filled by `tex_compressed_image` ended up in
+pub(super) struct CompressedUpload {
+    pub format: TextureFormat,
+    pub compression: Option<CompressedFormat>,
+    pub levels: u32,
+}
+
+// Whether a file is loaded as a `CompressedImage` rather than decoded by the image crate
+pub(super) fn is_container(path: &Path) -> bool {
+    matches!(extension(path).as_deref(), Some("ktx2" | "dds"))
+}
+
+// Fills the bound texture with the levels of an image, one face target per face of the image
+//
+// The blocks are uploaded as they are when the driver supports `format` (the image's format or
+// its sRGB version) and decompressed otherwise.
+pub(super) unsafe fn tex_compressed_image(
+    target: GLenum,
+    face_targets: &[GLenum],
+    image: &CompressedImage,
+    format: CompressedFormat,
+    mipmaps: bool,
+) -> CompressedUpload {
+    let levels = if mipmaps { image.levels() } else { 1 };
+    let compression = format.is_supported().then_some(format);
+    let uncompressed = format.uncompressed();
+
+    gl::TexParameteri(target, gl::TEXTURE_MAX_LEVEL, levels as GLint - 1);
+    let swizzle = format.swizzle().map(|channel| channel as GLint);
+    gl::TexParameteriv(target, gl::TEXTURE_SWIZZLE_RGBA, swizzle.as_ptr());
+
+    for level in 0..levels {
+        let (width, height) = image.level_size(level);
+
+        for (face, &face_target) in face_targets.iter().enumerate() {
+            let data = image.data(level, face as u32);
+
+            if let Some(compression) = compression {
+                gl::CompressedTexImage2D(
+                    face_target,
+                    level as GLint,
+                    compression.gl_internal_format(),
+                    width as GLsizei,
+                    height as GLsizei,
+                    0,
+                    data.len() as GLsizei,
+                    data.as_ptr().cast(),
+                );
+            } else {
+                let texels = decode::decompress(format, width, height, data);
+                let (internal_format, pixel_format, pixel_type) = uncompressed.gl_formats();
+
+                with_unpack_alignment(uncompressed, width, || {
+                    gl::TexImage2D(
+                        face_target,
+                        level as GLint,
+                        internal_format as GLint,
+                        width as GLsizei,
+                        height as GLsizei,
+                        0,
+                        pixel_format,
+                        pixel_type,
+                        texels.as_ptr().cast(),
+                    )
+                });
+            }
+        }
+    }
+
+    CompressedUpload {
+        format: uncompressed,
+        compression,
+        levels,
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+}
+
+fn gl_version() -> (GLint, GLint) {
+    let (mut major, mut minor) = (0, 0);
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+    }
+
+    (major, minor)
+}
+
+fn has_extension(name: &str) -> bool {
+    let mut count: GLint = 0;
+    unsafe { gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count) };
+
+    (0..count.max(0) as GLuint).any(|index| {
+        let extension = unsafe { gl::GetStringi(gl::EXTENSIONS, index) };
+        !extension.is_null()
+            && unsafe { CStr::from_ptr(extension.cast()) }.to_bytes() == name.as_bytes()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ddsfile::{AlphaMode, D3D10ResourceDimension, NewDxgiParams};
+
+    use super::*;
+
+    // The single BC1 block of a level of a face, filled with a byte naming both
+    fn block(level: u32, face: u32) -> [u8; 8] {
+        [(face * 16 + level) as u8; 8]
+    }
+
+    // A KTX2 cube map of 4x4 BC1 blocks with two levels, `trim` bytes cut off the last level
+    fn ktx2_cube_map(trim: usize) -> Vec<u8> {
+        let levels: Vec<Vec<u8>> = (0..2)
+            .map(|level| (0..6).flat_map(|face| block(level, face)).collect())
+            .collect();
+        let level_lengths = [levels[0].len(), levels[1].len() - trim];
+
+        let header = ktx2::Header {
+            format: Some(ktx2::Format::BC1_RGBA_UNORM_BLOCK),
+            type_size: 1,
+            pixel_width: 4,
+            pixel_height: 4,
+            pixel_depth: 0,
+            layer_count: 0,
+            face_count: 6,
+            level_count: 2,
+            supercompression_scheme: None,
+            // The reader wants a data format descriptor, the header's first bytes will do
+            index: ktx2::Index {
+                dfd_byte_offset: 0,
+                dfd_byte_length: 4,
+                kvd_byte_offset: 0,
+                kvd_byte_length: 0,
+                sgd_byte_offset: 0,
+                sgd_byte_length: 0,
+            },
+        };
+
+        let mut bytes = header.as_bytes().to_vec();
+        let mut offset = (ktx2::Header::LENGTH + 2 * ktx2::LevelIndex::LENGTH) as u64;
+        for length in level_lengths {
+            let index = ktx2::LevelIndex {
+                byte_offset: offset,
+                byte_length: length as u64,
+                uncompressed_byte_length: length as u64,
+            };
+            bytes.extend_from_slice(&index.as_bytes());
+            offset += length as u64;
+        }
+        for (level, length) in levels.iter().zip(level_lengths) {
+            bytes.extend_from_slice(&level[..length]);
+        }
+
+        bytes
+    }
+
+    // A DX10 DDS cube map of 4x4 BC1 blocks with two levels, `trim` bytes cut off the end
+    fn dds_cube_map(trim: usize) -> Vec<u8> {
+        let mut dds = Dds::new_dxgi(NewDxgiParams {
+            height: 4,
+            width: 4,
+            depth: None,
+            format: DxgiFormat::BC1_UNorm,
+            mipmap_levels: Some(2),
+            array_layers: Some(6),
+            caps2: None,
+            is_cubemap: true,
+            resource_dimension: D3D10ResourceDimension::Texture2D,
+            alpha_mode: AlphaMode::Unknown,
+        })
+        .unwrap();
+
+        dds.data = (0..6)
+            .flat_map(|face| (0..2).flat_map(move |level| block(level, face)))
+            .collect();
+        dds.data.truncate(dds.data.len() - trim);
+
+        let mut bytes = Vec::new();
+        dds.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn assert_cube_map(image: &CompressedImage) {
+        assert_eq!(image.format(), CompressedFormat::BC1Alpha);
+        assert_eq!((image.width(), image.height()), (4, 4));
+        assert_eq!((image.faces(), image.levels()), (6, 2));
+
+        for face in 0..6 {
+            for level in 0..2 {
+                assert_eq!(image.data(level, face), block(level, face));
+            }
+        }
+    }
+
+    #[test]
+    fn ktx2_faces_are_reordered_face_by_face() {
+        let image = CompressedImage::parse_ktx2(&ktx2_cube_map(0)).unwrap();
+
+        assert_cube_map(&image);
+        assert_eq!(image.data[..16], [block(0, 0), block(1, 0)].concat());
+    }
+
+    #[test]
+    fn ktx2_level_size_is_checked() {
+        let error = CompressedImage::parse_ktx2(&ktx2_cube_map(1)).unwrap_err();
+
+        assert_eq!(error, "level 1 is 47 bytes, expected 48");
+    }
+
+    #[test]
+    fn dds_faces_keep_their_mip_chains() {
+        let image = CompressedImage::parse_dds(&dds_cube_map(0)).unwrap();
+
+        assert_cube_map(&image);
+    }
+
+    #[test]
+    fn dds_data_size_is_checked() {
+        let error = CompressedImage::parse_dds(&dds_cube_map(8)).unwrap_err();
+
+        assert_eq!(error, "the image data is 88 bytes, expected 96");
+    }
+
+    #[test]
+    fn dds_trailing_data_is_ignored() {
+        let mut bytes = dds_cube_map(0);
+        bytes.extend_from_slice(&[0xFF; 8]);
+
+        let image = CompressedImage::parse_dds(&bytes).unwrap();
+        assert_cube_map(&image);
+    }
+}
@@ -0,0 +1,1103 @@
+// CPU decoding of BC1-BC7 blocks, for drivers that cannot sample a compressed format
+//
+// Every block covers 4x4 texels, stored row by row. The BC6H and BC7 tables and bit layouts
+// follow the ARB_texture_compression_bptc specification.
+
+use super::CompressedFormat;
+
+// Texel weights of the BC6H and BC7 indices, out of 64
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+// The subset of every texel in the two subset partitions, one bit per texel
+const PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80, 0xC800, 0xFFEC, 0xFE80, 0xE800,
+    0xFFE8, 0xFF00, 0xFFF0, 0xF000, 0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C, 0xAAAA, 0xF0F0, 0x5A5A, 0x33CC,
+    0x3C3C, 0x55AA, 0x9696, 0xA55A, 0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C, 0x9336, 0x9CC6, 0x817E, 0xE718,
+    0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+// The subset of every texel in the three subset partitions, two bits per texel
+const PARTITIONS_3: [u32; 64] = [
+    0xAA685050, 0x6A5A5040, 0x5A5A4200, 0x5450A0A8, 0xA5A50000, 0xA0A05050, 0x5555A0A0, 0x5A5A5050,
+    0xAA550000, 0xAA555500, 0xAAAA5500, 0x90909090, 0x94949494, 0xA4A4A4A4, 0xA9A59450, 0x2A0A4250,
+    0xA5945040, 0x0A425054, 0xA5A5A500, 0x55A0A0A0, 0xA8A85454, 0x6A6A4040, 0xA4A45000, 0x1A1A0500,
+    0x0050A4A4, 0xAAA59090, 0x14696914, 0x69691400, 0xA08585A0, 0xAA821414, 0x50A4A450, 0x6A5A0200,
+    0xA9A58000, 0x5090A0A8, 0xA8A09050, 0x24242424, 0x00AA5500, 0x24924924, 0x24499224, 0x50A50A50,
+    0x500AA550, 0xAAAA4444, 0x66660000, 0xA5A0A5A0, 0x50A050A0, 0x69286928, 0x44AAAA44, 0x66666600,
+    0xAA444444, 0x54A854A8, 0x95809580, 0x96969600, 0xA85454A8, 0x80959580, 0xAA141414, 0x96960000,
+    0xAAAA1414, 0xA05050A0, 0xA0A5A5A0, 0x96000000, 0x40804080, 0xA9A8A9A8, 0xAAAAAA44, 0x2A4A5254,
+];
+
+// The texels whose index is stored with one bit less, besides the first one
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+const ANCHORS_3_SECOND: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5,
+    15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5,
+    10, 8, 13, 15, 12, 3, 3,
+];
+const ANCHORS_3_THIRD: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6,
+    10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+/// Decodes the blocks of a `width` x `height` image to the pixel layout of
+/// [`CompressedFormat::uncompressed`]
+///
+/// `data` must hold every block of the image.
+pub(super) fn decompress(
+    format: CompressedFormat,
+    width: u32,
+    height: u32,
+    data: &[u8],
+) -> Vec<u8> {
+    let block_size = format.block_size();
+
+    match format {
+        CompressedFormat::BC1 | CompressedFormat::BC1Srgb => {
+            decode_blocks(width, height, data, block_size, |block| {
+                decode_bc1(block, Some(255))
+            })
+        }
+        CompressedFormat::BC1Alpha | CompressedFormat::BC1AlphaSrgb => {
+            decode_blocks(width, height, data, block_size, |block| {
+                decode_bc1(block, Some(0))
+            })
+        }
+        CompressedFormat::BC2 | CompressedFormat::BC2Srgb => {
+            decode_blocks(width, height, data, block_size, decode_bc2)
+        }
+        CompressedFormat::BC3 | CompressedFormat::BC3Srgb => {
+            decode_blocks(width, height, data, block_size, decode_bc3)
+        }
+        CompressedFormat::BC4 => decode_blocks(width, height, data, block_size, |block| {
+            decode_bc4(block, false).map(|red| [to_unorm8(red), 0, 0, 255])
+        }),
+        CompressedFormat::BC4Signed => {
+            let texels = decode_blocks(width, height, data, block_size, |block| {
+                decode_bc4(block, true).map(|red| [red, 0.0, 0.0, 1.0])
+            });
+            float_bytes(&texels)
+        }
+        CompressedFormat::BC5 => decode_blocks(width, height, data, block_size, |block| {
+            let red = decode_bc4(&block[..8], false);
+            let green = decode_bc4(&block[8..], false);
+            std::array::from_fn(|texel| [to_unorm8(red[texel]), to_unorm8(green[texel]), 0, 255])
+        }),
+        CompressedFormat::BC5Signed => {
+            let texels = decode_blocks(width, height, data, block_size, |block| {
+                let red = decode_bc4(&block[..8], true);
+                let green = decode_bc4(&block[8..], true);
+                std::array::from_fn(|texel| [red[texel], green[texel], 0.0, 1.0])
+            });
+            float_bytes(&texels)
+        }
+        CompressedFormat::BC6H | CompressedFormat::BC6HSigned => {
+            let signed = format == CompressedFormat::BC6HSigned;
+            let texels = decode_blocks(width, height, data, block_size, |block| {
+                decode_bc6h(block, signed)
+            });
+            float_bytes(&texels)
+        }
+        CompressedFormat::BC7 | CompressedFormat::BC7Srgb => {
+            decode_blocks(width, height, data, block_size, decode_bc7)
+        }
+    }
+}
+
+// Decodes every block of an image and writes the texels inside the image row by row
+fn decode_blocks<T: Copy + Default, const N: usize>(
+    width: u32,
+    height: u32,
+    data: &[u8],
+    block_size: usize,
+    decode_block: impl Fn(&[u8]) -> [[T; N]; 16],
+) -> Vec<T> {
+    let (width, height) = (width as usize, height as usize);
+    let blocks_wide = width.div_ceil(4);
+    let mut texels = vec![T::default(); width * height * N];
+
+    for (index, block) in data.chunks_exact(block_size).enumerate() {
+        let (block_x, block_y) = (index % blocks_wide * 4, index / blocks_wide * 4);
+        if block_y >= height {
+            break;
+        }
+
+        for (texel, value) in decode_block(block).iter().enumerate() {
+            let (x, y) = (block_x + texel % 4, block_y + texel / 4);
+            if x < width && y < height {
+                let offset = (y * width + x) * N;
+                texels[offset..offset + N].copy_from_slice(value);
+            }
+        }
+    }
+
+    texels
+}
+
+fn float_bytes(values: &[f32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_ne_bytes())
+        .collect()
+}
+
+fn to_unorm8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// Reads the bits of a block from the least significant end
+struct BlockBits(u128);
+
+impl BlockBits {
+    fn new(block: &[u8]) -> Self {
+        Self(u128::from_le_bytes(block.try_into().unwrap()))
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        let value = (self.0 & ((1 << count) - 1)) as u32;
+        self.0 >>= count;
+        value
+    }
+}
+
+fn rgb565(color: u16) -> [u32; 3] {
+    let (red, green, blue) = (
+        (color >> 11) as u32,
+        (color >> 5) as u32 & 0x3F,
+        color as u32 & 0x1F,
+    );
+
+    [
+        (red << 3) | (red >> 2),
+        (green << 2) | (green >> 4),
+        (blue << 3) | (blue >> 2),
+    ]
+}
+
+// The color half of BC1-BC3 blocks
+//
+// `transparent_alpha` is the alpha of the fourth color when the endpoints are in ascending order,
+// which switches BC1 to three colors. BC2 and BC3 always interpolate four colors.
+fn decode_bc1(block: &[u8], transparent_alpha: Option<u8>) -> [[u8; 4]; 16] {
+    let endpoints = [
+        u16::from_le_bytes([block[0], block[1]]),
+        u16::from_le_bytes([block[2], block[3]]),
+    ];
+    let [first, second] = endpoints.map(rgb565);
+    let color = |first_weight: u32, second_weight: u32| {
+        let total = first_weight + second_weight;
+        let [red, green, blue] = [0, 1, 2].map(|channel| {
+            ((first[channel] * first_weight + second[channel] * second_weight) / total) as u8
+        });
+        [red, green, blue, 255]
+    };
+
+    let palette = match transparent_alpha {
+        Some(alpha) if endpoints[0] <= endpoints[1] => {
+            [color(1, 0), color(0, 1), color(1, 1), [0, 0, 0, alpha]]
+        }
+        _ => [color(1, 0), color(0, 1), color(2, 1), color(1, 2)],
+    };
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    std::array::from_fn(|texel| palette[(indices >> (texel * 2)) as usize & 3])
+}
+
+// Explicit 4-bit alpha followed by a BC1 color block
+fn decode_bc2(block: &[u8]) -> [[u8; 4]; 16] {
+    let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+    let mut texels = decode_bc1(&block[8..], None);
+
+    for (texel, color) in texels.iter_mut().enumerate() {
+        color[3] = ((alpha >> (texel * 4)) & 0xF) as u8 * 17;
+    }
+
+    texels
+}
+
+// A BC4 alpha block followed by a BC1 color block
+fn decode_bc3(block: &[u8]) -> [[u8; 4]; 16] {
+    let alpha = decode_bc4(&block[..8], false);
+    let mut texels = decode_bc1(&block[8..], None);
+
+    for (color, alpha) in texels.iter_mut().zip(alpha) {
+        color[3] = to_unorm8(alpha);
+    }
+
+    texels
+}
+
+// A single channel between two endpoints, as 0 to 1 (or -1 to 1 when signed)
+fn decode_bc4(block: &[u8], signed: bool) -> [f32; 16] {
+    let (first, second, ascending) = if signed {
+        let [first, second] = [block[0], block[1]].map(|value| (value as i8).max(-127));
+        (first as f32 / 127.0, second as f32 / 127.0, first <= second)
+    } else {
+        (
+            block[0] as f32 / 255.0,
+            block[1] as f32 / 255.0,
+            block[0] <= block[1],
+        )
+    };
+
+    let mut palette = [first, second, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+    if ascending {
+        // Six interpolated values and the two extremes
+        for step in 1..5 {
+            palette[step + 1] = (first * (5 - step) as f32 + second * step as f32) / 5.0;
+        }
+        palette[6] = if signed { -1.0 } else { 0.0 };
+        palette[7] = 1.0;
+    } else {
+        for step in 1..7 {
+            palette[step + 1] = (first * (7 - step) as f32 + second * step as f32) / 7.0;
+        }
+    }
+
+    let mut indices = [0; 8];
+    indices[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(indices);
+
+    std::array::from_fn(|texel| palette[(indices >> (texel * 3)) as usize & 7])
+}
+
+fn interpolate(first: u32, second: u32, weight: u32) -> u32 {
+    ((64 - weight) * first + weight * second + 32) >> 6
+}
+
+fn weight(index_bits: u32, index: u32) -> u32 {
+    match index_bits {
+        2 => WEIGHTS_2[index as usize],
+        3 => WEIGHTS_3[index as usize],
+        _ => WEIGHTS_4[index as usize],
+    }
+}
+
+// The subset of a texel in a partition
+fn subset(subsets: usize, partition: usize, texel: usize) -> usize {
+    match subsets {
+        2 => (PARTITIONS_2[partition] >> texel) as usize & 1,
+        3 => (PARTITIONS_3[partition] >> (texel * 2)) as usize & 3,
+        _ => 0,
+    }
+}
+
+// Whether a texel's index is stored with its top bit left out
+fn is_anchor(subsets: usize, partition: usize, texel: usize) -> bool {
+    texel == 0
+        || match subsets {
+            2 => texel == ANCHORS_2[partition] as usize,
+            3 => {
+                texel == ANCHORS_3_SECOND[partition] as usize
+                    || texel == ANCHORS_3_THIRD[partition] as usize
+            }
+            _ => false,
+        }
+}
+
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    // One p-bit per endpoint, or one shared by both endpoints of a subset
+    endpoint_p_bits: bool,
+    shared_p_bits: bool,
+    index_bits: u32,
+    // Modes 4 and 5 store a second set of indices, for alpha unless the index selection swaps them
+    second_index_bits: u32,
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode {
+        subsets: 3,
+        partition_bits: 4,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 4,
+        alpha_bits: 0,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 3,
+        second_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 6,
+        alpha_bits: 0,
+        endpoint_p_bits: false,
+        shared_p_bits: true,
+        index_bits: 3,
+        second_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 3,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 0,
+        endpoint_p_bits: false,
+        shared_p_bits: false,
+        index_bits: 2,
+        second_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 0,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 2,
+        second_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 1,
+        color_bits: 5,
+        alpha_bits: 6,
+        endpoint_p_bits: false,
+        shared_p_bits: false,
+        index_bits: 2,
+        second_index_bits: 3,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 8,
+        endpoint_p_bits: false,
+        shared_p_bits: false,
+        index_bits: 2,
+        second_index_bits: 2,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 7,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 4,
+        second_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 5,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 2,
+        second_index_bits: 0,
+    },
+];
+
+fn decode_bc7(block: &[u8]) -> [[u8; 4]; 16] {
+    // The mode is the number of zero bits before the first set one
+    let mode_number = block[0].trailing_zeros();
+    let Some(mode) = BC7_MODES.get(mode_number as usize) else {
+        return [[0; 4]; 16];
+    };
+
+    let mut bits = BlockBits::new(block);
+    bits.read(mode_number + 1);
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits);
+    let index_selection = bits.read(mode.index_selection_bits);
+
+    // [subset][endpoint][channel]
+    let mut endpoints = [[[0u32; 4]; 2]; 3];
+    for channel in 0..4 {
+        let channel_bits = if channel < 3 {
+            mode.color_bits
+        } else {
+            mode.alpha_bits
+        };
+        for subset in endpoints.iter_mut().take(mode.subsets) {
+            for endpoint in subset.iter_mut() {
+                endpoint[channel] = bits.read(channel_bits);
+            }
+        }
+    }
+
+    // P-bits add a shared lowest bit to every channel of an endpoint
+    let (mut color_bits, mut alpha_bits) = (mode.color_bits, mode.alpha_bits);
+    if mode.endpoint_p_bits || mode.shared_p_bits {
+        for subset in endpoints.iter_mut().take(mode.subsets) {
+            let shared = bits.read(mode.shared_p_bits as u32);
+            for endpoint in subset.iter_mut() {
+                let p_bit = if mode.endpoint_p_bits {
+                    bits.read(1)
+                } else {
+                    shared
+                };
+                for channel in endpoint.iter_mut() {
+                    *channel = (*channel << 1) | p_bit;
+                }
+            }
+        }
+
+        color_bits += 1;
+        if alpha_bits > 0 {
+            alpha_bits += 1;
+        }
+    }
+
+    for subset in endpoints.iter_mut().take(mode.subsets) {
+        for endpoint in subset.iter_mut() {
+            for (channel, value) in endpoint.iter_mut().enumerate() {
+                let channel_bits = if channel < 3 { color_bits } else { alpha_bits };
+                *value = if channel_bits == 0 {
+                    255
+                } else {
+                    // Repeat the top bits to fill 8 bits
+                    let value = *value << (8 - channel_bits);
+                    value | (value >> channel_bits)
+                };
+            }
+        }
+    }
+
+    let mut indices = [0; 16];
+    for (texel, index) in indices.iter_mut().enumerate() {
+        let anchor = is_anchor(mode.subsets, partition, texel);
+        *index = bits.read(mode.index_bits - anchor as u32);
+    }
+    let mut second_indices = [0; 16];
+    if mode.second_index_bits > 0 {
+        for (texel, index) in second_indices.iter_mut().enumerate() {
+            *index = bits.read(mode.second_index_bits - (texel == 0) as u32);
+        }
+    }
+
+    std::array::from_fn(|texel| {
+        let [first, second] = endpoints[subset(mode.subsets, partition, texel)];
+        let (color_index, alpha_index) = match (mode.second_index_bits, index_selection) {
+            (0, _) => (
+                (indices[texel], mode.index_bits),
+                (indices[texel], mode.index_bits),
+            ),
+            (_, 0) => (
+                (indices[texel], mode.index_bits),
+                (second_indices[texel], mode.second_index_bits),
+            ),
+            _ => (
+                (second_indices[texel], mode.second_index_bits),
+                (indices[texel], mode.index_bits),
+            ),
+        };
+
+        let mut texel = [0u8; 4];
+        for (channel, value) in texel.iter_mut().enumerate() {
+            let (index, index_bits) = if channel < 3 {
+                color_index
+            } else {
+                alpha_index
+            };
+            let weight = weight(index_bits, index);
+            *value = interpolate(first[channel], second[channel], weight) as u8;
+        }
+
+        // The rotation swaps alpha with one of the color channels
+        if rotation > 0 {
+            texel.swap(3, rotation as usize - 1);
+        }
+
+        texel
+    })
+}
+
+// The endpoint values of a BC6H block, as `[channel][endpoint]`
+//
+// Endpoints 0 and 1 (w and x in the specification) belong to the first region, 2 and 3 (y and z)
+// to the second.
+type Bc6hEndpoints = [[i32; 4]; 3];
+
+// Where a run of endpoint bits is stored: `(channel, endpoint, first bit, bit count)`
+type Bc6hBits = (usize, usize, u32, u32);
+
+struct Bc6hMode {
+    // The mode number stored in the first 2 or 5 bits
+    number: u32,
+    endpoint_bits: u32,
+    // The bits of the deltas from the first endpoint, per channel
+    delta_bits: [u32; 3],
+    transformed: bool,
+    layout: &'static [Bc6hBits],
+}
+
+const R: usize = 0;
+const G: usize = 1;
+const B: usize = 2;
+
+#[rustfmt::skip]
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode {
+        number: 0b00, endpoint_bits: 10, delta_bits: [5, 5, 5], transformed: true,
+        layout: &[
+            (G, 2, 4, 1), (B, 2, 4, 1), (B, 3, 4, 1), (R, 0, 0, 10), (G, 0, 0, 10), (B, 0, 0, 10),
+            (R, 1, 0, 5), (G, 3, 4, 1), (G, 2, 0, 4), (G, 1, 0, 5), (B, 3, 0, 1), (G, 3, 0, 4),
+            (B, 1, 0, 5), (B, 3, 1, 1), (B, 2, 0, 4), (R, 2, 0, 5), (B, 3, 2, 1), (R, 3, 0, 5),
+            (B, 3, 3, 1),
+        ],
+    },
+    Bc6hMode {
+        number: 0b01, endpoint_bits: 7, delta_bits: [6, 6, 6], transformed: true,
+        layout: &[
+            (G, 2, 5, 1), (G, 3, 4, 1), (G, 3, 5, 1), (R, 0, 0, 7), (B, 3, 0, 1), (B, 3, 1, 1),
+            (B, 2, 4, 1), (G, 0, 0, 7), (B, 2, 5, 1), (B, 3, 2, 1), (G, 2, 4, 1), (B, 0, 0, 7),
+            (B, 3, 3, 1), (B, 3, 5, 1), (B, 3, 4, 1), (R, 1, 0, 6), (G, 2, 0, 4), (G, 1, 0, 6),
+            (G, 3, 0, 4), (B, 1, 0, 6), (B, 2, 0, 4), (R, 2, 0, 6), (R, 3, 0, 6),
+        ],
+    },
+    Bc6hMode {
+        number: 0b00010, endpoint_bits: 11, delta_bits: [5, 4, 4], transformed: true,
+        layout: &[
+            (R, 0, 0, 10), (G, 0, 0, 10), (B, 0, 0, 10), (R, 1, 0, 5), (R, 0, 10, 1), (G, 2, 0, 4),
+            (G, 1, 0, 4), (G, 0, 10, 1), (B, 3, 0, 1), (G, 3, 0, 4), (B, 1, 0, 4), (B, 0, 10, 1),
+            (B, 3, 1, 1), (B, 2, 0, 4), (R, 2, 0, 5), (B, 3, 2, 1), (R, 3, 0, 5), (B, 3, 3, 1),
+        ],
+    },
+    Bc6hMode {
+        number: 0b00110, endpoint_bits: 11, delta_bits: [4, 5, 4], transformed: true,
+        layout: &[
+            (R, 0, 0, 10), (G, 0, 0, 10), (B, 0, 0, 10), (R, 1, 0, 4), (R, 0, 10, 1), (G, 3, 4, 1),
+            (G, 2, 0, 4), (G, 1, 0, 5), (G, 0, 10, 1), (G, 3, 0, 4), (B, 1, 0, 4), (B, 0, 10, 1),
+            (B, 3, 1, 1), (B, 2, 0, 4), (R, 2, 0, 4), (B, 3, 0, 1), (B, 3, 2, 1), (R, 3, 0, 4),
+            (G, 2, 4, 1), (B, 3, 3, 1),
+        ],
+    },
+    Bc6hMode {
+        number: 0b01010, endpoint_bits: 11, delta_bits: [4, 4, 5], transformed: true,
+        layout: &[
+            (R, 0, 0, 10), (G, 0, 0, 10), (B, 0, 0, 10), (R, 1, 0, 4), (R, 0, 10, 1), (B, 2, 4, 1),
+            (G, 2, 0, 4), (G, 1, 0, 4), (G, 0, 10, 1), (B, 3, 0, 1), (G, 3, 0, 4), (B, 1, 0, 5),
+            (B, 0, 10, 1), (B, 2, 0, 4), (R, 2, 0, 4), (B, 3, 1, 1), (B, 3, 2, 1), (R, 3, 0, 4),
+            (B, 3, 4, 1), (B, 3, 3, 1),
+        ],
+    },
+    Bc6hMode {
+        number: 0b01110, endpoint_bits: 9, delta_bits: [5, 5, 5], transformed: true,
+        layout: &[
+            (R, 0, 0, 9), (B, 2, 4, 1), (G, 0, 0, 9), (G, 2, 4, 1), (B, 0, 0, 9), (B, 3, 4, 1),
+            (R, 1, 0, 5), (G, 3, 4, 1), (G, 2, 0, 4), (G, 1, 0, 5), (B, 3, 0, 1), (G, 3, 0, 4),
+            (B, 1, 0, 5), (B, 3, 1, 1), (B, 2, 0, 4), (R, 2, 0, 5), (B, 3, 2, 1), (R, 3, 0, 5),
+            (B, 3, 3, 1),
+        ],
+    },
+    Bc6hMode {
+        number: 0b10010, endpoint_bits: 8, delta_bits: [6, 5, 5], transformed: true,
+        layout: &[
+            (R, 0, 0, 8), (G, 3, 4, 1), (B, 2, 4, 1), (G, 0, 0, 8), (B, 3, 2, 1), (G, 2, 4, 1),
+            (B, 0, 0, 8), (B, 3, 3, 1), (B, 3, 4, 1), (R, 1, 0, 6), (G, 2, 0, 4), (G, 1, 0, 5),
+            (B, 3, 0, 1), (G, 3, 0, 4), (B, 1, 0, 5), (B, 3, 1, 1), (B, 2, 0, 4), (R, 2, 0, 6),
+            (R, 3, 0, 6),
+        ],
+    },
+    Bc6hMode {
+        number: 0b10110, endpoint_bits: 8, delta_bits: [5, 6, 5], transformed: true,
+        layout: &[
+            (R, 0, 0, 8), (B, 3, 0, 1), (B, 2, 4, 1), (G, 0, 0, 8), (G, 2, 5, 1), (G, 2, 4, 1),
+            (B, 0, 0, 8), (G, 3, 5, 1), (B, 3, 4, 1), (R, 1, 0, 5), (G, 3, 4, 1), (G, 2, 0, 4),
+            (G, 1, 0, 6), (G, 3, 0, 4), (B, 1, 0, 5), (B, 3, 1, 1), (B, 2, 0, 4), (R, 2, 0, 5),
+            (B, 3, 2, 1), (R, 3, 0, 5), (B, 3, 3, 1),
+        ],
+    },
+    Bc6hMode {
+        number: 0b11010, endpoint_bits: 8, delta_bits: [5, 5, 6], transformed: true,
+        layout: &[
+            (R, 0, 0, 8), (B, 3, 1, 1), (B, 2, 4, 1), (G, 0, 0, 8), (B, 2, 5, 1), (G, 2, 4, 1),
+            (B, 0, 0, 8), (B, 3, 5, 1), (B, 3, 4, 1), (R, 1, 0, 5), (G, 3, 4, 1), (G, 2, 0, 4),
+            (G, 1, 0, 5), (B, 3, 0, 1), (G, 3, 0, 4), (B, 1, 0, 6), (B, 2, 0, 4), (R, 2, 0, 5),
+            (B, 3, 2, 1), (R, 3, 0, 5), (B, 3, 3, 1),
+        ],
+    },
+    Bc6hMode {
+        number: 0b11110, endpoint_bits: 6, delta_bits: [6, 6, 6], transformed: false,
+        layout: &[
+            (R, 0, 0, 6), (G, 3, 4, 1), (B, 3, 0, 1), (B, 3, 1, 1), (B, 2, 4, 1), (G, 0, 0, 6),
+            (G, 2, 5, 1), (B, 2, 5, 1), (B, 3, 2, 1), (G, 2, 4, 1), (B, 0, 0, 6), (G, 3, 5, 1),
+            (B, 3, 3, 1), (B, 3, 5, 1), (B, 3, 4, 1), (R, 1, 0, 6), (G, 2, 0, 4), (G, 1, 0, 6),
+            (G, 3, 0, 4), (B, 1, 0, 6), (B, 2, 0, 4), (R, 2, 0, 6), (R, 3, 0, 6),
+        ],
+    },
+    Bc6hMode {
+        number: 0b00011, endpoint_bits: 10, delta_bits: [10, 10, 10], transformed: false,
+        layout: &[
+            (R, 0, 0, 10), (G, 0, 0, 10), (B, 0, 0, 10), (R, 1, 0, 10), (G, 1, 0, 10),
+            (B, 1, 0, 10),
+        ],
+    },
+    Bc6hMode {
+        number: 0b00111, endpoint_bits: 11, delta_bits: [9, 9, 9], transformed: true,
+        layout: &[
+            (R, 0, 0, 10), (G, 0, 0, 10), (B, 0, 0, 10), (R, 1, 0, 9), (R, 0, 10, 1), (G, 1, 0, 9),
+            (G, 0, 10, 1), (B, 1, 0, 9), (B, 0, 10, 1),
+        ],
+    },
+    // The top bits of the last two modes are stored in reverse order
+    Bc6hMode {
+        number: 0b01011, endpoint_bits: 12, delta_bits: [8, 8, 8], transformed: true,
+        layout: &[
+            (R, 0, 0, 10), (G, 0, 0, 10), (B, 0, 0, 10), (R, 1, 0, 8), (R, 0, 11, 1), (R, 0, 10, 1),
+            (G, 1, 0, 8), (G, 0, 11, 1), (G, 0, 10, 1), (B, 1, 0, 8), (B, 0, 11, 1), (B, 0, 10, 1),
+        ],
+    },
+    Bc6hMode {
+        number: 0b01111, endpoint_bits: 16, delta_bits: [4, 4, 4], transformed: true,
+        layout: &[
+            (R, 0, 0, 10), (G, 0, 0, 10), (B, 0, 0, 10), (R, 1, 0, 4), (R, 0, 15, 1), (R, 0, 14, 1),
+            (R, 0, 13, 1), (R, 0, 12, 1), (R, 0, 11, 1), (R, 0, 10, 1), (G, 1, 0, 4), (G, 0, 15, 1),
+            (G, 0, 14, 1), (G, 0, 13, 1), (G, 0, 12, 1), (G, 0, 11, 1), (G, 0, 10, 1), (B, 1, 0, 4),
+            (B, 0, 15, 1), (B, 0, 14, 1), (B, 0, 13, 1), (B, 0, 12, 1), (B, 0, 11, 1),
+            (B, 0, 10, 1),
+        ],
+    },
+];
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+// Scales an endpoint to the 16-bit range interpolation works in
+fn unquantize_bc6h(value: i32, bits: u32, signed: bool) -> i32 {
+    if !signed {
+        match value {
+            _ if bits >= 15 => value,
+            0 => 0,
+            _ if value == (1 << bits) - 1 => 0xFFFF,
+            _ => ((value << 16) + 0x8000) >> bits,
+        }
+    } else {
+        let magnitude = value.abs();
+        let unquantized = match magnitude {
+            _ if bits >= 16 => magnitude,
+            0 => 0,
+            _ if magnitude >= (1 << (bits - 1)) - 1 => 0x7FFF,
+            _ => ((magnitude << 15) + 0x4000) >> (bits - 1),
+        };
+
+        if value < 0 {
+            -unquantized
+        } else {
+            unquantized
+        }
+    }
+}
+
+// Turns an interpolated value into the bits of a half float
+fn finish_bc6h(value: i32, signed: bool) -> u16 {
+    if !signed {
+        ((value * 31) >> 6) as u16
+    } else if value < 0 {
+        (((-value * 31) >> 5) as u16) | 0x8000
+    } else {
+        ((value * 31) >> 5) as u16
+    }
+}
+
+fn half_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = (half >> 10) & 0x1F;
+    let mantissa = (half & 0x3FF) as u32;
+
+    match exponent {
+        0 => sign * mantissa as f32 / (1 << 24) as f32,
+        0x1F if mantissa == 0 => sign * f32::INFINITY,
+        0x1F => f32::NAN,
+        _ => f32::from_bits(
+            ((half as u32 & 0x8000) << 16) | ((exponent as u32 + 112) << 23) | (mantissa << 13),
+        ),
+    }
+}
+
+fn decode_bc6h(block: &[u8], signed: bool) -> [[f32; 3]; 16] {
+    let mut bits = BlockBits::new(block);
+    let mut number = bits.read(2);
+    if number > 1 {
+        number |= bits.read(3) << 2;
+    }
+
+    // Reserved modes decode to black
+    let Some(mode) = BC6H_MODES.iter().find(|mode| mode.number == number) else {
+        return [[0.0; 3]; 16];
+    };
+    let regions = if number & 0b11 == 0b11 { 1 } else { 2 };
+
+    let mut endpoints: Bc6hEndpoints = [[0; 4]; 3];
+    for &(channel, endpoint, first_bit, count) in mode.layout {
+        endpoints[channel][endpoint] |= (bits.read(count) << first_bit) as i32;
+    }
+    let partition = if regions == 2 {
+        bits.read(5) as usize
+    } else {
+        0
+    };
+
+    let endpoint_count = regions * 2;
+    for (channel, values) in endpoints.iter_mut().enumerate() {
+        if signed {
+            values[0] = sign_extend(values[0], mode.endpoint_bits);
+        }
+        let base = values[0];
+
+        // The other endpoints are stored as deltas from the first one
+        for value in values.iter_mut().take(endpoint_count).skip(1) {
+            if mode.transformed {
+                let delta = sign_extend(*value, mode.delta_bits[channel]);
+                *value = (base + delta) & ((1 << mode.endpoint_bits) - 1);
+            }
+            if signed {
+                *value = sign_extend(*value, mode.endpoint_bits);
+            }
+        }
+
+        for value in values.iter_mut().take(endpoint_count) {
+            *value = unquantize_bc6h(*value, mode.endpoint_bits, signed);
+        }
+    }
+
+    let index_bits = if regions == 2 { 3 } else { 4 };
+    let mut indices = [0; 16];
+    for (texel, index) in indices.iter_mut().enumerate() {
+        let anchor = is_anchor(regions, partition, texel);
+        *index = bits.read(index_bits - anchor as u32);
+    }
+
+    std::array::from_fn(|texel| {
+        let region = subset(regions, partition, texel);
+        let weight = weight(index_bits, indices[texel]) as i32;
+
+        [0, 1, 2].map(|channel| {
+            let first = endpoints[channel][region * 2];
+            let second = endpoints[channel][region * 2 + 1];
+            let value = ((64 - weight) * first + weight * second + 32) >> 6;
+            half_to_f32(finish_bc6h(value, signed))
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Packs the fields of a 128-bit block from the least significant bit, the order `BlockBits`
+    // reads them in
+    struct BlockWriter {
+        bits: u128,
+        length: u32,
+    }
+
+    impl BlockWriter {
+        fn new() -> Self {
+            Self { bits: 0, length: 0 }
+        }
+
+        fn write(mut self, count: u32, value: u32) -> Self {
+            assert!(
+                count == 32 || value >> count == 0,
+                "{} does not fit {} bits",
+                value,
+                count
+            );
+            self.bits |= (value as u128) << self.length;
+            self.length += count;
+            self
+        }
+
+        fn finish(self) -> [u8; 16] {
+            assert_eq!(self.length, 128);
+            self.bits.to_le_bytes()
+        }
+    }
+
+    // The 3-bit indices of a BC4 block following its two endpoints
+    fn bc4_block(first: u8, second: u8, indices: [u64; 16]) -> [u8; 8] {
+        let indices = indices
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (texel, index)| bits | (index << (texel * 3)));
+
+        let mut block = [0; 8];
+        block[0] = first;
+        block[1] = second;
+        block[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+        block
+    }
+
+    fn assert_palette(texels: [f32; 16], palette: [f32; 8]) {
+        for (texel, value) in texels.iter().enumerate() {
+            let expected = palette[texel % 8];
+            assert!(
+                (value - expected).abs() < 1e-6,
+                "texel {}: {} != {}",
+                texel,
+                value,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn rgb565_replicates_top_bits() {
+        assert_eq!(rgb565(0xFFFF), [255, 255, 255]);
+        assert_eq!(rgb565(0x8410), [132, 130, 132]);
+        assert_eq!(rgb565(0x0000), [0, 0, 0]);
+    }
+
+    #[test]
+    fn bc1_descending_endpoints_interpolate_four_colors() {
+        // Red and blue endpoints, every row uses indices 0, 1, 2, 3
+        let block = [0x00, 0xF8, 0x1F, 0x00, 0xE4, 0xE4, 0xE4, 0xE4];
+        let row = [
+            [255, 0, 0, 255],
+            [0, 0, 255, 255],
+            [170, 0, 85, 255],
+            [85, 0, 170, 255],
+        ];
+
+        for alpha in [Some(0), Some(255), None] {
+            assert_eq!(decode_bc1(&block, alpha), [row; 4].concat()[..]);
+        }
+    }
+
+    #[test]
+    fn bc1_ascending_endpoints_use_three_colors() {
+        let block = [0x1F, 0x00, 0x00, 0xF8, 0xE4, 0xE4, 0xE4, 0xE4];
+        let row = |alpha| {
+            [
+                [0, 0, 255, 255],
+                [255, 0, 0, 255],
+                [127, 0, 127, 255],
+                [0, 0, 0, alpha],
+            ]
+        };
+
+        assert_eq!(decode_bc1(&block, Some(0)), [row(0); 4].concat()[..]);
+        assert_eq!(decode_bc1(&block, Some(255)), [row(255); 4].concat()[..]);
+
+        // BC2 and BC3 color blocks always have four colors
+        let four_colors = [
+            [0, 0, 255, 255],
+            [255, 0, 0, 255],
+            [85, 0, 170, 255],
+            [170, 0, 85, 255],
+        ];
+        assert_eq!(decode_bc1(&block, None), [four_colors; 4].concat()[..]);
+    }
+
+    #[test]
+    fn bc4_descending_endpoints_interpolate_eight_values() {
+        let indices = std::array::from_fn(|texel| texel as u64 % 8);
+
+        let unsigned = decode_bc4(&bc4_block(255, 0, indices), false);
+        assert_palette(
+            unsigned,
+            [
+                1.0,
+                0.0,
+                6.0 / 7.0,
+                5.0 / 7.0,
+                4.0 / 7.0,
+                3.0 / 7.0,
+                2.0 / 7.0,
+                1.0 / 7.0,
+            ],
+        );
+
+        // -128 is clamped to -127
+        let signed = decode_bc4(&bc4_block(127, 0x80, indices), true);
+        assert_palette(
+            signed,
+            [
+                1.0,
+                -1.0,
+                5.0 / 7.0,
+                3.0 / 7.0,
+                1.0 / 7.0,
+                -1.0 / 7.0,
+                -3.0 / 7.0,
+                -5.0 / 7.0,
+            ],
+        );
+    }
+
+    #[test]
+    fn bc4_ascending_endpoints_add_the_extremes() {
+        let indices = std::array::from_fn(|texel| texel as u64 % 8);
+
+        let unsigned = decode_bc4(&bc4_block(51, 204, indices), false);
+        assert_palette(unsigned, [0.2, 0.8, 0.32, 0.44, 0.56, 0.68, 0.0, 1.0]);
+
+        let signed = decode_bc4(&bc4_block(-127i8 as u8, 127, indices), true);
+        assert_palette(signed, [-1.0, 1.0, -0.6, -0.2, 0.2, 0.6, -1.0, 1.0]);
+    }
+
+    #[test]
+    fn bc7_mode_1_uses_partition_and_shared_p_bits() {
+        // Partition 0 puts the two right columns in the second subset, whose anchor is texel 15
+        let indices = [0, 1, 2, 3, 4, 5, 6, 7, 7, 6, 5, 4, 3, 2, 1, 0];
+        let mut block = BlockWriter::new().write(2, 0b10).write(6, 0);
+        // Subset 0 goes from black to white, subset 1 from red to blue
+        for channel in 0..3 {
+            let red = if channel == 0 { 63 } else { 0 };
+            let blue = if channel == 2 { 63 } else { 0 };
+            block = block.write(6, 0).write(6, 63).write(6, red).write(6, blue);
+        }
+        block = block.write(1, 1).write(1, 0);
+        for (texel, &index) in indices.iter().enumerate() {
+            let anchor = texel == 0 || texel == 15;
+            block = block.write(3 - anchor as u32, index);
+        }
+
+        let texels = decode_bc7(&block.finish());
+        assert_eq!(texels[0], [2, 2, 2, 255]);
+        assert_eq!(texels[1], [38, 38, 38, 255]);
+        assert_eq!(texels[2], [182, 0, 71, 255]);
+        assert_eq!(texels[4], [148, 148, 148, 255]);
+        assert_eq!(texels[7], [0, 0, 253, 255]);
+        assert_eq!(texels[8], [255, 255, 255, 255]);
+        assert_eq!(texels[14], [217, 0, 36, 255]);
+        assert_eq!(texels[15], [253, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc7_mode_4_rotates_alpha_into_red() {
+        let mut block = BlockWriter::new()
+            .write(5, 0b10000)
+            // Rotation 1 swaps red and alpha, index selection 0 keeps the 2-bit indices for color
+            .write(2, 1)
+            .write(1, 0)
+            .write(5, 31)
+            .write(5, 0)
+            .write(5, 0)
+            .write(5, 0)
+            .write(5, 0)
+            .write(5, 31)
+            .write(6, 0)
+            .write(6, 63);
+        for texel in 0..16 {
+            block = block.write(2 - (texel == 0) as u32, texel % 4);
+        }
+        for texel in 0..16 {
+            block = block.write(3 - (texel == 0) as u32, texel % 8);
+        }
+
+        let texels = decode_bc7(&block.finish());
+        assert_eq!(texels[0], [0, 0, 0, 255]);
+        assert_eq!(texels[1], [36, 0, 84, 171]);
+        assert_eq!(texels[2], [72, 0, 171, 84]);
+        assert_eq!(texels[3], [108, 0, 255, 0]);
+        assert_eq!(texels[4], [147, 0, 0, 255]);
+        assert_eq!(texels[7], [255, 0, 255, 0]);
+    }
+
+    #[test]
+    fn bc7_mode_6_interpolates_color_and_alpha() {
+        let mut block = BlockWriter::new()
+            .write(7, 0b1000000)
+            .write(7, 0)
+            .write(7, 127)
+            .write(7, 0)
+            .write(7, 64)
+            .write(7, 0)
+            .write(7, 0)
+            .write(7, 127)
+            .write(7, 0)
+            .write(1, 0)
+            .write(1, 1);
+        for texel in 0..16 {
+            block = block.write(4 - (texel == 0) as u32, texel);
+        }
+
+        let texels = decode_bc7(&block.finish());
+        assert_eq!(texels[0], [0, 0, 0, 254]);
+        assert_eq!(texels[5], [84, 42, 0, 171]);
+        assert_eq!(texels[10], [171, 87, 1, 84]);
+        assert_eq!(texels[15], [255, 129, 1, 1]);
+    }
+
+    #[test]
+    fn bc7_reserved_mode_is_black() {
+        assert_eq!(decode_bc7(&[0; 16]), [[0; 4]; 16]);
+    }
+
+    #[test]
+    fn bc6h_adds_deltas_to_the_first_endpoint() {
+        // Mode 0b00111 has one region with 11-bit endpoints and 9-bit deltas, the top bit of
+        // each first endpoint stored after its delta
+        let [red, green, blue] = [900, 1000, 1029];
+        let [red_delta, green_delta, blue_delta] =
+            [-50i32, 200, 0].map(|delta| delta as u32 & 0x1FF);
+        let mut block = BlockWriter::new()
+            .write(5, 0b00111)
+            .write(10, red & 0x3FF)
+            .write(10, green & 0x3FF)
+            .write(10, blue & 0x3FF)
+            .write(9, red_delta)
+            .write(1, red >> 10)
+            .write(9, green_delta)
+            .write(1, green >> 10)
+            .write(9, blue_delta)
+            .write(1, blue >> 10);
+        for texel in 0..16 {
+            block = block.write(4 - (texel == 0) as u32, texel);
+        }
+
+        let texels = decode_bc6h(&block.finish(), false);
+        assert_eq!(texels[0], [0.407_470_7, 1.1435547, 1.5830078]);
+        assert_eq!(texels[7], [0.31884766, 3.125, 1.5830078]);
+        assert_eq!(texels[15], [0.23413086, 9.3671875, 1.5830078]);
+    }
+
+    #[test]
+    fn decode_blocks_clips_partial_blocks() {
+        // A 5x5 image has 2x2 blocks, each texel records its block and position in the block
+        let texels = decode_blocks(5, 5, &[10, 20, 30, 40], 1, |block| {
+            std::array::from_fn(|texel| [block[0], texel as u8])
+        });
+
+        assert_eq!(texels.len(), 5 * 5 * 2);
+        for y in 0..5 {
+            for x in 0..5 {
+                let block = 10 * (1 + x / 4 + y / 4 * 2) as u8;
+                let texel = (y % 4 * 4 + x % 4) as u8;
+                let offset = (y * 5 + x) * 2;
+                assert_eq!(
+                    texels[offset..offset + 2],
+                    [block, texel],
+                    "texel ({}, {})",
+                    x,
+                    y
+                );
+            }
+        }
+    }
+}
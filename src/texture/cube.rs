@@ -0,0 +1,406 @@
+use std::{marker::PhantomData, mem, path::Path};
+
+use gl::types::*;
+use image::DynamicImage;
+
+use super::{
+    allocate_image, compressed::tex_compressed_image, mip_levels, missing_image, prepare_image,
+    set_bound_texture_storage, tex_image, CompressedFormat, CompressedImage, SamplerDescriptor,
+    Texture, TextureBuilder, TextureError, TextureFilter, TextureFormat, TextureWrap,
+};
+
+/// One of the six faces of a cube map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum CubeFace {
+    PositiveX = gl::TEXTURE_CUBE_MAP_POSITIVE_X,
+    NegativeX = gl::TEXTURE_CUBE_MAP_NEGATIVE_X,
+    PositiveY = gl::TEXTURE_CUBE_MAP_POSITIVE_Y,
+    NegativeY = gl::TEXTURE_CUBE_MAP_NEGATIVE_Y,
+    PositiveZ = gl::TEXTURE_CUBE_MAP_POSITIVE_Z,
+    NegativeZ = gl::TEXTURE_CUBE_MAP_NEGATIVE_Z,
+}
+
+impl CubeFace {
+    /// Every face in the order GL numbers them (right, left, top, bottom, front, back)
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ,
+    ];
+}
+
+/// How the six faces of a cube map are packed into a single image
+///
+/// The crosses unfold the cube around the +Z face:
+///
+/// ```text
+///   horizontal cross      vertical cross
+///
+///         +Y                   +Y
+///     -X  +Z  +X  -Z       -X  +Z  +X
+///         -Y                   -Y
+///                              -Z  (upside down)
+/// ```
+///
+/// Strips hold the faces side by side in [`CubeFace::ALL`] order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CubeLayout {
+    HorizontalCross,
+    VerticalCross,
+    HorizontalStrip,
+    VerticalStrip,
+}
+
+impl CubeLayout {
+    /// Guesses the layout from the aspect ratio of an image
+    pub fn detect(width: u32, height: u32) -> Option<Self> {
+        if width == 0 || height == 0 {
+            None
+        } else if width * 3 == height * 4 {
+            Some(CubeLayout::HorizontalCross)
+        } else if width * 4 == height * 3 {
+            Some(CubeLayout::VerticalCross)
+        } else if width == height * 6 {
+            Some(CubeLayout::HorizontalStrip)
+        } else if height == width * 6 {
+            Some(CubeLayout::VerticalStrip)
+        } else {
+            None
+        }
+    }
+
+    // The size of the layout in faces
+    fn grid(&self) -> (u32, u32) {
+        match self {
+            CubeLayout::HorizontalCross => (4, 3),
+            CubeLayout::VerticalCross => (3, 4),
+            CubeLayout::HorizontalStrip => (6, 1),
+            CubeLayout::VerticalStrip => (1, 6),
+        }
+    }
+
+    // The column and row holding a face, and whether it is stored upside down
+    fn cell(&self, face: CubeFace) -> (u32, u32, bool) {
+        let index = CubeFace::ALL.iter().position(|&f| f == face).unwrap() as u32;
+
+        match (self, face) {
+            (CubeLayout::HorizontalStrip, _) => (index, 0, false),
+            (CubeLayout::VerticalStrip, _) => (0, index, false),
+            (_, CubeFace::PositiveY) => (1, 0, false),
+            (_, CubeFace::NegativeX) => (0, 1, false),
+            (_, CubeFace::PositiveZ) => (1, 1, false),
+            (_, CubeFace::PositiveX) => (2, 1, false),
+            (_, CubeFace::NegativeY) => (1, 2, false),
+            (CubeLayout::HorizontalCross, CubeFace::NegativeZ) => (3, 1, false),
+            (CubeLayout::VerticalCross, CubeFace::NegativeZ) => (1, 3, true),
+        }
+    }
+
+    /// Cuts the faces out of an image, in [`CubeFace::ALL`] order
+    pub fn split(&self, img: &DynamicImage) -> Vec<DynamicImage> {
+        let (columns, rows) = self.grid();
+        let size = (img.width() / columns).min(img.height() / rows);
+
+        CubeFace::ALL
+            .iter()
+            .map(|&face| {
+                let (column, row, upside_down) = self.cell(face);
+                let face = img.crop_imm(column * size, row * size, size, size);
+
+                if upside_down {
+                    face.rotate180()
+                } else {
+                    face
+                }
+            })
+            .collect()
+    }
+}
+
+/// An owned cube map texture, deleted when dropped
+///
+/// Cube maps are sampled with a direction instead of a coordinate. Filtering across faces needs
+/// `GL_TEXTURE_CUBE_MAP_SEAMLESS` to be enabled, which main.rs does once at startup.
+pub struct TextureCube {
+    id: GLuint,
+    size: u32,
+    levels: u32,
+    format: TextureFormat,
+    compression: Option<CompressedFormat>,
+    sampler: SamplerDescriptor,
+    // Textures belong to the context's thread
+    _not_send: PhantomData<*const ()>,
+}
+
+impl TextureCube {
+    /// Loads a cross or strip image (see [`CubeLayout`]) as sRGB color with clamped edges
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, TextureError> {
+        TextureBuilder::new()
+            .srgb(true)
+            .wrap(TextureWrap::ClampToEdge)
+            .load_cube(path)
+    }
+
+    /// Loads six face images in [`CubeFace::ALL`] order as sRGB color with clamped edges
+    pub fn from_faces<P: AsRef<Path>>(paths: &[P]) -> Result<Self, TextureError> {
+        TextureBuilder::new()
+            .srgb(true)
+            .wrap(TextureWrap::ClampToEdge)
+            .load_cube_faces(paths)
+    }
+
+    /// The missing texture checkerboard on every face
+    pub fn missing() -> Self {
+        TextureBuilder::new()
+            .filters(TextureFilter::Nearest, TextureFilter::Nearest)
+            .mipmaps(false)
+            .upload_cube(&vec![missing_image(); 6])
+            .expect("The missing texture is always uploadable")
+    }
+
+    /// Creates a cube map without contents, for rendering into
+    ///
+    /// Every face gets `levels` mip levels, to be rendered or generated separately.
+    pub fn allocate(
+        size: u32,
+        format: TextureFormat,
+        levels: u32,
+        sampler: SamplerDescriptor,
+    ) -> Self {
+        let levels = levels.clamp(1, mip_levels(size, size));
+        let mut texture: u32 = 0;
+
+        unsafe {
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, texture);
+
+            sampler.apply_to_bound_texture(gl::TEXTURE_CUBE_MAP);
+            set_bound_texture_storage(gl::TEXTURE_CUBE_MAP, format, levels);
+
+            for level in 0..levels {
+                let level_size = (size >> level).max(1);
+                for face in CubeFace::ALL {
+                    allocate_image(face as GLenum, level, format, level_size, level_size);
+                }
+            }
+        }
+
+        Self {
+            id: texture,
+            size,
+            levels,
+            format,
+            compression: None,
+            sampler,
+            _not_send: PhantomData,
+        }
+    }
+
+    pub(super) fn upload(
+        faces: &[DynamicImage],
+        format: Option<TextureFormat>,
+        sampler: SamplerDescriptor,
+        mipmaps: bool,
+    ) -> Result<Self, TextureError> {
+        if faces.len() != CubeFace::ALL.len() {
+            return Err(TextureError::InvalidCubeMap {
+                message: format!("expected 6 faces, got {}", faces.len()),
+            });
+        }
+
+        let size = faces[0].width();
+        for (face, img) in CubeFace::ALL.iter().zip(faces) {
+            if img.width() != size || img.height() != size {
+                return Err(TextureError::InvalidCubeMap {
+                    message: format!(
+                        "face {:?} is {}x{}, expected {}x{} like the first face",
+                        face,
+                        img.width(),
+                        img.height(),
+                        size,
+                        size
+                    ),
+                });
+            }
+        }
+
+        let max_size = max_cube_map_size();
+        if size > max_size {
+            return Err(TextureError::TooLarge {
+                width: size,
+                height: size,
+                max: max_size,
+            });
+        }
+
+        // Every face is stored in the format picked for the first one
+        let (format, first) = prepare_image(&faces[0], format)?;
+        let mut prepared = vec![first];
+        for img in &faces[1..] {
+            prepared.push(prepare_image(img, Some(format))?.1);
+        }
+
+        let levels = if mipmaps { mip_levels(size, size) } else { 1 };
+        let mut texture: u32 = 0;
+
+        unsafe {
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, texture);
+
+            sampler.apply_to_bound_texture(gl::TEXTURE_CUBE_MAP);
+            set_bound_texture_storage(gl::TEXTURE_CUBE_MAP, format, levels);
+
+            for (face, img) in CubeFace::ALL.iter().zip(&prepared) {
+                tex_image(*face as GLenum, format, img);
+            }
+            if mipmaps {
+                gl::GenerateMipmap(gl::TEXTURE_CUBE_MAP);
+            }
+        }
+
+        Ok(Self {
+            id: texture,
+            size,
+            levels,
+            format,
+            compression: None,
+            sampler,
+            _not_send: PhantomData,
+        })
+    }
+
+    pub(super) fn upload_compressed(
+        image: &CompressedImage,
+        format: CompressedFormat,
+        sampler: SamplerDescriptor,
+        mipmaps: bool,
+    ) -> Result<Self, TextureError> {
+        if image.faces() != 6 || image.width() != image.height() {
+            return Err(TextureError::InvalidCubeMap {
+                message: format!(
+                    "expected 6 square faces, got {} of {}x{}",
+                    image.faces(),
+                    image.width(),
+                    image.height()
+                ),
+            });
+        }
+
+        let size = image.width();
+        let max_size = max_cube_map_size();
+        if size > max_size {
+            return Err(TextureError::TooLarge {
+                width: size,
+                height: size,
+                max: max_size,
+            });
+        }
+
+        let mut texture: u32 = 0;
+        let face_targets = CubeFace::ALL.map(|face| face as GLenum);
+        let upload = unsafe {
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, texture);
+
+            sampler.apply_to_bound_texture(gl::TEXTURE_CUBE_MAP);
+            tex_compressed_image(gl::TEXTURE_CUBE_MAP, &face_targets, image, format, mipmaps)
+        };
+
+        Ok(Self {
+            id: texture,
+            size,
+            levels: upload.levels,
+            format: upload.format,
+            compression: upload.compression,
+            sampler,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// The width and height of every face
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// The number of mip levels of every face
+    pub fn levels(&self) -> u32 {
+        self.levels
+    }
+
+    /// The storage format, or for block compressed cube maps the uncompressed format with the
+    /// same channels
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    /// The block compression the faces are stored with, see [`Texture2d::compressed_format`]
+    ///
+    /// [`Texture2d::compressed_format`]: super::Texture2d::compressed_format
+    pub fn compressed_format(&self) -> Option<CompressedFormat> {
+        self.compression
+    }
+
+    /// Fills every level below the first from the first one
+    pub fn generate_mipmaps(&self) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.id);
+            gl::GenerateMipmap(gl::TEXTURE_CUBE_MAP);
+        }
+    }
+
+    pub fn bind_texture(&self) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.id);
+        }
+    }
+
+    /// Gives up ownership of the texture without deleting it
+    pub fn leak(mut self) -> GLuint {
+        mem::take(&mut self.id)
+    }
+
+    /// The texture's own sampling state (used when no [`Sampler`](super::Sampler) is bound to
+    /// its unit)
+    pub fn sampler(&self) -> &SamplerDescriptor {
+        &self.sampler
+    }
+
+    pub fn set_sampler(&mut self, sampler: SamplerDescriptor) {
+        self.sampler = sampler;
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.id);
+            self.sampler.apply_to_bound_texture(gl::TEXTURE_CUBE_MAP);
+        }
+    }
+}
+
+/// The largest face size the driver accepts for cube maps (`GL_MAX_CUBE_MAP_TEXTURE_SIZE`)
+pub fn max_cube_map_size() -> u32 {
+    let mut size: GLint = 0;
+    unsafe { gl::GetIntegerv(gl::MAX_CUBE_MAP_TEXTURE_SIZE, &mut size) };
+
+    size.max(0) as u32
+}
+
+impl Texture for TextureCube {
+    fn target(&self) -> GLenum {
+        gl::TEXTURE_CUBE_MAP
+    }
+
+    unsafe fn id(&self) -> GLuint {
+        self.id
+    }
+}
+
+impl Drop for TextureCube {
+    fn drop(&mut self) {
+        if self.id != 0 {
+            unsafe { gl::DeleteTextures(1, &self.id) };
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::{marker::PhantomData, mem};
+
+use gl::types::*;
+
+use super::{TextureFilter, TextureUnit, TextureWrap};
+
+/// How a texture is sampled: wrapping, filtering, border color and LOD bias
+///
+/// Applied either to a texture's own sampling state or to a standalone [`Sampler`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerDescriptor {
+    pub wrap_s: TextureWrap,
+    pub wrap_t: TextureWrap,
+    pub wrap_r: TextureWrap,
+    pub min_filter: TextureFilter,
+    pub mag_filter: TextureFilter,
+    /// Used with `TextureWrap::ClampToBorder`
+    pub border_color: [f32; 4],
+    pub lod_bias: f32,
+}
+
+impl Default for SamplerDescriptor {
+    fn default() -> Self {
+        Self {
+            wrap_s: TextureWrap::Repeat,
+            wrap_t: TextureWrap::Repeat,
+            wrap_r: TextureWrap::Repeat,
+            min_filter: TextureFilter::LinearMipmapLinear,
+            mag_filter: TextureFilter::Linear,
+            border_color: [0.0; 4],
+            lod_bias: 0.0,
+        }
+    }
+}
+
+impl SamplerDescriptor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the wrap mode of every axis
+    pub fn wrap(mut self, wrap: TextureWrap) -> Self {
+        self.wrap_s = wrap;
+        self.wrap_t = wrap;
+        self.wrap_r = wrap;
+        self
+    }
+
+    pub fn filters(mut self, min_filter: TextureFilter, mag_filter: TextureFilter) -> Self {
+        self.min_filter = min_filter;
+        self.mag_filter = mag_filter;
+        self
+    }
+
+    pub fn border_color(mut self, border_color: [f32; 4]) -> Self {
+        self.border_color = border_color;
+        self
+    }
+
+    pub fn lod_bias(mut self, lod_bias: f32) -> Self {
+        self.lod_bias = lod_bias;
+        self
+    }
+
+    /// Whether the minification filter reads from mipmap levels
+    pub fn uses_mipmaps(&self) -> bool {
+        !matches!(
+            self.min_filter,
+            TextureFilter::Nearest | TextureFilter::Linear
+        )
+    }
+
+    // Textures and sampler objects take the same parameters through different functions
+    fn apply(
+        &self,
+        mut set_int: impl FnMut(GLenum, GLint),
+        mut set_float: impl FnMut(GLenum, GLfloat),
+        set_floats: impl FnOnce(GLenum, &[GLfloat; 4]),
+    ) {
+        set_int(gl::TEXTURE_WRAP_S, self.wrap_s as GLint);
+        set_int(gl::TEXTURE_WRAP_T, self.wrap_t as GLint);
+        set_int(gl::TEXTURE_WRAP_R, self.wrap_r as GLint);
+        set_int(gl::TEXTURE_MIN_FILTER, self.min_filter as GLint);
+        set_int(gl::TEXTURE_MAG_FILTER, self.mag_filter as GLint);
+        set_float(gl::TEXTURE_LOD_BIAS, self.lod_bias);
+        set_floats(gl::TEXTURE_BORDER_COLOR, &self.border_color);
+    }
+
+    /// Applies the state to the texture currently bound to `target`
+    ///
+    /// # Safety
+    /// A texture must be bound to `target` on the active unit
+    pub unsafe fn apply_to_bound_texture(&self, target: GLenum) {
+        self.apply(
+            |parameter, value| gl::TexParameteri(target, parameter, value),
+            |parameter, value| gl::TexParameterf(target, parameter, value),
+            |parameter, values| gl::TexParameterfv(target, parameter, values.as_ptr()),
+        );
+    }
+}
+
+/// A GL sampler object, overriding the sampling state of whatever texture is on its unit
+pub struct Sampler {
+    id: GLuint,
+    descriptor: SamplerDescriptor,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Sampler {
+    pub fn new(descriptor: &SamplerDescriptor) -> Self {
+        let mut id: u32 = 0;
+
+        unsafe {
+            gl::GenSamplers(1, &mut id);
+        }
+
+        let sampler = Self {
+            id,
+            descriptor: *descriptor,
+            _not_send: PhantomData,
+        };
+        sampler.apply();
+
+        sampler
+    }
+
+    pub fn descriptor(&self) -> &SamplerDescriptor {
+        &self.descriptor
+    }
+
+    pub fn set_descriptor(&mut self, descriptor: &SamplerDescriptor) {
+        self.descriptor = *descriptor;
+        self.apply();
+    }
+
+    pub fn bind(&self, unit: TextureUnit) {
+        unsafe {
+            gl::BindSampler(unit.0, self.id);
+        }
+    }
+
+    /// Restores the texture's own sampling state on a unit
+    pub fn unbind(unit: TextureUnit) {
+        unsafe {
+            gl::BindSampler(unit.0, 0);
+        }
+    }
+
+    pub unsafe fn id(&self) -> GLuint {
+        self.id
+    }
+
+    /// Gives up ownership of the sampler without deleting it
+    pub fn leak(mut self) -> GLuint {
+        mem::take(&mut self.id)
+    }
+
+    fn apply(&self) {
+        let id = self.id;
+
+        unsafe {
+            self.descriptor.apply(
+                |parameter, value| gl::SamplerParameteri(id, parameter, value),
+                |parameter, value| gl::SamplerParameterf(id, parameter, value),
+                |parameter, values| gl::SamplerParameterfv(id, parameter, values.as_ptr()),
+            );
+        }
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        if self.id != 0 {
+            unsafe { gl::DeleteSamplers(1, &self.id) };
+        }
+    }
+}
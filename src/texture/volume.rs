@@ -0,0 +1,222 @@
+use std::{marker::PhantomData, mem, path::Path, ptr};
+
+use gl::types::*;
+use image::DynamicImage;
+
+use super::{
+    allocate_layers, check_layers, mip_levels, prepare_image, set_bound_texture_storage, tex_layer,
+    SamplerDescriptor, Texture, TextureBuilder, TextureError, TextureFormat,
+};
+
+/// An owned 3D texture, deleted when dropped
+///
+/// Sampled with `sampler3D` and a `vec3` coordinate, filtering between slices as well as within
+/// them. Used for volume data and color grading lookup tables.
+pub struct Texture3d {
+    id: GLuint,
+    width: u32,
+    height: u32,
+    depth: u32,
+    format: TextureFormat,
+    sampler: SamplerDescriptor,
+    // Textures belong to the context's thread
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Texture3d {
+    /// Loads one slice per image, see [`TextureBuilder::load_3d`]
+    pub fn new<P: AsRef<Path>>(paths: &[P]) -> Result<Self, TextureError> {
+        TextureBuilder::new().load_3d(paths)
+    }
+
+    pub(super) fn upload(
+        slices: &[DynamicImage],
+        format: Option<TextureFormat>,
+        sampler: SamplerDescriptor,
+        mipmaps: bool,
+    ) -> Result<Self, TextureError> {
+        let (width, height) = check_layers(slices)?;
+        let depth = slices.len() as u32;
+        check_size(width, height, depth)?;
+
+        // Every slice is stored in the format picked for the first one
+        let (format, first) = prepare_image(&slices[0], format)?;
+        let mut prepared = vec![first];
+        for img in &slices[1..] {
+            prepared.push(prepare_image(img, Some(format))?.1);
+        }
+
+        let texture = Self::create(
+            width,
+            height,
+            depth,
+            format,
+            sampler,
+            mipmaps,
+            |target| unsafe {
+                allocate_layers(target, format, width, height, depth, ptr::null());
+                for (slice, img) in prepared.iter().enumerate() {
+                    tex_layer(target, slice as u32, format, img);
+                }
+            },
+        );
+
+        Ok(texture)
+    }
+
+    /// Uploads tightly packed texels, `width` first and `depth` last, in the layout of `format`
+    pub(super) fn upload_raw(
+        data: &[u8],
+        [width, height, depth]: [u32; 3],
+        format: TextureFormat,
+        sampler: SamplerDescriptor,
+        mipmaps: bool,
+    ) -> Result<Self, TextureError> {
+        check_size(width, height, depth)?;
+
+        let texture = Self::create(
+            width,
+            height,
+            depth,
+            format,
+            sampler,
+            mipmaps,
+            |target| unsafe {
+                allocate_layers(target, format, width, height, depth, data.as_ptr().cast());
+            },
+        );
+
+        Ok(texture)
+    }
+
+    // Creates the texture object and lets `fill` upload the first level
+    fn create(
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: TextureFormat,
+        sampler: SamplerDescriptor,
+        mipmaps: bool,
+        fill: impl FnOnce(GLenum),
+    ) -> Self {
+        let levels = if mipmaps {
+            mip_levels(width.max(height), depth)
+        } else {
+            1
+        };
+        let mut texture: u32 = 0;
+
+        unsafe {
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_3D, texture);
+
+            sampler.apply_to_bound_texture(gl::TEXTURE_3D);
+            set_bound_texture_storage(gl::TEXTURE_3D, format, levels);
+
+            fill(gl::TEXTURE_3D);
+            if mipmaps {
+                gl::GenerateMipmap(gl::TEXTURE_3D);
+            }
+        }
+
+        Self {
+            id: texture,
+            width,
+            height,
+            depth,
+            format,
+            sampler,
+            _not_send: PhantomData,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn bind_texture(&self) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_3D, self.id);
+        }
+    }
+
+    /// Gives up ownership of the texture without deleting it
+    pub fn leak(mut self) -> GLuint {
+        mem::take(&mut self.id)
+    }
+
+    /// The texture's own sampling state (used when no [`Sampler`](super::Sampler) is bound to
+    /// its unit)
+    pub fn sampler(&self) -> &SamplerDescriptor {
+        &self.sampler
+    }
+
+    pub fn set_sampler(&mut self, sampler: SamplerDescriptor) {
+        self.sampler = sampler;
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_3D, self.id);
+            self.sampler.apply_to_bound_texture(gl::TEXTURE_3D);
+        }
+    }
+}
+
+fn check_size(width: u32, height: u32, depth: u32) -> Result<(), TextureError> {
+    let max_size = max_3d_texture_size();
+
+    if width > max_size || height > max_size {
+        return Err(TextureError::TooLarge {
+            width,
+            height,
+            max: max_size,
+        });
+    }
+
+    if depth > max_size {
+        return Err(TextureError::TooManyLayers {
+            layers: depth,
+            max: max_size,
+        });
+    }
+
+    Ok(())
+}
+
+/// The largest width, height or depth the driver accepts for 3D textures
+/// (`GL_MAX_3D_TEXTURE_SIZE`)
+pub fn max_3d_texture_size() -> u32 {
+    let mut size: GLint = 0;
+    unsafe { gl::GetIntegerv(gl::MAX_3D_TEXTURE_SIZE, &mut size) };
+
+    size.max(0) as u32
+}
+
+impl Texture for Texture3d {
+    fn target(&self) -> GLenum {
+        gl::TEXTURE_3D
+    }
+
+    unsafe fn id(&self) -> GLuint {
+        self.id
+    }
+}
+
+impl Drop for Texture3d {
+    fn drop(&mut self) {
+        if self.id != 0 {
+            unsafe { gl::DeleteTextures(1, &self.id) };
+        }
+    }
+}
@@ -0,0 +1,404 @@
+use std::{collections::HashMap, marker::PhantomData, sync::RwLock};
+
+use gl::types::*;
+use nalgebra_glm as glm;
+
+use crate::buffer::{Buffer, BufferTarget, BufferUsage};
+
+/// A type with a std140 memory layout that can be written into a uniform buffer
+///
+/// Implemented for scalars, glm vectors and matrices and arrays of them. Structs can implement it
+/// with the [`std140_struct!`] macro.
+pub trait Std140 {
+    /// Base alignment of the type in bytes
+    const ALIGNMENT: usize;
+    /// Size of the type in bytes, including any trailing padding
+    const SIZE: usize;
+
+    /// Appends exactly `SIZE` bytes to the buffer, assuming it is already aligned
+    fn write_std140(&self, buffer: &mut Vec<u8>);
+}
+
+pub const fn align_up(offset: usize, alignment: usize) -> usize {
+    offset.div_ceil(alignment) * alignment
+}
+
+/// Pads the buffer with zeros until the data written since `start` is a multiple of `alignment`
+pub fn pad_to(buffer: &mut Vec<u8>, start: usize, alignment: usize) {
+    let length = align_up(buffer.len() - start, alignment);
+    buffer.resize(start + length, 0);
+}
+
+macro_rules! impl_std140_scalar {
+    ($type:ty, |$value:ident| $bytes:expr) => {
+        impl Std140 for $type {
+            const ALIGNMENT: usize = 4;
+            const SIZE: usize = 4;
+
+            fn write_std140(&self, buffer: &mut Vec<u8>) {
+                let $value = *self;
+                buffer.extend_from_slice(&$bytes);
+            }
+        }
+    };
+}
+
+impl_std140_scalar!(f32, |value| value.to_ne_bytes());
+impl_std140_scalar!(i32, |value| value.to_ne_bytes());
+impl_std140_scalar!(u32, |value| value.to_ne_bytes());
+impl_std140_scalar!(bool, |value| (value as u32).to_ne_bytes());
+
+// vec2 is aligned to 8 bytes, vec3 and vec4 to 16
+macro_rules! impl_std140_vector {
+    ($type:ty, $components:expr) => {
+        impl Std140 for $type {
+            const ALIGNMENT: usize = if $components == 2 { 8 } else { 16 };
+            const SIZE: usize = 4 * $components;
+
+            fn write_std140(&self, buffer: &mut Vec<u8>) {
+                for component in self.iter() {
+                    component.write_std140(buffer);
+                }
+            }
+        }
+    };
+}
+
+impl_std140_vector!(glm::Vec2, 2);
+impl_std140_vector!(glm::Vec3, 3);
+impl_std140_vector!(glm::Vec4, 4);
+impl_std140_vector!(glm::IVec2, 2);
+impl_std140_vector!(glm::IVec3, 3);
+impl_std140_vector!(glm::IVec4, 4);
+impl_std140_vector!(glm::UVec2, 2);
+impl_std140_vector!(glm::UVec3, 3);
+impl_std140_vector!(glm::UVec4, 4);
+impl_std140_vector!(glm::BVec2, 2);
+impl_std140_vector!(glm::BVec3, 3);
+impl_std140_vector!(glm::BVec4, 4);
+
+// Matrices are stored as an array of column vectors, each padded out to 16 bytes
+macro_rules! impl_std140_matrix {
+    ($type:ty, $rows:expr, $columns:expr) => {
+        impl Std140 for $type {
+            const ALIGNMENT: usize = 16;
+            const SIZE: usize = 16 * $columns;
+
+            fn write_std140(&self, buffer: &mut Vec<u8>) {
+                for column in self.column_iter() {
+                    let start = buffer.len();
+
+                    for component in column.iter() {
+                        component.write_std140(buffer);
+                    }
+
+                    pad_to(buffer, start, 16);
+                }
+            }
+        }
+    };
+}
+
+impl_std140_matrix!(glm::Mat2, 2, 2);
+impl_std140_matrix!(glm::Mat3, 3, 3);
+impl_std140_matrix!(glm::Mat4, 4, 4);
+impl_std140_matrix!(glm::Mat2x3, 2, 3);
+impl_std140_matrix!(glm::Mat3x2, 3, 2);
+impl_std140_matrix!(glm::Mat2x4, 2, 4);
+impl_std140_matrix!(glm::Mat4x2, 4, 2);
+impl_std140_matrix!(glm::Mat3x4, 3, 4);
+impl_std140_matrix!(glm::Mat4x3, 4, 3);
+
+// Array elements are padded out to a multiple of 16 bytes
+impl<T: Std140, const N: usize> Std140 for [T; N] {
+    const ALIGNMENT: usize = align_up(T::ALIGNMENT, 16);
+    const SIZE: usize = align_up(T::SIZE, 16) * N;
+
+    fn write_std140(&self, buffer: &mut Vec<u8>) {
+        for element in self {
+            let start = buffer.len();
+            element.write_std140(buffer);
+            pad_to(buffer, start, 16);
+        }
+    }
+}
+
+/// Declares a struct and implements [`Std140`] for it, laying out its fields by the std140 rules
+///
+/// ```ignore
+/// std140_struct! {
+///     pub struct CameraBlock {
+///         pub view: glm::Mat4,
+///         pub projection: glm::Mat4,
+///         pub position: glm::Vec3,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! std140_struct {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident: $type:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy)]
+        $vis struct $name {
+            $($field_vis $field: $type),*
+        }
+
+        impl $crate::uniform_buffer::Std140 for $name {
+            const ALIGNMENT: usize = {
+                let mut alignment = 16;
+                $(
+                    let field_alignment = <$type as $crate::uniform_buffer::Std140>::ALIGNMENT;
+                    if field_alignment > alignment {
+                        alignment = field_alignment;
+                    }
+                )*
+                $crate::uniform_buffer::align_up(alignment, 16)
+            };
+
+            const SIZE: usize = {
+                let mut offset = 0;
+                $(
+                    offset = $crate::uniform_buffer::align_up(
+                        offset,
+                        <$type as $crate::uniform_buffer::Std140>::ALIGNMENT,
+                    ) + <$type as $crate::uniform_buffer::Std140>::SIZE;
+                )*
+                $crate::uniform_buffer::align_up(offset, Self::ALIGNMENT)
+            };
+
+            fn write_std140(&self, buffer: &mut Vec<u8>) {
+                let start = buffer.len();
+                $(
+                    $crate::uniform_buffer::pad_to(
+                        buffer,
+                        start,
+                        <$type as $crate::uniform_buffer::Std140>::ALIGNMENT,
+                    );
+                    $crate::uniform_buffer::Std140::write_std140(&self.$field, buffer);
+                )*
+                $crate::uniform_buffer::pad_to(buffer, start, Self::ALIGNMENT);
+            }
+        }
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockBinding {
+    pub binding: GLuint,
+    pub size: usize,
+}
+
+lazy_static::lazy_static! {
+    // Uniform block name -> binding point, applied to every program as it is linked
+    static ref BLOCK_BINDINGS: RwLock<HashMap<String, BlockBinding>> = RwLock::new(HashMap::new());
+}
+
+/// Looks up the binding point registered for a uniform block name
+pub fn block_binding(name: &str) -> Option<BlockBinding> {
+    BLOCK_BINDINGS.read().unwrap().get(name).copied()
+}
+
+fn register_block(name: &str, block: BlockBinding) {
+    BLOCK_BINDINGS
+        .write()
+        .unwrap()
+        .insert(name.to_owned(), block);
+}
+
+// Removes a registration unless the name was registered again for another binding point since
+fn unregister_block(name: &str, binding: GLuint) {
+    let Ok(mut bindings) = BLOCK_BINDINGS.write() else {
+        return;
+    };
+
+    if bindings
+        .get(name)
+        .is_some_and(|registered| registered.binding == binding)
+    {
+        bindings.remove(name);
+    }
+}
+
+/// A uniform buffer holding one `T`, bound to a binding point shared by every shader program
+///
+/// Creating the buffer registers `block_name` so any program declaring
+/// `layout (std140) uniform <block_name>` that is linked afterwards gets bound to it
+/// automatically, until the buffer is dropped. Registration only happens at link time, so create
+/// uniform buffers before the programs that use them; programs linked earlier print a warning and
+/// have to be bound with `Shader::bind_uniform_block`.
+pub struct UniformBuffer<T: Std140> {
+    buffer: Buffer,
+    block_name: String,
+    binding: GLuint,
+    bytes: Vec<u8>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Std140> UniformBuffer<T> {
+    pub fn new(block_name: &str, binding: GLuint) -> Self {
+        let buffer = Buffer::new(BufferTarget::Uniform);
+        buffer.allocate(T::SIZE, BufferUsage::Dynamic);
+
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, buffer.id());
+        }
+
+        register_block(
+            block_name,
+            BlockBinding {
+                binding,
+                size: T::SIZE,
+            },
+        );
+
+        Self {
+            buffer,
+            block_name: block_name.to_owned(),
+            binding,
+            bytes: Vec::with_capacity(T::SIZE),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn binding(&self) -> GLuint {
+        self.binding
+    }
+
+    /// Writes the value into the buffer using its std140 layout
+    pub fn upload(&mut self, value: &T) {
+        self.bytes.clear();
+        value.write_std140(&mut self.bytes);
+        debug_assert_eq!(self.bytes.len(), T::SIZE);
+
+        self.buffer.set_sub_data(0, &self.bytes);
+
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+    }
+}
+
+impl<T: Std140> Drop for UniformBuffer<T> {
+    fn drop(&mut self) {
+        // Programs linked from now on must not be bound to a binding point nothing fills
+        unregister_block(&self.block_name, self.binding);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::std140_struct! {
+        struct Vec3ThenFloat {
+            direction: glm::Vec3,
+            intensity: f32,
+        }
+    }
+
+    crate::std140_struct! {
+        struct Inner {
+            value: f32,
+        }
+    }
+
+    crate::std140_struct! {
+        struct Outer {
+            first: f32,
+            inner: Inner,
+            offset: glm::Vec2,
+            last: f32,
+        }
+    }
+
+    fn bytes<T: Std140>(value: &T) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        value.write_std140(&mut buffer);
+        assert_eq!(buffer.len(), T::SIZE);
+
+        buffer
+    }
+
+    fn f32_at(buffer: &[u8], offset: usize) -> f32 {
+        f32::from_ne_bytes(buffer[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn float_packs_after_vec3() {
+        let buffer = bytes(&Vec3ThenFloat {
+            direction: glm::vec3(1.0, 2.0, 3.0),
+            intensity: 4.0,
+        });
+
+        assert_eq!(Vec3ThenFloat::SIZE, 16);
+        assert_eq!(
+            [0, 4, 8, 12].map(|offset| f32_at(&buffer, offset)),
+            [1.0, 2.0, 3.0, 4.0]
+        );
+    }
+
+    #[test]
+    fn float_arrays_have_a_16_byte_stride() {
+        let buffer = bytes(&[1.0f32, 2.0, 3.0]);
+
+        assert_eq!(<[f32; 3]>::ALIGNMENT, 16);
+        assert_eq!(<[f32; 3]>::SIZE, 48);
+        assert_eq!(
+            [0, 16, 32].map(|offset| f32_at(&buffer, offset)),
+            [1.0, 2.0, 3.0]
+        );
+        assert!(buffer[4..16].iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn mat3_columns_are_padded_to_vec4() {
+        let matrix = glm::mat3(1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0);
+        let buffer = bytes(&matrix);
+
+        assert_eq!(glm::Mat3::SIZE, 48);
+        for column in 0..3 {
+            let offset = column * 16;
+            let values = [0, 4, 8].map(|row| f32_at(&buffer, offset + row));
+
+            assert_eq!(values, [1.0, 4.0, 7.0].map(|row| row + column as f32));
+            assert_eq!(f32_at(&buffer, offset + 12), 0.0);
+        }
+    }
+
+    #[test]
+    fn nested_structs_are_aligned_to_16_bytes() {
+        let buffer = bytes(&Outer {
+            first: 1.0,
+            inner: Inner { value: 2.0 },
+            offset: glm::vec2(3.0, 4.0),
+            last: 5.0,
+        });
+
+        assert_eq!(Inner::SIZE, 16);
+        assert_eq!(Outer::ALIGNMENT, 16);
+        assert_eq!(Outer::SIZE, 48);
+        assert_eq!(
+            [0, 16, 32, 36, 40].map(|offset| f32_at(&buffer, offset)),
+            [1.0, 2.0, 3.0, 4.0, 5.0]
+        );
+    }
+
+    #[test]
+    fn unregistering_keeps_newer_registrations() {
+        let block = |binding| BlockBinding { binding, size: 16 };
+
+        register_block("TestBlock", block(3));
+        unregister_block("TestBlock", 3);
+        assert_eq!(block_binding("TestBlock"), None);
+
+        register_block("TestBlock", block(4));
+        unregister_block("TestBlock", 3);
+        assert_eq!(block_binding("TestBlock"), Some(block(4)));
+    }
+}